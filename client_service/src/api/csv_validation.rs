@@ -0,0 +1,170 @@
+use std::fmt;
+
+use cs_interface::{CsvSchema, FixedPointEncoding, NetAccess};
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A problem found in an uploaded csv file.
+#[derive(Object, Serialize, Debug, PartialEq)]
+pub struct CsvError {
+    /// Line in the file, starting at 1 with the header
+    pub row: usize,
+    /// Column in the row, starting at 1. Missing if the whole row is affected
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "row {}, column {}: {}", self.row, column, self.message),
+            None => write!(f, "row {}: {}", self.row, self.message),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct CollaborationSchema {
+    csv_schema: CsvSchema,
+}
+
+/// Get the csv schema of the collaboration.
+pub async fn get_csv_schema(coord_uri: &str, collab_id: i32, net: &impl NetAccess) -> Result<CsvSchema> {
+    let url = format!("{}/collaboration/{}", coord_uri, collab_id);
+    let bytes = net.get(&url).await?;
+    Ok(serde_json::from_slice::<CollaborationSchema>(&bytes)?.csv_schema)
+}
+
+fn split_row(row: &str) -> Vec<&str> {
+    row.trim_end_matches('\r').split(',').map(|c| c.trim()).collect()
+}
+
+/// Check the csv against the schema of the collaboration and return all encoded values row by row.
+///
+/// All errors of the file are collected, so they can be fixed at once.
+pub fn validate(csv: &str, schema: &CsvSchema, encoding: &FixedPointEncoding) -> Result<Vec<String>> {
+    let header_line = schema.header_line();
+    let expected = split_row(&header_line);
+    let mut rows = csv.split('\n').enumerate().map(|(i, row)| (i + 1, row));
+    let mut errors = Vec::new();
+
+    let header = rows.next().map(|(_, row)| split_row(row)).unwrap_or_default();
+    if header != expected {
+        errors.push(CsvError {
+            row: 1,
+            column: None,
+            message: format!("header '{}' does not match '{}'", header.join(","), header_line),
+        });
+    }
+
+    let mut values = Vec::new();
+    for (row, line) in rows.filter(|(_, line)| !line.trim().is_empty()) {
+        let cells = split_row(line);
+        if cells.len() != expected.len() {
+            errors.push(CsvError {
+                row,
+                column: None,
+                message: format!("expected {} columns, got {}", expected.len(), cells.len()),
+            });
+            continue;
+        }
+        for (column, (cell, spec)) in cells.iter().zip(&schema.columns).enumerate() {
+            match spec.encode(cell, encoding) {
+                Ok(value) => values.push(value.to_string()),
+                Err(err) => errors.push(CsvError {
+                    row,
+                    column: Some(column + 1),
+                    message: match err {
+                        cs_interface::Error::InvalidNumber(message) => message,
+                        err => err.to_string(),
+                    },
+                }),
+            }
+        }
+    }
+    if errors.is_empty() && values.is_empty() {
+        errors.push(CsvError {
+            row: 1,
+            column: None,
+            message: "file contains no data rows".to_string(),
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        Err(Error::InvalidCsv { errors })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cs_interface::MockNetAccess;
+
+    use super::*;
+
+    fn errors(res: Result<Vec<String>>) -> Vec<CsvError> {
+        match res {
+            Err(Error::InvalidCsv { errors }) => errors,
+            other => panic!("expected csv errors, got {:?}", other),
+        }
+    }
+
+    fn schema(json: &str) -> CsvSchema {
+        CsvSchema::from_json(json).unwrap()
+    }
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let schema = schema(r#"{"columns":[{"name":"age","type":"int"},{"name":"income","type":"int"}]}"#);
+        let values = validate("age, income\r\n30,1000\n-4,2\n\n", &schema, &FixedPointEncoding::integer())?;
+        assert_eq!(values, vec!["30", "1000", "-4", "2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_typed_columns() -> Result<()> {
+        let schema = schema(r#"{"columns":[
+            {"name":"x","type":"fixed_point"},
+            {"name":"smoker","type":"boolean","nullable":true},
+            {"name":"region","type":"categorical","values":["north","south"]}]}"#);
+        let values = validate("x,smoker,region\n0.5,true,south\n-1,,north", &schema, &FixedPointEncoding::new(2).unwrap())?;
+        assert_eq!(values, vec!["2", "1", "1", "-4", "0", "0"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let schema = schema(r#"{"columns":[{"name":"age","type":"int","max":120},{"name":"income","type":"int"}]}"#);
+        let res = validate("age,salary\n30\n31,abc\n1.5,2\n121,", &schema, &FixedPointEncoding::integer());
+        assert_eq!(errors(res), vec![
+            CsvError { row: 1, column: None, message: "header 'age,salary' does not match 'age,income'".to_string() },
+            CsvError { row: 2, column: None, message: "expected 2 columns, got 1".to_string() },
+            CsvError { row: 3, column: Some(2), message: "'abc' is not a valid int value".to_string() },
+            CsvError { row: 4, column: Some(1), message: "'1.5' is not a valid int value".to_string() },
+            CsvError { row: 5, column: Some(1), message: "121 is out of range [, 120]".to_string() },
+            CsvError { row: 5, column: Some(2), message: "value is required".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_validate_empty() {
+        let schema = schema(r#"{"columns":[{"name":"data","type":"int"}]}"#);
+        let res = validate("data\n", &schema, &FixedPointEncoding::integer());
+        assert_eq!(errors(res)[0].message, "file contains no data rows");
+    }
+
+    #[tokio::test]
+    async fn test_get_csv_schema() -> Result<()> {
+        let mut net = MockNetAccess::new();
+        net.expect_get()
+            .times(1)
+            .returning(|_| Ok(r#"{"id":1,"csv_schema":{"columns":[{"name":"age","type":"int"}]}}"#.as_bytes().to_vec()))
+            .withf(|url| url == "http://coordinator/collaboration/1");
+        let schema = get_csv_schema("http://coordinator", 1, &net).await?;
+        assert_eq!(schema.header_line(), "age");
+        Ok(())
+    }
+}
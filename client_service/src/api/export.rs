@@ -0,0 +1,192 @@
+use std::{env, fs, path::PathBuf, sync::Arc};
+
+use cs_interface::{CarbynestackConfig, ClearTextSecret, FixedPointEncoding, NativeCsClient, NetAccess};
+use serde::Serialize;
+use tracing::{event, Level};
+
+use super::{result::{self, ResultResponse}, utils};
+use crate::{error::{Error, Result}, notification_store::{NotificationStore, RetrievalState}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    /// One row per value: `secret_id,index,value,decimal_value`
+    Csv,
+}
+
+/// Where retrieved results are written to
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultSink {
+    /// A file `collaboration_{id}.{json|csv}` in the directory
+    Directory { path: PathBuf, format: ExportFormat },
+    /// The results are posted as json to the url
+    Http { url: String },
+}
+
+impl ResultSink {
+    fn describe(&self, collab_id: i32) -> String {
+        match self {
+            ResultSink::Directory { path, format } => file_path(path, *format, collab_id).display().to_string(),
+            ResultSink::Http { url } => url.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportBody<'a> {
+    collaboration_id: i32,
+    results: &'a [ClearTextSecret],
+}
+
+/// Sinks results are exported to once a notification arrives, read from the environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportConfig {
+    pub sinks: Vec<ResultSink>,
+}
+
+impl ExportConfig {
+    /// `None` if neither `RESULT_EXPORT_DIR` nor `RESULT_EXPORT_URL` is set.
+    pub fn from_env() -> Result<Option<ExportConfig>> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        let mut sinks = Vec::new();
+        if let Some(dir) = var("RESULT_EXPORT_DIR") {
+            let format = match var("RESULT_EXPORT_FORMAT").as_deref() {
+                None | Some("json") => ExportFormat::Json,
+                Some("csv") => ExportFormat::Csv,
+                Some(other) => return Err(Error::from(format!("unknown RESULT_EXPORT_FORMAT '{}'", other))),
+            };
+            sinks.push(ResultSink::Directory { path: PathBuf::from(dir), format });
+        }
+        if let Some(url) = var("RESULT_EXPORT_URL") {
+            sinks.push(ResultSink::Http { url });
+        }
+        Ok(if sinks.is_empty() { None } else { Some(ExportConfig { sinks }) })
+    }
+}
+
+fn file_path(dir: &std::path::Path, format: ExportFormat, collab_id: i32) -> PathBuf {
+    let extension = match format {
+        ExportFormat::Json => "json",
+        ExportFormat::Csv => "csv",
+    };
+    dir.join(format!("collaboration_{}.{}", collab_id, extension))
+}
+
+fn to_csv(results: &[ClearTextSecret]) -> String {
+    let mut csv = "secret_id,index,value,decimal_value\n".to_string();
+    for secret in results {
+        for (index, value) in secret.values.iter().enumerate() {
+            let decimal = secret
+                .decimal_values
+                .as_ref()
+                .and_then(|values| values.get(index))
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            csv.push_str(&format!("{},{},{},{}\n", secret.secret_id, index, value, decimal));
+        }
+    }
+    csv
+}
+
+/// Write the results of a collaboration to a sink.
+pub async fn export_results(collab_id: i32, results: &[ClearTextSecret], sink: &ResultSink, net: &impl NetAccess) -> Result<()> {
+    let body = ExportBody { collaboration_id: collab_id, results };
+    match sink {
+        ResultSink::Directory { path, format } => {
+            fs::create_dir_all(path)?;
+            let content = match format {
+                ExportFormat::Json => serde_json::to_string_pretty(&body)?,
+                ExportFormat::Csv => to_csv(results),
+            };
+            // write to a temporary file first, so readers never see a partial export
+            let target = file_path(path, *format, collab_id);
+            let tmp = target.with_extension("tmp");
+            fs::write(&tmp, content)?;
+            fs::rename(&tmp, &target)?;
+        }
+        ResultSink::Http { url } => {
+            net.post(url, serde_json::to_string(&body)?).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Fetch the results of a collaboration and write them to all configured sinks, tracking the state in the store.
+pub async fn retrieve_and_export(coord_uri: String, collab_id: i32, config: ExportConfig, store: Arc<NotificationStore>) {
+    let res = retrieve(&coord_uri, collab_id, &config).await;
+    let update = match res {
+        Ok(exported_to) => {
+            event!(Level::INFO, "results of collaboration {} exported to {}", collab_id, exported_to.join(", "));
+            store.set_retrieval(collab_id, RetrievalState::Fetched, None, exported_to)
+        }
+        Err(err) => {
+            event!(Level::WARN, "unable to export the results of collaboration {}: {}", collab_id, err);
+            store.set_retrieval(collab_id, RetrievalState::Failed, Some(err.to_string()), vec![])
+        }
+    };
+    if let Err(err) = update {
+        event!(Level::ERROR, "unable to store the retrieval state of collaboration {}: {}", collab_id, err);
+    }
+}
+
+async fn retrieve(coord_uri: &str, collab_id: i32, config: &ExportConfig) -> Result<Vec<String>> {
+    let net = utils::coordinator_client(coord_uri);
+    let cs_config = CarbynestackConfig::get_from_coordinator(coord_uri, collab_id, &net).await?;
+    let encoding = FixedPointEncoding::get_from_coordinator(coord_uri, collab_id, &net).await?;
+    let client = NativeCsClient::new(cs_config)?;
+    let ResultResponse::ComputationResult(results) = result::result(coord_uri, collab_id, 0, &encoding, &client, &net).await?;
+    let mut exported_to = Vec::new();
+    for sink in &config.sinks {
+        export_results(collab_id, &results.0, sink, &net).await?;
+        exported_to.push(sink.describe(collab_id));
+    }
+    Ok(exported_to)
+}
+
+#[cfg(test)]
+mod test {
+    use cs_interface::MockNetAccess;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn results() -> Vec<ClearTextSecret> {
+        vec![ClearTextSecret {
+            secret_id: "a".to_string(),
+            values: vec![3, -2],
+            decimal_values: Some(vec![1.5, -1.0]),
+            tags: vec![],
+            creation_date: None,
+            game_id: None,
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_export_to_directory() -> Result<()> {
+        let dir = TempDir::new("export")?;
+        let net = MockNetAccess::new();
+        for format in [ExportFormat::Json, ExportFormat::Csv] {
+            let sink = ResultSink::Directory { path: dir.path().to_path_buf(), format };
+            export_results(7, &results(), &sink, &net).await?;
+        }
+        let csv = fs::read_to_string(dir.path().join("collaboration_7.csv"))?;
+        assert_eq!(csv, "secret_id,index,value,decimal_value\na,0,3,1.5\na,1,-2,-1\n");
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path().join("collaboration_7.json"))?)?;
+        assert_eq!(json["collaborationId"], 7);
+        assert_eq!(json["results"][0]["values"], serde_json::json!([3, -2]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_to_http() -> Result<()> {
+        let mut net = MockNetAccess::new();
+        net.expect_post()
+            .times(1)
+            .returning(|_, _| Ok(vec![]))
+            .withf(|url, body| url == "http://downstream/results" && body.contains(r#""collaborationId":7"#));
+        let sink = ResultSink::Http { url: "http://downstream/results".to_string() };
+        export_results(7, &results(), &sink, &net).await?;
+        Ok(())
+    }
+}
@@ -1,14 +1,17 @@
 use poem::web;
 use poem_openapi::{
-    param::Path , payload::Json, Object, OpenApi
+    param::{Header, Path}, payload::{Binary, Json}, Object, OpenApi
 };
-use cs_interface::{CarbynestackConfig, JavaCsClient};
-use cs_interface::RequestsClient;
-use crate::{error::Result};
+use cs_interface::{CarbynestackConfig, FixedPointEncoding, NativeCsClient};
+use std::sync::Arc;
+
+use crate::{error::Result, notification_store::{NotificationStore, RetrievalState}};
 mod secrets;
+pub(crate) mod csv_validation;
 mod secret_shares;
 mod result;
 mod notify;
+pub mod export;
 mod utils;
 mod sys_status;
 
@@ -40,10 +43,12 @@ impl Api {
         /// csv of secrets
         payload: secrets::UploadPayload
     ) -> Result<secrets::UploadResponse> {
-        let net = RequestsClient::new();
+        let net = utils::coordinator_client(coord_uri.0);
         let config = CarbynestackConfig::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
-        let client = JavaCsClient::new(config)?;
-        secrets::upload(collab_id.0, party_id.0, payload, &client, &net).await
+        let encoding = FixedPointEncoding::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
+        let schema = csv_validation::get_csv_schema(coord_uri.0, collab_id.0, &net).await?;
+        let client = NativeCsClient::new(config)?;
+        secrets::upload(collab_id.0, party_id.0, payload, &schema, &encoding, &client, &net).await
     }
 
     /// get secret by secret ids.
@@ -54,10 +59,11 @@ impl Api {
         collab_id: Path<i32>,
         /// identifiers of secrets to get
         secret_id: Path<String>) -> Result<secrets::GetSecretResponse> {
-        let net = RequestsClient::new();
+        let net = utils::coordinator_client(coord_uri.0);
         let config = CarbynestackConfig::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
-        let client = JavaCsClient::new(config)?;
-        secrets::get(secret_id.0, &client).await
+        let encoding = FixedPointEncoding::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
+        let client = NativeCsClient::new(config)?;
+        secrets::get(secret_id.0, &encoding, &client).await
     }
 
     /// list secrets
@@ -66,9 +72,9 @@ impl Api {
         coord_uri: web::Data<&String>,
         /// identifier of collaboration
          collab_id: Path<i32>) -> Result<secrets::ListSecretsResponse> {
-        let net = RequestsClient::new();
+        let net = utils::coordinator_client(coord_uri.0);
         let config = CarbynestackConfig::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
-        let client = JavaCsClient::new(config)?;
+        let client = NativeCsClient::new(config)?;
         secrets::list_secrets(&client).await
     }
 
@@ -80,9 +86,9 @@ impl Api {
         collab_id: Path<i32>,
         /// identifiers of secrets to remove
         secret_ids: Json<Vec<String>>) -> Result<secrets::DelSecretResp> {
-        let net = RequestsClient::new();
+        let net = utils::coordinator_client(coord_uri.0);
         let config = CarbynestackConfig::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
-        let client = JavaCsClient::new(config)?;
+        let client = NativeCsClient::new(config)?;
         secrets::delete(secret_ids.0, &client).await
     }
 
@@ -94,31 +100,64 @@ impl Api {
          collab_id: Path<i32>, 
         /// identifier of the output party
          party_id: Path<i32>) -> Result<result::ResultResponse> {
-        let net = RequestsClient::new();
+        let net = utils::coordinator_client(coord_uri.0);
         let config = CarbynestackConfig::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
-        let client = JavaCsClient::new(config)?;
-        result::result(coord_uri.0, collab_id.0, party_id.0, &client, &net).await
+        let encoding = FixedPointEncoding::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
+        let client = NativeCsClient::new(config)?;
+        result::result(coord_uri.0, collab_id.0, party_id.0, &encoding, &client, &net).await
     }
 
-    /// notify client that results are finished.
+    /// notify client that results are finished. The body is a `NotifyBody` signed by the coordinator.
+    /// If a result export is configured, the results are retrieved and exported in the background.
     #[oai(path = "/notify", method = "put")]
-    async fn notify(&self, 
+    async fn notify(&self,
+        /// unix time the notification was signed at
+        #[oai(name = "X-Notification-Timestamp")]
+        timestamp: Header<Option<String>>,
+        /// `sha256=` followed by the hex encoded HMAC of `{timestamp}.{body}`
+        #[oai(name = "X-Notification-Signature")]
+        signature: Header<Option<String>>,
+        /// json of a `NotifyBody`, kept as is to check the signature
+        notification: Binary<Vec<u8>>,
+        coord_uri: web::Data<&String>,
+        store: web::Data<&Arc<NotificationStore>>,
+        export: web::Data<&Option<export::ExportConfig>>) -> Result<notify::NotifyResponse> {
+        let headers = notify::SignatureHeaders { timestamp: timestamp.0, signature: signature.0 };
+        let available = notify::notify(&notification.0, headers, utils::notification_secret().as_deref(), store.0)?;
+        if let (Some(collab_id), Some(config)) = (available, export.0.clone()) {
+            store.0.set_retrieval(collab_id, RetrievalState::Pending, None, vec![])?;
+            tokio::spawn(export::retrieve_and_export(coord_uri.0.clone(), collab_id, config, store.0.clone()));
+        }
+        Ok(notify::NotifyResponse::NotificationAccepted)
+    }
+
+    /// List the received result notifications of all collaborations.
+    #[oai(path = "/notifications", method = "get")]
+    async fn list_notifications(&self,
+        store: web::Data<&Arc<NotificationStore>>) -> Result<notify::ListNotificationsResponse> {
+        Ok(notify::list(store.0))
+    }
+
+    /// Get the received result notifications of a collaboration.
+    #[oai(path = "/notifications/:collab_id", method = "get")]
+    async fn get_notifications(&self,
         /// identifier of collaboration
-        notification: Json<notify::NotifyBody>) -> Result<notify::NotifyResponse> {
-        notify::notify(notification.0)
+        collab_id: Path<i32>,
+        store: web::Data<&Arc<NotificationStore>>) -> Result<notify::GetNotificationsResponse> {
+        notify::get(collab_id.0, store.0)
     }
 
-    /// Get secret shares.
+    /// Get secret shares and the value reconstructed from them.
     #[oai(path = "/secret_shares/:collab_id/:secret_id", method = "get")]
     async fn get_secret_shares(&self, 
         coord_uri: web::Data<&String>,
         collab_id: Path<i32>, 
         secret_id: Path<String>) -> Result<secret_shares::GetSecretShareResponse> {
 
-        let net = RequestsClient::new();
+        let net = utils::coordinator_client(coord_uri.0);
         let config = CarbynestackConfig::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
-        let client = JavaCsClient::new(config)?;
-        secret_shares::get_secret_share(secret_id.0, &client).await
+        let encoding = FixedPointEncoding::get_from_coordinator(coord_uri.0, collab_id.0, &net).await?;
+        secret_shares::get_secret_share(secret_id.0, &config, &encoding, &net).await
     }
 
     /// Returns status code 200. Used to check if service is available.
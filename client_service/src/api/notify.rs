@@ -1,8 +1,17 @@
-use crate::error::{Error, Result};
-use poem_openapi::{ApiResponse, Object};
+use std::{collections::HashMap, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{error::{Error, Result}, notification_store::{CollaborationNotifications, NotificationStore}};
+use cs_interface::signature;
+use lazy_static::lazy_static;
+use poem_openapi::{payload::Json, ApiResponse, Object};
 use serde::Deserialize;
 use tracing::{event, Level};
 
+lazy_static! {
+    /// Signatures of accepted notifications and the time they stop being accepted, to reject replays until then
+    static ref SEEN_SIGNATURES: Mutex<HashMap<String, i64>> = Mutex::new(HashMap::new());
+}
+
 #[derive(Object, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[oai(rename_all = "camelCase")]
@@ -10,7 +19,8 @@ pub struct NotifyBody {
     pub message: String,
     pub code: i32,
     pub collaboration_id: i32,
-    pub secret_id: String
+    /// Missing in notices of failed computations
+    pub secret_id: Option<String>
 }
 
 #[derive(ApiResponse, PartialEq, Debug)]
@@ -20,43 +30,149 @@ pub enum NotifyResponse {
     NotificationAccepted,
 }
 
-pub fn notify(notification: NotifyBody) -> Result<NotifyResponse> {
-    if notification.code != 200 {
-        return Err(Error::Unprocessable { message: "Not waiting for a notification".to_string() });
+/// Headers the coordinator signs a notification with
+pub struct SignatureHeaders {
+    pub timestamp: Option<String>,
+    pub signature: Option<String>,
+}
+
+/// Accept a notification signed with `secret` and keep it in the `store`, the body is the json of a `NotifyBody`.
+///
+/// Notices of failed computations are kept as well, they do not make the result available.
+/// Returns the id of the collaboration if its result is available.
+pub fn notify(body: &[u8], headers: SignatureHeaders, secret: Option<&str>, store: &NotificationStore) -> Result<Option<i32>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+    verify(body, &headers, secret, now)?;
+    let notification = serde_json::from_slice::<NotifyBody>(body).map_err(|err| Error::Unprocessable { message: err.to_string() })?;
+    let succeeded = notification.code == 200;
+    if succeeded {
+        event!(Level::INFO, "A result notification was received for collaboration {}.", notification.collaboration_id);
+    } else {
+        event!(Level::WARN, "The computation of collaboration {} failed: {}", notification.collaboration_id, notification.message);
+    }
+    store.add(notification.collaboration_id, notification.message, notification.code, notification.secret_id)?;
+    Ok(succeeded.then_some(notification.collaboration_id))
+}
+
+#[derive(ApiResponse)]
+pub enum ListNotificationsResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<CollaborationNotifications>>),
+}
+
+pub fn list(store: &NotificationStore) -> ListNotificationsResponse {
+    ListNotificationsResponse::Ok(Json(store.list()))
+}
+
+#[derive(ApiResponse)]
+pub enum GetNotificationsResponse {
+    #[oai(status = 200)]
+    Ok(Json<CollaborationNotifications>),
+}
+
+/// Notifications of a collaboration, not found if none was received yet.
+pub fn get(collab_id: i32, store: &NotificationStore) -> Result<GetNotificationsResponse> {
+    store
+        .get(collab_id)
+        .map(|notifications| GetNotificationsResponse::Ok(Json(notifications)))
+        .ok_or(Error::CollaborationNotFound { collab_id })
+}
+
+fn verify(body: &[u8], headers: &SignatureHeaders, secret: Option<&str>, now: i64) -> Result<()> {
+    let Some(secret) = secret else {
+        return Err(Error::Unauthorized { message: "NOTIFICATION_SECRET is not configured".to_string() });
+    };
+    let (Some(timestamp), Some(signature)) = (&headers.timestamp, &headers.signature) else {
+        return Err(Error::Unauthorized { message: "notification is not signed".to_string() });
+    };
+    signature::verify(secret, timestamp, signature, body, now)?;
+    // the window is counted from the signed timestamp, which may be ahead of our clock
+    let signed_at = timestamp.trim().parse::<i64>().unwrap_or(now);
+
+    let mut seen = SEEN_SIGNATURES.lock().unwrap();
+    seen.retain(|_, expires_at| now <= *expires_at);
+    if seen.insert(signature.clone(), signed_at + signature::MAX_AGE_SECONDS).is_some() {
+        return Err(Error::Unauthorized { message: "notification was already received".to_string() });
     }
-    event!(Level::INFO, "A result notification was received for collaboration {}.", notification.collaboration_id);
-    Ok(NotifyResponse::NotificationAccepted)
+    Ok(())
 }
 
 #[cfg(test)]
 mod notify_tests {
     use super::*;
 
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn signed(body: &str, timestamp: i64) -> SignatureHeaders {
+        SignatureHeaders {
+            timestamp: Some(timestamp.to_string()),
+            signature: Some(signature::sign(SECRET, timestamp, body.as_bytes())),
+        }
+    }
+
+    fn now() -> i64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+    }
+
     #[test]
     fn test_notify() {
-        let res = notify(NotifyBody{
-            message: "Hello World!".to_string(),
-            code: 200,
-            collaboration_id: 0,
-            secret_id: "-".to_string()
-        });
-        if let Ok(resp) = res {
-            assert_eq!(resp, NotifyResponse::NotificationAccepted);
-        }
+        let store = NotificationStore::in_memory();
+        let body = r#"{"message":"Hello World!","code":200,"collaborationId":0,"secretId":"-"}"#;
+        let res = notify(body.as_bytes(), signed(body, now()), Some(SECRET), &store);
+        assert_eq!(res.unwrap(), Some(0));
+        assert!(store.get(0).unwrap().result_available);
+        assert!(matches!(get(1, &store), Err(Error::CollaborationNotFound { collab_id: 1 })));
     }
 
     #[test]
     fn test_notify_failing() {
-        let res = notify(NotifyBody{
-            message: "Hello World!".to_string(),
-            code: 400,
-            collaboration_id: 0,
-            secret_id: "-".to_string()
-        });
-        if let Err(Error::Unprocessable { message: _}) = res {
-            assert!(true);
-        } else {
-            assert!(false, "Expected unprocessable response");
+        let store = NotificationStore::in_memory();
+        let body = r#"{"message":"Computation failed","code":500,"collaborationId":4}"#;
+        let res = notify(body.as_bytes(), signed(body, now()), Some(SECRET), &store);
+        assert_eq!(res.unwrap(), None);
+        let stored = store.get(4).unwrap();
+        assert!(!stored.result_available);
+        assert_eq!(stored.notifications[0].message, "Computation failed");
+        assert_eq!(stored.notifications[0].secret_id, None);
+
+        let garbage = r#"{"code":500}"#;
+        let res = notify(garbage.as_bytes(), signed(garbage, now()), Some(SECRET), &store);
+        assert!(matches!(res, Err(Error::Unprocessable { .. })), "{:?}", res);
+    }
+
+    #[test]
+    fn test_notify_rejected() {
+        let body = r#"{"message":"Hello World!","code":200,"collaborationId":1,"secretId":"-"}"#;
+        let forged = r#"{"message":"Hello World!","code":200,"collaborationId":2,"secretId":"-"}"#;
+        let unsigned = SignatureHeaders { timestamp: None, signature: None };
+        for res in [
+            notify(body.as_bytes(), signed(body, now()), None, &NotificationStore::in_memory()),
+            notify(body.as_bytes(), unsigned, Some(SECRET), &NotificationStore::in_memory()),
+            notify(forged.as_bytes(), signed(body, now()), Some(SECRET), &NotificationStore::in_memory()),
+            notify(body.as_bytes(), signed(body, now() - 2 * signature::MAX_AGE_SECONDS), Some(SECRET), &NotificationStore::in_memory()),
+        ] {
+            assert!(matches!(res, Err(Error::Unauthorized { .. })), "{:?}", res);
         }
     }
+
+    #[test]
+    fn test_notify_replayed() {
+        let body = r#"{"message":"replay","code":200,"collaborationId":3,"secretId":"-"}"#;
+        let timestamp = now();
+        assert!(notify(body.as_bytes(), signed(body, timestamp), Some(SECRET), &NotificationStore::in_memory()).is_ok());
+        let res = notify(body.as_bytes(), signed(body, timestamp), Some(SECRET), &NotificationStore::in_memory());
+        assert!(matches!(res, Err(Error::Unauthorized { .. })), "{:?}", res);
+    }
+
+    #[test]
+    fn test_replay_of_future_timestamp() {
+        let body = r#"{"message":"replay","code":200,"collaborationId":5,"secretId":"-"}"#;
+        let signed_at = now() + signature::MAX_AGE_SECONDS;
+        let headers = || signed(body, signed_at);
+        assert!(verify(body.as_bytes(), &headers(), Some(SECRET), now()).is_ok());
+        // still within the window of the signed timestamp, long after it was first received
+        let later = now() + 2 * signature::MAX_AGE_SECONDS - 1;
+        let res = verify(body.as_bytes(), &headers(), Some(SECRET), later);
+        assert!(matches!(res, Err(Error::Unauthorized { .. })), "{:?}", res);
+    }
 }
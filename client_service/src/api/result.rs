@@ -1,4 +1,4 @@
-use cs_interface::{ClearTextSecret, CsClient, NetAccess};
+use cs_interface::{ClearTextSecret, CsClient, FixedPointEncoding, NetAccess};
 use poem_openapi::{payload::Json, types::ParseFromJSON, ApiResponse};
 use crate::{error::{Error, Result}};
 use tracing::{event, Level};
@@ -10,11 +10,12 @@ pub enum ResultResponse {
     ComputationResult(Json<Vec<ClearTextSecret>>),
 }
 
-pub async fn result(coord_uri: &str, collab_id: i32, _party_id: i32, cs_client: &impl CsClient, net: &impl NetAccess) -> Result<ResultResponse> {
+pub async fn result(coord_uri: &str, collab_id: i32, _party_id: i32, encoding: &FixedPointEncoding, cs_client: &impl CsClient, net: &impl NetAccess) -> Result<ResultResponse> {
     let result_ids = get_result_ids(coord_uri, collab_id, net).await?;
     let mut secrets: Vec<ClearTextSecret> = vec![];
     for id in result_ids {
-        let res = cs_client.get_secret(&id)?;
+        let mut res = cs_client.get_secret(&id).await?;
+        res.decode_fixed_point(encoding);
         secrets.push(res);
     }
 
@@ -89,16 +90,22 @@ mod test {
             .times(1)
             .returning(|_| {
                 Ok(ClearTextSecret {
-                    result: "asdf".to_string(),
-                    // creation_date: None,
-                    // game_id: None,
+                    secret_id: "asdf".to_string(),
+                    values: vec![1, -2],
+                    decimal_values: None,
+                    tags: Vec::new(),
+                    creation_date: None,
+                    game_id: Some("game".to_string()),
                 })
             });
-        let ResultResponse::ComputationResult(res) = result("http://coordinator",1, 1, &client, &net).await?;
+        let encoding = FixedPointEncoding::new(1)?;
+        let ResultResponse::ComputationResult(res) = result("http://coordinator",1, 1, &encoding, &client, &net).await?;
         net.checkpoint();
         client.checkpoint();
         assert_eq!(res.0.len(), 1);
-        assert_eq!(res.0[0].result, "asdf");
+        assert_eq!(res.0[0].values, vec![1, -2]);
+        assert_eq!(res.0[0].decimal_values, Some(vec![0.5, -1.0]));
+        assert_eq!(res.0[0].game_id.as_deref(), Some("game"));
         Ok(())
     }
 
@@ -131,7 +138,7 @@ mod test {
         let mut client = MockCsClient::new();
         client.expect_get_secret()
             .times(0);
-        let res = result("http://coordinator",1, 1, &client, &net).await;
+        let res = result("http://coordinator",1, 1, &FixedPointEncoding::integer(), &client, &net).await;
         assert_err!(res);
         net.checkpoint();
         client.checkpoint();
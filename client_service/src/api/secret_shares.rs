@@ -1,10 +1,9 @@
-use cs_interface::CsClient;
+use cs_interface::{reconstruct_secret, CarbynestackConfig, ClearTextSecret, FixedPointEncoding, NetAccess};
 use poem_openapi::{payload::Json, ApiResponse, Object};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{event, Level};
 use uuid::Uuid;
-use crate::error::{Error, Result};
+use crate::error::Result;
 
 #[derive(Serialize, Deserialize, Object)]
 #[oai(rename_all = "camelCase")]
@@ -31,7 +30,10 @@ struct SecretShare {
 
 #[derive(Serialize, Object)]
 pub struct GetSecretSharesResult {
-    result: Vec<Option<SecretShare>>,
+    /// Raw shares of each provider, in provider order
+    result: Vec<SecretShare>,
+    /// Value reconstructed from the shares after checking their integrity
+    secret: ClearTextSecret,
 }
 
 #[derive(ApiResponse)]
@@ -41,44 +43,23 @@ pub enum GetSecretShareResponse {
     OK(Json<GetSecretSharesResult>),
 }
 
-pub async fn get_secret_share(secret_id: String, cs_client: &impl CsClient) -> Result<GetSecretShareResponse> {
-    //let config = get_config(collab_id, &RequestsClient::new()).await?;
-
-    let comp_parties = cs_client.get_comp_party_urls();
+/// Download the shares of a secret from all providers and reconstruct its value.
+///
+/// Fails with an integrity error if the shares of a provider do not match the others.
+pub async fn get_secret_share(secret_id: String, config: &CarbynestackConfig, encoding: &FixedPointEncoding, net: &impl NetAccess) -> Result<GetSecretShareResponse> {
+    // all providers have to answer the same request id to hand out matching masks
     let req_uuid = Uuid::new_v4();
-    let _resp = Client::new()
-        .get(format!("{}/amphora/secret-shares/{}?requestId={}", comp_parties[0], secret_id, req_uuid))
-        .header("accepts", "application/json")
-        .send().await;
-    let mut resp_arr: Vec<Option<SecretShare>> = comp_parties.iter().map(|_| None).collect();
-    for (i, comp_party) in comp_parties.iter().enumerate().rev() {
-        //let comp_party = comp_parties[x];
-        let resp_p = Client::new()
-            .get(format!("{}/amphora/secret-shares/{}?requestId={}", comp_party, secret_id, req_uuid))
-            .header("accepts", "application/json")
-            .send().await;
-        match resp_p {
-            Ok(resp) => {
-                let status = resp.status();
-                let body = resp.bytes().await?.to_vec();
-                let s = String::from_utf8_lossy(&body);
-                if status.is_success() {
-                    event!(Level::INFO, "Shares retrieved from computation instance {}.", i);
-                    let data: SecretShare = serde_json::from_str(s.to_string().as_str())?;
-                    resp_arr[i] = Some(data);
-                } else {
-                    event!(Level::ERROR, "Err: {}", s);
-                    return Err(Error::InternalServerError { message: s.to_string() })
-                }
-            },
-            Err(e) => {
-                event!(Level::ERROR, "Err: {}", e);
-                return Err(Error::InternalServerError { message: e.to_string()} );
-            }
-        }
+    let mut responses = Vec::new();
+    for (i, provider) in config.providers.iter().enumerate() {
+        let url = format!("{}/secret-shares/{}?requestId={}", provider.amphora_service_url.trim_end_matches('/'), secret_id, req_uuid);
+        responses.push(net.get(&url).await?);
+        event!(Level::INFO, "Shares retrieved from computation instance {}.", i);
     }
-    let resp = GetSecretSharesResult {
-        result: resp_arr
-    };
-    Ok(GetSecretShareResponse::OK(Json(resp)))
+    let mut secret = reconstruct_secret(config, &secret_id, &responses)?;
+    secret.decode_fixed_point(encoding);
+    let shares = responses
+        .iter()
+        .map(|bytes| serde_json::from_slice::<SecretShare>(bytes))
+        .collect::<serde_json::Result<Vec<SecretShare>>>()?;
+    Ok(GetSecretShareResponse::OK(Json(GetSecretSharesResult { result: shares, secret })))
 }
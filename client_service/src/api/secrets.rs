@@ -1,9 +1,9 @@
-use cs_interface::{ClearTextSecret, CsClient, NetAccess};
+use cs_interface::{ClearTextSecret, CsClient, CsvSchema, FixedPointEncoding, NetAccess};
 use poem_openapi::{payload::{Json, PlainText}, types::{ToJSON, multipart::Upload}, ApiResponse, Multipart};
 use tracing::{event, Level};
 use crate::{error::{Error, Result}};
 
-use super::utils;
+use super::{csv_validation, utils};
 
 #[derive(Debug, Multipart)]
 pub struct UploadPayload {
@@ -30,16 +30,15 @@ pub enum GetSecretResponse {
 }
 
 /// Function to upload a secret.
-pub async fn upload(collab_id: i32, party_id: i32, secrets: UploadPayload, cs_client: &impl CsClient, net: &impl NetAccess) -> Result<UploadResponse> {
-    let secret_arr = match secrets.data_csv.into_string().await {
+pub async fn upload(collab_id: i32, party_id: i32, secrets: UploadPayload, schema: &CsvSchema, encoding: &FixedPointEncoding, cs_client: &impl CsClient, net: &impl NetAccess) -> Result<UploadResponse> {
+    let csv = match secrets.data_csv.into_string().await {
         Ok(v) => v,
         Err(err) => {
             return Err(Error::from(err.to_string()));
         }
     };
-    let mut secret_arr = secret_arr.split("\n").into_iter().map(|s| s.to_string()).collect::<Vec<String>>();
-    secret_arr.remove(0); // remove header
-    let secret_ids = cs_client.create_secrets(secret_arr, secrets.uuid)?;
+    let secret_arr = csv_validation::validate(&csv, schema, encoding)?;
+    let secret_ids = cs_client.create_secrets(secret_arr, secrets.uuid).await?;
     event!(Level::INFO, "Secrets for collaboration {} and party {} successfully created on the computation instances", collab_id, party_id);
     register_upload(&secret_ids, collab_id, party_id, net).await?;
     Ok(UploadResponse::OK(Json(secret_ids)))
@@ -53,8 +52,9 @@ async fn register_upload(secrets: &Vec<String>, collab_id: i32, party_id: i32, n
     Ok(())
 }
 
-pub async fn get(secret_id: String, cs_client: &impl CsClient) -> Result<GetSecretResponse> {
-    let secret = cs_client.get_secret(&secret_id)?;
+pub async fn get(secret_id: String, encoding: &FixedPointEncoding, cs_client: &impl CsClient) -> Result<GetSecretResponse> {
+    let mut secret = cs_client.get_secret(&secret_id).await?;
+    secret.decode_fixed_point(encoding);
     Ok(GetSecretResponse::Secret(Json(secret)))
 }
 
@@ -67,7 +67,7 @@ pub enum DelSecretResp {
 }
 
 pub async fn delete(secret_ids: Vec<String>, cs_client: &impl CsClient) -> Result<DelSecretResp> {
-    let output = cs_client.delete_secrets(secret_ids)?;
+    let output = cs_client.delete_secrets(secret_ids).await?;
     Ok(DelSecretResp::OK(PlainText(output)))
 }
 
@@ -79,7 +79,7 @@ pub enum ListSecretsResponse {
 }
 
 pub async fn list_secrets(cs_client: &impl CsClient) -> Result<ListSecretsResponse> {
-    let secrets = cs_client.list_secrets()?;
+    let secrets = cs_client.list_secrets().await?;
     Ok(ListSecretsResponse::Secrets(Json(secrets)))
 }
 
@@ -1,8 +1,23 @@
 use std::env;
 
+use cs_interface::RequestsClient;
+
 pub fn coordinator_uri() -> String {
     match env::var("COORDINATOR_URI") {
         Ok(addr) => addr,
         Err(_) => "http://localhost:8081".to_string(),
     }
-}
\ No newline at end of file
+}
+
+/// Secret the coordinator signs notifications to this party with, agreed on when registering as output party.
+pub fn notification_secret() -> Option<String> {
+    env::var("NOTIFICATION_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+/// Client for the coordinator, authenticated with `COORDINATOR_API_KEY` if it is set.
+pub fn coordinator_client(coord_uri: &str) -> RequestsClient {
+    match env::var("COORDINATOR_API_KEY").ok().filter(|key| !key.is_empty()) {
+        Some(key) => RequestsClient::with_bearer_token(coord_uri, &key),
+        None => RequestsClient::new(),
+    }
+}
@@ -3,6 +3,8 @@ use poem::{error::ResponseError, http::StatusCode, IntoResponse};
 use poem_openapi::{ApiResponse, registry::Registry, registry::{MetaResponses, MetaResponse}};
 use tracing::{event, Level};
 
+use crate::api::csv_validation::CsvError;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug)]
@@ -14,9 +16,18 @@ pub enum Error {
     #[error("{message}")]
     Unprocessable{message: String},
 
+    #[error("invalid csv file: {}", errors.iter().map(|e| e.to_string()).collect::<Vec<String>>().join("; "))]
+    InvalidCsv{errors: Vec<CsvError>},
+
     #[error("HTTP-Error: status {code}, message: {message}")]
     HttpError{code: u16, message: String},
 
+    #[error("Unauthorized: {message}")]
+    Unauthorized{message: String},
+
+    #[error("Integrity check failed: {message}")]
+    IntegrityError{message: String},
+
     // -- CS-Client errors
     #[error("Collaboration with id {collab_id} not found")]
     CollaborationNotFound{collab_id: i32},
@@ -66,6 +77,12 @@ impl From<cs_interface::Error> for Error {
             cs_interface::Error::HttpError { code, message } => Self::HttpError{ code, message},
             cs_interface::Error::StdError(e) => Self::StdError(e),
             cs_interface::Error::B64DecodeError(decode_error) => Self::Custom(format!("Decode error: {}", decode_error.to_string())),
+            cs_interface::Error::InvalidNumber(e) => Self::InternalServerError { message: e },
+            cs_interface::Error::InvalidShare(e) => Self::InternalServerError { message: e },
+            cs_interface::Error::IntegrityError(e) => Self::IntegrityError { message: e },
+            cs_interface::Error::ExecutionFailed(e) => Self::InternalServerError { message: e },
+            cs_interface::Error::InvalidSchema(e) => Self::Unprocessable { message: e },
+            cs_interface::Error::InvalidSignature(e) => Self::Unauthorized { message: e },
         }
     }
 }
@@ -76,6 +93,9 @@ impl ResponseError for Error {
         match self {
             Error::CollaborationNotFound { collab_id: _ } => StatusCode::NOT_FOUND,
             Error::Unprocessable{message: _} => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::InvalidCsv{errors: _} => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Unauthorized{message: _} => StatusCode::UNAUTHORIZED,
+            Error::IntegrityError{message: _} => StatusCode::BAD_GATEWAY,
             Error::HttpError { code, message: _ } => StatusCode::from_u16(*code).unwrap(),
             Error::ReqwestError(err) => {
                 match err.status() {
@@ -87,10 +107,14 @@ impl ResponseError for Error {
         }
     }
     fn as_response(&self) -> poem::Response {
-        let body = poem::Body::from_json(serde_json::json!({
+        let mut body = serde_json::json!({
             "code": self.status().as_u16(),
             "message": self.to_string(),
-        })).unwrap();
+        });
+        if let Error::InvalidCsv { errors } = self {
+            body["errors"] = serde_json::json!(errors);
+        }
+        let body = poem::Body::from_json(body).unwrap();
         event!(Level::INFO, "Returning {} response", self.status());
         poem::Response::builder().status(self.status()).body(body).into_response()
     }
@@ -127,6 +151,20 @@ impl ApiResponse for Error {
                     content: vec![],
                     headers: vec![],
                     status_range: None
+                },
+                MetaResponse {
+                    description: "Unauthorized",
+                    status: Some(401),
+                    content: vec![],
+                    headers: vec![],
+                    status_range: None
+                },
+                MetaResponse {
+                    description: "Shares of a provider failed the integrity check",
+                    status: Some(502),
+                    content: vec![],
+                    headers: vec![],
+                    status_range: None
                 }
             ]
         }
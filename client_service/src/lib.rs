@@ -1,5 +1,6 @@
 
 pub mod api;
+pub mod notification_store;
 mod cs_definitions;
 //mod cs_config;
 //mod netaccess;
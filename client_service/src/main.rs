@@ -1,10 +1,11 @@
 mod api;
 mod error;
+mod notification_store;
 
 use poem::{listener::TcpListener, Route, Server, EndpointExt, middleware::Cors};
 use poem_openapi::OpenApiService;
 use tracing::{event, Level};
-use std::env;
+use std::{env, sync::Arc};
 
 
 
@@ -61,6 +62,22 @@ async fn main() {
         Err(_) => "".to_string()
     };
 
+    // an empty path keeps notifications in memory only
+    let store_path = env::var("NOTIFICATION_STORE").unwrap_or("notifications.json".to_string());
+    let notification_store = match store_path.as_str() {
+        "" => Arc::new(notification_store::NotificationStore::in_memory()),
+        path => match notification_store::NotificationStore::open(path) {
+            Ok(store) => Arc::new(store),
+            Err(e) => panic!("Unable to open the notification store {}: {}", path, e)
+        }
+    };
+
+    // a misconfigured export fails here instead of rejecting every notification
+    let export_config = match api::export::ExportConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => panic!("Unable to configure the result export: {}", e)
+    };
+
     let api_service =
         OpenApiService::new(api::Api, format!("Client Service {}", party_name), "1.0")
             .description("Client Service to access the computation service.")
@@ -73,6 +90,8 @@ async fn main() {
         .nest(format!("{}/docs", &prefix), ui)
         .nest(format!("{}/docs/spec", &prefix), spec_endpoint)
         .data(coord_uri)
+        .data(notification_store)
+        .data(export_config)
         .with(Cors::new());
 
     let _ = Server::new(TcpListener::bind(format!("{}:{}", addr, port)))
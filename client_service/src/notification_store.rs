@@ -0,0 +1,158 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::error::Result;
+
+/// A result notification as received from the coordinator
+#[derive(Object, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[oai(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct ReceivedNotification {
+    pub message: String,
+    pub code: i32,
+    /// Missing in notices of failed computations
+    pub secret_id: Option<String>,
+    /// Unix time in seconds the notification was received at
+    pub received_at: i64,
+}
+
+/// State of the automatic retrieval and export of a result
+#[derive(Enum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum RetrievalState {
+    Pending,
+    /// The result was fetched and written to all sinks
+    Fetched,
+    Failed,
+}
+
+/// All notifications received for a collaboration
+#[derive(Object, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[oai(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct CollaborationNotifications {
+    pub collaboration_id: i32,
+    /// The coordinator reported a finished computation, the result can be retrieved
+    pub result_available: bool,
+    pub notifications: Vec<ReceivedNotification>,
+    /// Missing if no result export is configured
+    #[serde(default)]
+    pub retrieval: Option<RetrievalState>,
+    #[serde(default)]
+    pub retrieval_error: Option<String>,
+    /// Files and endpoints the result was written to
+    #[serde(default)]
+    pub exported_to: Vec<String>,
+}
+
+/// Keeps received notifications, in a json file if a path is given.
+///
+/// The file is rewritten on every change, so the store survives restarts of the service.
+pub struct NotificationStore {
+    path: Option<PathBuf>,
+    collaborations: Mutex<BTreeMap<i32, CollaborationNotifications>>,
+}
+
+impl NotificationStore {
+    /// Store that only lives as long as the service, used in tests.
+    pub fn in_memory() -> NotificationStore {
+        NotificationStore { path: None, collaborations: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Open the store at `path`, loading the notifications received so far.
+    pub fn open(path: impl Into<PathBuf>) -> Result<NotificationStore> {
+        let path = path.into();
+        let collaborations = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<Vec<CollaborationNotifications>>(&bytes)?
+                .into_iter()
+                .map(|c| (c.collaboration_id, c))
+                .collect(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        event!(Level::INFO, "Loaded notifications of {} collaborations from {}.", collaborations.len(), path.display());
+        Ok(NotificationStore { path: Some(path), collaborations: Mutex::new(collaborations) })
+    }
+
+    /// Record a notification, one with code 200 marks the result of the collaboration as available.
+    pub fn add(&self, collaboration_id: i32, message: String, code: i32, secret_id: Option<String>) -> Result<()> {
+        let received_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+        let mut collaborations = self.collaborations.lock().unwrap();
+        let entry = collaborations.entry(collaboration_id).or_insert_with(|| CollaborationNotifications {
+            collaboration_id,
+            result_available: false,
+            notifications: vec![],
+            retrieval: None,
+            retrieval_error: None,
+            exported_to: vec![],
+        });
+        entry.result_available |= code == 200;
+        entry.notifications.push(ReceivedNotification { message, code, secret_id, received_at });
+        self.persist(&collaborations)
+    }
+
+    /// Update the retrieval state of a collaboration a notification was received for.
+    pub fn set_retrieval(&self, collaboration_id: i32, state: RetrievalState, error: Option<String>, exported_to: Vec<String>) -> Result<()> {
+        let mut collaborations = self.collaborations.lock().unwrap();
+        if let Some(entry) = collaborations.get_mut(&collaboration_id) {
+            entry.retrieval = Some(state);
+            entry.retrieval_error = error;
+            entry.exported_to = exported_to;
+        }
+        self.persist(&collaborations)
+    }
+
+    pub fn list(&self) -> Vec<CollaborationNotifications> {
+        self.collaborations.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, collaboration_id: i32) -> Option<CollaborationNotifications> {
+        self.collaborations.lock().unwrap().get(&collaboration_id).cloned()
+    }
+
+    fn persist(&self, collaborations: &BTreeMap<i32, CollaborationNotifications>) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        // write a copy first, so a crash never leaves a truncated store
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&collaborations.values().collect::<Vec<_>>())?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_add_and_reopen() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("notifications.json");
+        let store = NotificationStore::open(&path)?;
+        assert!(store.list().is_empty());
+        store.add(1, "Success".to_string(), 200, Some("abc".to_string()))?;
+        store.add(2, "Failed".to_string(), 500, None)?;
+
+        let reopened = NotificationStore::open(&path)?;
+        assert_eq!(reopened.list(), store.list());
+        let first = reopened.get(1).unwrap();
+        assert!(first.result_available);
+        assert_eq!(first.notifications[0].secret_id.as_deref(), Some("abc"));
+        assert!(!reopened.get(2).unwrap().result_available);
+        assert!(reopened.get(3).is_none());
+
+        reopened.set_retrieval(1, RetrievalState::Fetched, None, vec!["out/1.json".to_string()])?;
+        let first = NotificationStore::open(&path)?.get(1).unwrap();
+        assert_eq!(first.retrieval, Some(RetrievalState::Fetched));
+        assert_eq!(first.exported_to, vec!["out/1.json"]);
+        Ok(())
+    }
+}
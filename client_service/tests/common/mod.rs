@@ -7,7 +7,9 @@ use reqwest::{multipart::{Form, Part}, Client};
 use serde::Deserialize;
 use tempfile::NamedTempFile;
 
-use std::io::Write;
+use std::{io::Write, sync::Arc};
+
+use client_service::{api::export::ExportConfig, notification_store::NotificationStore};
 
 fn coord_uri() -> String {
     match env::var("COORDINATOR_URI") {
@@ -17,7 +19,11 @@ fn coord_uri() -> String {
 }
 
 pub fn test_client() -> TestClient<Route> {
-    let api_service = OpenApiService::new(client_service::api::Api, "", "1.0").data(coord_uri());
+    let store = Arc::new(NotificationStore::in_memory());
+    let api_service = OpenApiService::new(client_service::api::Api, "", "1.0")
+        .data(coord_uri())
+        .data(store)
+        .data(None::<ExportConfig>);
     let app = Route::new().nest("/", api_service);
     TestClient::new(app)
 }
@@ -27,16 +33,14 @@ pub struct Collaboration {
     pub id: i32,
     /// Name of the collaboration
     pub name: String,
-    /// SPDZ Programm (base64 encoded)
-    pub mpc_program: String,
+    /// SHA-256 of the current SPDZ program
+    pub program_sha256: String,
     /// input parameter cs specification (base64 encoded)
     pub csv_specification: String,
     // Number of participating parties
     pub participation_number: i32,
     // id of cs configuration
-    pub config_id: i32,
-    // endpoints of output parties
-    pub output_parties: Option<Vec<Option<String>>>
+    pub config_id: i32
 }
 
 pub struct EnvConfig {
@@ -118,7 +122,7 @@ sint.write_to_socket(socket_id, resp)
     // create test collaboration
     let form = Form::new()
         .text("name", "testing")
-        .text("csv_header_line", "data")
+        .text("csv_schema", r#"{"columns":[{"name":"data","type":"int"}]}"#)
         .text("number_of_parties", "1")
         .part("mpc_program", program)
         .part("cs_config", config);
@@ -128,17 +132,42 @@ sint.write_to_socket(socket_id, resp)
     collab.json::<Collaboration>().await.unwrap()
 }
 
-pub async fn register_input_party(collab_id: i32, party_id: i32) {
-    let client = Client::new();   
+/// Add an input party to the party registry of the coordinator, let it join the collaboration and register it, returns its id.
+pub async fn register_input_party(collab_id: i32) -> i32 {
+    let client = Client::new();
+    let party = client
+        .post(format!("{}/party", coord_uri()))
+        .json(&serde_json::json!({"display_name": "client service test", "roles": ["input", "output"]}))
+        .send().await.unwrap()
+        .json::<serde_json::Value>().await.unwrap();
+    let party_id = party["party"]["id"].as_i64().unwrap() as i32;
+    let invitation = client
+        .post(format!("{}/collaboration/{}/invitations", coord_uri(), collab_id))
+        .json(&serde_json::json!({"party_id": party_id, "role": "input"}))
+        .send().await.unwrap()
+        .json::<serde_json::Value>().await.unwrap();
+    let invitation_id = invitation["id"].as_i64().unwrap();
+    let _ = client
+        .post(format!("{}/collaboration/{}/invitations/{}/accept", coord_uri(), collab_id, invitation_id))
+        .send().await.unwrap();
+    let program = client
+        .get(format!("{}/collaboration/{}/program", coord_uri(), collab_id))
+        .send().await.unwrap()
+        .json::<serde_json::Value>().await.unwrap();
+    let _ = client
+        .post(format!("{}/collaboration/{}/invitations/{}/approve-program", coord_uri(), collab_id, invitation_id))
+        .json(&serde_json::json!({"program_sha256": program["program_sha256"]}))
+        .send().await.unwrap();
     let _ = client
         .post(format!("{}/collaboration/{}/register-input-party/{}", coord_uri(), collab_id, party_id))
         .send().await.unwrap();
+    party_id
 }
 
 pub async fn upload_secret(client: &TestClient<Route>, collab_id: i32, party_id: i32, secret_id: Option<String>) -> TestResponse {
     // Create a temporary file with some content
     let mut temp_file = NamedTempFile::new().unwrap();
-    temp_file.write_all(b"data\n22").unwrap();
+    temp_file.write_all(b"data\n22\n30").unwrap();
     let file_path = temp_file.path().to_owned();
 
     let file = tokio::fs::File::open(file_path.clone()).await.unwrap();
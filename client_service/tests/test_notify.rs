@@ -2,24 +2,66 @@ mod common;
 
 #[cfg(test)]
 mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use cs_interface::signature;
     use reqwest::StatusCode;
     use tokio;
 
     use crate::common;
-    /// Testing the /notify endpoint
-    #[tokio::test]
-    async fn test_put_notify() {
-        let client = common::test_client();
-        let request_body = serde_json::json!({
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn request_body() -> String {
+        serde_json::json!({
             "message": "testing notify",
             "code": 200,
             "collaborationId": 123,
             "secretId": "abc-123"
-        });
+        }).to_string()
+    }
+
+    /// Testing the /notify endpoint
+    #[tokio::test]
+    async fn test_put_notify() {
+        std::env::set_var("NOTIFICATION_SECRET", SECRET);
+        let client = common::test_client();
+        let body = request_body();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
         let resp = client.put("/notify")
-            .body_json(&request_body)
+            .header("Content-Type", "application/json")
+            .header(signature::TIMESTAMP_HEADER, timestamp)
+            .header(signature::SIGNATURE_HEADER, signature::sign(SECRET, timestamp, body.as_bytes()))
+            .body(body)
             .send()
             .await;
         resp.assert_status(StatusCode::ACCEPTED);
+
+        // the notification is kept and marks the result as available
+        let resp = client.get("/notifications/123").send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let notifications = json.value().object();
+        notifications.get("collaborationId").assert_i64(123);
+        notifications.get("resultAvailable").assert_bool(true);
+        notifications.get("notifications").array().get(0).object().get("secretId").assert_string("abc-123");
+        let resp = client.get("/notifications").send().await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().array().assert_len(1);
+        let resp = client.get("/notifications/124").send().await;
+        resp.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    /// Unsigned notifications are rejected
+    #[tokio::test]
+    async fn test_put_notify_unsigned() {
+        std::env::set_var("NOTIFICATION_SECRET", SECRET);
+        let client = common::test_client();
+        let resp = client.put("/notify")
+            .header("Content-Type", "application/json")
+            .body(request_body())
+            .send()
+            .await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
     }
-}
\ No newline at end of file
+}
@@ -16,10 +16,10 @@ mod test {
     async fn test_get_result_success() {
         let collab = common::setup_env().await;
         let client = common::test_client();
-        common::register_input_party(collab.id, 1).await;
+        let party_id = common::register_input_party(collab.id).await;
 
         // Upload secret to start execution
-        let upload_resp = common::upload_secret(&client, collab.id, 1, None).await;
+        let upload_resp = common::upload_secret(&client, collab.id, party_id, None).await;
         
         upload_resp.assert_status_is_ok();
 
@@ -28,18 +28,20 @@ mod test {
             // Sleep two seconds to wait for execution to be finished.
             thread::sleep(time::Duration::from_secs(2));
 
-            let resp= client.get(format!("/result/{}/1", collab.id))
+            let resp= client.get(format!("/result/{}/{}", collab.id, party_id))
                 .send()
                 .await;
             // proccessing not finished yet.
             if resp.0.status() == StatusCode::CONFLICT {
                 continue;
             }
-            //let body = resp.0.into_body().into_string().await;
-            //assert!(false, "response: {:?}", body);
             resp.assert_status_is_ok();
-            break;
+            // the mock evaluates the program in plaintext: 22 < 30
+            let body = resp.json().await;
+            body.value().array().get(0).object().get("values").assert_i64_array(&[1]);
+            return;
         }
+        panic!("no result after 10 seconds");
     }
 
     #[tokio::test]
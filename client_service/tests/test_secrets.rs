@@ -15,7 +15,7 @@ mod test {
     #[tokio::test]
     async fn test_upload_secret() {
         let collab = common::setup_env().await;
-        common::register_input_party(collab.id, 1).await;
+        let party_id = common::register_input_party(collab.id).await;
         // Create a temporary file with some content
         let mut temp_file = NamedTempFile::new().unwrap();
         temp_file.write_all(b"data\n22").unwrap();
@@ -28,7 +28,7 @@ mod test {
         let field = TestFormField::async_reader(tokio::io::BufReader::new(file))
             .filename("data_csv")
             .name("data_csv");
-        let resp = client.post(format!("/secrets/{}/1", collab.id))
+        let resp = client.post(format!("/secrets/{}/{}", collab.id, party_id))
             .multipart(TestForm::new().field(field))
             .send()
             .await;
@@ -37,6 +37,33 @@ mod test {
         assert!(true, "response: {:?}", body);
     }
 
+    #[tokio::test]
+    async fn test_secret_shares_reconstructed() {
+        let collab = common::setup_env().await;
+        let party_id = common::register_input_party(collab.id).await;
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"data\n22\n-5").unwrap();
+
+        let client = common::test_client();
+        let file = tokio::fs::File::open(temp_file.path()).await.unwrap();
+        let field = TestFormField::async_reader(tokio::io::BufReader::new(file))
+            .filename("data_csv")
+            .name("data_csv");
+        let resp = client.post(format!("/secrets/{}/{}", collab.id, party_id))
+            .multipart(TestForm::new().field(field))
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let ids = resp.json().await.value().deserialize::<Vec<String>>();
+
+        let resp = client.get(format!("/secret_shares/{}/{}", collab.id, ids[0])).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let body = json.value().object();
+        body.get("result").array().assert_len(2);
+        body.get("secret").object().get("values").assert_i64_array(&[22, -5]);
+    }
+
     #[tokio::test]
     async fn test_upload_secret_no_collab() {
         let collab = common::setup_env().await;
@@ -76,6 +103,36 @@ mod test {
             .multipart(TestForm::new().field(field))
             .send()
             .await;
-        resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let json = resp.json().await;
+        let error = json.value().object().get("errors").array().get(0).object();
+        error.get("row").assert_i64(2);
+        error.get("column").assert_i64(1);
+    }
+
+    #[tokio::test]
+    async fn test_upload_secret_wrong_header() {
+        let collab = common::setup_env().await;
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"other,data
+1,2").unwrap();
+        let file_path = temp_file.path().to_owned();
+
+        let client = common::test_client();
+
+        let file = tokio::fs::File::open(file_path.clone()).await.unwrap();
+        let field = TestFormField::async_reader(tokio::io::BufReader::new(file))
+            .filename("data_csv")
+            .name("data_csv");
+        let resp = client.post(format!("/secrets/{}/1", collab.id))
+            .multipart(TestForm::new().field(field))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let json = resp.json().await;
+        let errors = json.value().object().get("errors").array();
+        errors.assert_len(2);
+        errors.get(0).object().get("row").assert_i64(1);
+        errors.get(1).object().get("row").assert_i64(2);
     }
 }
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE collaborations DROP COLUMN fixed_point_precision;
@@ -0,0 +1 @@
+ALTER TABLE collaborations ADD COLUMN fixed_point_precision INT NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+ALTER TABLE collaborations ADD COLUMN csv_schema JSONB;
+
+-- Existing collaborations only have a header line, every column becomes a number column.
+UPDATE collaborations SET csv_schema = jsonb_build_object('columns', (
+  SELECT coalesce(jsonb_agg(jsonb_build_object(
+    'name', trim(c),
+    'type', CASE WHEN fixed_point_precision > 0 THEN 'fixed_point' ELSE 'int' END,
+    'nullable', false)), '[]'::jsonb)
+  FROM unnest(string_to_array(csv_specification, ',')) AS c
+));
+
+ALTER TABLE collaborations ALTER COLUMN csv_schema SET NOT NULL;
@@ -0,0 +1,7 @@
+ALTER TABLE computation_results DROP COLUMN started_at, DROP COLUMN finished_at;
+ALTER TABLE participations DROP COLUMN registered_at, DROP COLUMN uploaded_at;
+ALTER TABLE collaborations
+  DROP CONSTRAINT collaborations_status_check,
+  DROP COLUMN status,
+  DROP COLUMN created_at,
+  DROP COLUMN updated_at;
@@ -0,0 +1,34 @@
+ALTER TABLE collaborations
+  ADD COLUMN status VARCHAR(32) NOT NULL DEFAULT 'open',
+  ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+ALTER TABLE participations
+  ADD COLUMN registered_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  ADD COLUMN uploaded_at TIMESTAMPTZ;
+
+ALTER TABLE computation_results
+  ADD COLUMN started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  ADD COLUMN finished_at TIMESTAMPTZ;
+
+-- Derive the status of existing collaborations from their uploads and results.
+UPDATE participations SET uploaded_at = now() WHERE secret_ids IS NOT NULL;
+UPDATE computation_results SET finished_at = now() WHERE finished;
+
+UPDATE collaborations c SET status = CASE
+    WHEN r.error IS NOT NULL THEN 'failed'
+    WHEN r.finished THEN 'succeeded'
+    ELSE 'running'
+  END
+  FROM computation_results r WHERE r.collab_id = c.id;
+
+UPDATE collaborations c SET status = CASE
+    WHEN uploads.count >= c.participation_number THEN 'ready'
+    ELSE 'collecting'
+  END
+  FROM (SELECT collaboration_id, count(*) AS count FROM participations
+        WHERE secret_ids IS NOT NULL GROUP BY collaboration_id) uploads
+  WHERE uploads.collaboration_id = c.id AND c.status = 'open';
+
+ALTER TABLE collaborations ADD CONSTRAINT collaborations_status_check CHECK (status IN
+  ('draft', 'open', 'collecting', 'ready', 'running', 'succeeded', 'failed', 'cancelled', 'archived'));
@@ -0,0 +1,20 @@
+CREATE TABLE notifications (
+  id SERIAL PRIMARY KEY,
+  collab_id INT NOT NULL,
+  endpoint TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  status VARCHAR(32) NOT NULL DEFAULT 'pending',
+  attempts INT NOT NULL DEFAULT 0,
+  next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  last_error TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  delivered_at TIMESTAMPTZ,
+  CONSTRAINT notifications_collab_endpoint UNIQUE (collab_id, endpoint),
+  CONSTRAINT notifications_status_check CHECK (status IN ('pending', 'delivered', 'dead')),
+  CONSTRAINT fk_collab_notifications
+    FOREIGN KEY(collab_id)
+      REFERENCES collaborations(id)
+      ON DELETE CASCADE
+);
+
+CREATE INDEX notifications_due ON notifications (next_attempt_at) WHERE status = 'pending';
@@ -0,0 +1 @@
+DROP TABLE output_party_secrets;
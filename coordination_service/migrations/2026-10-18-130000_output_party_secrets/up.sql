@@ -0,0 +1,12 @@
+-- Secrets output parties agreed on at registration, used to sign their notifications
+CREATE TABLE output_party_secrets (
+  collab_id INT NOT NULL,
+  endpoint TEXT NOT NULL,
+  party_id INT NOT NULL,
+  secret TEXT NOT NULL,
+  CONSTRAINT output_party_secrets_pkey PRIMARY KEY (collab_id, endpoint),
+  CONSTRAINT fk_collab_output_party_secrets
+    FOREIGN KEY(collab_id)
+      REFERENCES collaborations(id)
+      ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+ALTER TABLE collaborations DROP COLUMN created_by;
@@ -0,0 +1,2 @@
+-- Identity that created the collaboration, only it or an admin may delete it
+ALTER TABLE collaborations ADD COLUMN created_by TEXT;
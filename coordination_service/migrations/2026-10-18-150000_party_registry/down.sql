@@ -0,0 +1,23 @@
+ALTER TABLE collaborations ADD COLUMN output_parties text [];
+CREATE TABLE output_party_secrets (
+  collab_id INT NOT NULL,
+  endpoint TEXT NOT NULL,
+  party_id INT NOT NULL,
+  secret TEXT NOT NULL,
+  CONSTRAINT output_party_secrets_pkey PRIMARY KEY (collab_id, endpoint),
+  CONSTRAINT fk_collab_output_party_secrets
+    FOREIGN KEY(collab_id)
+      REFERENCES collaborations(id)
+      ON DELETE CASCADE
+);
+
+INSERT INTO output_party_secrets (collab_id, endpoint, party_id, secret)
+SELECT DISTINCT ON (r.collab_id, p.client_endpoint) r.collab_id, p.client_endpoint, r.party_id, r.secret
+FROM output_registrations r JOIN parties p ON p.id = r.party_id
+WHERE p.client_endpoint IS NOT NULL;
+UPDATE collaborations c
+SET output_parties = (SELECT ARRAY_AGG(o.endpoint) FROM output_party_secrets o WHERE o.collab_id = c.id);
+
+DROP TABLE output_registrations;
+ALTER TABLE participations DROP CONSTRAINT fk_party_participation;
+DROP TABLE parties;
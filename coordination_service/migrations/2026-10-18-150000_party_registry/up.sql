@@ -0,0 +1,79 @@
+-- Registry of the parties taking part in collaborations
+CREATE TABLE parties (
+  id SERIAL PRIMARY KEY,
+  display_name VARCHAR(255) NOT NULL,
+  organisation VARCHAR(255),
+  roles TEXT[] NOT NULL DEFAULT '{}',
+  client_endpoint TEXT,
+  api_key_sha256 VARCHAR(64),
+  active BOOL NOT NULL DEFAULT true,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  CONSTRAINT parties_api_key UNIQUE (api_key_sha256),
+  CONSTRAINT parties_roles_check CHECK (roles <@ ARRAY['input', 'output', 'creator'])
+);
+
+-- parties that were only known by their id so far
+INSERT INTO parties (id, display_name, roles, client_endpoint)
+SELECT ids.party_id,
+  'party ' || ids.party_id,
+  ARRAY_REMOVE(ARRAY[
+    CASE WHEN EXISTS (SELECT 1 FROM participations p WHERE p.party_id = ids.party_id) THEN 'input' END,
+    CASE WHEN EXISTS (SELECT 1 FROM output_party_secrets o WHERE o.party_id = ids.party_id) THEN 'output' END
+  ], NULL),
+  (SELECT o.endpoint FROM output_party_secrets o WHERE o.party_id = ids.party_id ORDER BY o.collab_id DESC LIMIT 1)
+FROM (SELECT party_id FROM participations UNION SELECT party_id FROM output_party_secrets) ids;
+SELECT setval(pg_get_serial_sequence('parties', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM parties;
+
+ALTER TABLE participations ADD CONSTRAINT fk_party_participation
+  FOREIGN KEY(party_id)
+    REFERENCES parties(id);
+
+-- Output parties of a collaboration, notified at the client endpoint of the party
+CREATE TABLE output_registrations (
+  collab_id INT NOT NULL,
+  party_id INT NOT NULL,
+  secret TEXT NOT NULL,
+  registered_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  CONSTRAINT output_registrations_pkey PRIMARY KEY (collab_id, party_id),
+  CONSTRAINT fk_collab_output_registrations
+    FOREIGN KEY(collab_id)
+      REFERENCES collaborations(id)
+      ON DELETE CASCADE,
+  CONSTRAINT fk_party_output_registrations
+    FOREIGN KEY(party_id)
+      REFERENCES parties(id)
+);
+
+INSERT INTO output_registrations (collab_id, party_id, secret)
+SELECT DISTINCT ON (collab_id, party_id) collab_id, party_id, secret FROM output_party_secrets;
+
+-- Unfinished collaborations notify their output parties at the endpoint of the party from now on. Stop instead of
+-- losing endpoints registered before notifications were signed, which belong to no party, or endpoints a party
+-- registered per collaboration, which the registry keeps one of. Finished collaborations queued their notifications.
+DO $$
+DECLARE
+  unsigned TEXT;
+  collapsed TEXT;
+BEGIN
+  SELECT string_agg(DISTINCT c.id || ' ' || e.endpoint, ', ') INTO unsigned
+  FROM collaborations c CROSS JOIN LATERAL unnest(c.output_parties) AS e(endpoint)
+  WHERE c.status NOT IN ('succeeded', 'failed', 'cancelled', 'archived')
+    AND NOT EXISTS (SELECT 1 FROM output_party_secrets o WHERE o.collab_id = c.id AND o.endpoint = e.endpoint);
+  IF unsigned IS NOT NULL THEN
+    RAISE EXCEPTION 'unfinished collaborations have output parties without party id (%), register them with their party id or finish the collaborations first', unsigned;
+  END IF;
+
+  SELECT string_agg(DISTINCT o.party_id::TEXT, ', ') INTO collapsed
+  FROM output_party_secrets o
+  JOIN collaborations c ON c.id = o.collab_id
+  JOIN parties p ON p.id = o.party_id
+  WHERE c.status NOT IN ('succeeded', 'failed', 'cancelled', 'archived')
+    AND o.endpoint IS DISTINCT FROM p.client_endpoint;
+  IF collapsed IS NOT NULL THEN
+    RAISE EXCEPTION 'output parties % registered different endpoints to unfinished collaborations, register them with one endpoint or finish the collaborations first', collapsed;
+  END IF;
+END $$;
+
+DROP TABLE output_party_secrets;
+ALTER TABLE collaborations DROP COLUMN output_parties;
@@ -0,0 +1,37 @@
+-- Invitations of parties to collaborations and their consent to the program
+CREATE TABLE invitations (
+  id SERIAL PRIMARY KEY,
+  collab_id INT NOT NULL,
+  party_id INT NOT NULL,
+  role TEXT NOT NULL,
+  status VARCHAR(32) NOT NULL DEFAULT 'pending',
+  expires_at TIMESTAMPTZ NOT NULL,
+  invited_by TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  responded_at TIMESTAMPTZ,
+  approved_program_sha256 VARCHAR(64),
+  approved_at TIMESTAMPTZ,
+  CONSTRAINT invitations_collab_party_role UNIQUE (collab_id, party_id, role),
+  CONSTRAINT invitations_role_check CHECK (role IN ('input', 'output')),
+  CONSTRAINT invitations_status_check CHECK (status IN ('pending', 'accepted', 'declined')),
+  CONSTRAINT fk_collab_invitations
+    FOREIGN KEY(collab_id)
+      REFERENCES collaborations(id)
+      ON DELETE CASCADE,
+  CONSTRAINT fk_party_invitations
+    FOREIGN KEY(party_id)
+      REFERENCES parties(id)
+);
+
+-- parties registered before invitations existed joined with the program of their collaboration,
+-- record that as an accepted invitation approving it
+INSERT INTO invitations (collab_id, party_id, role, status, expires_at, invited_by, responded_at,
+  approved_program_sha256, approved_at)
+SELECT r.collab_id, r.party_id, r.role, 'accepted', r.registered_at, COALESCE(c.created_by, 'migration'),
+  r.registered_at, encode(sha256(decode(c.mpc_program, 'base64')), 'hex'), r.registered_at
+FROM (
+  SELECT collaboration_id AS collab_id, party_id, 'input' AS role, registered_at FROM participations
+  UNION ALL
+  SELECT collab_id, party_id, 'output', registered_at FROM output_registrations
+) r
+JOIN collaborations c ON c.id = r.collab_id;
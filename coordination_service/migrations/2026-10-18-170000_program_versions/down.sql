@@ -0,0 +1,10 @@
+ALTER TABLE computation_results DROP COLUMN program_sha256;
+
+ALTER TABLE collaborations ADD COLUMN mpc_program TEXT;
+UPDATE collaborations c SET mpc_program = p.source FROM programs p WHERE p.sha256 = c.program_sha256;
+ALTER TABLE collaborations ALTER COLUMN mpc_program SET NOT NULL;
+ALTER TABLE collaborations DROP COLUMN program_version;
+ALTER TABLE collaborations DROP COLUMN program_sha256;
+
+DROP TABLE program_versions;
+DROP TABLE programs;
@@ -0,0 +1,48 @@
+-- programs are stored once per content, collaborations refer to a version of their program by its hash
+CREATE TABLE programs (
+  sha256 VARCHAR(64) NOT NULL,
+  source TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  CONSTRAINT programs_pkey PRIMARY KEY (sha256)
+);
+
+CREATE TABLE program_versions (
+  collab_id INT NOT NULL,
+  version INT NOT NULL,
+  program_sha256 VARCHAR(64) NOT NULL,
+  proposed_by TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  CONSTRAINT program_versions_pkey PRIMARY KEY (collab_id, version),
+  CONSTRAINT fk_collab_program_versions
+    FOREIGN KEY(collab_id)
+      REFERENCES collaborations(id)
+      ON DELETE CASCADE,
+  CONSTRAINT fk_program_program_versions
+    FOREIGN KEY(program_sha256)
+      REFERENCES programs(sha256)
+);
+
+INSERT INTO programs (sha256, source)
+SELECT encode(sha256(decode(mpc_program, 'base64')), 'hex'), mpc_program FROM collaborations
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE collaborations ADD COLUMN program_sha256 VARCHAR(64);
+ALTER TABLE collaborations ADD COLUMN program_version INT NOT NULL DEFAULT 1;
+UPDATE collaborations SET program_sha256 = encode(sha256(decode(mpc_program, 'base64')), 'hex');
+ALTER TABLE collaborations ALTER COLUMN program_sha256 SET NOT NULL;
+ALTER TABLE collaborations ADD CONSTRAINT fk_collab_program
+  FOREIGN KEY(program_sha256)
+    REFERENCES programs(sha256);
+
+INSERT INTO program_versions (collab_id, version, program_sha256, proposed_by, created_at)
+SELECT id, 1, program_sha256, created_by, created_at FROM collaborations;
+
+ALTER TABLE collaborations DROP COLUMN mpc_program;
+
+-- results of former executions ran the only program of their collaboration
+ALTER TABLE computation_results ADD COLUMN program_sha256 VARCHAR(64);
+UPDATE computation_results r SET program_sha256 = c.program_sha256 FROM collaborations c WHERE c.id = r.collab_id;
+ALTER TABLE computation_results ALTER COLUMN program_sha256 SET NOT NULL;
+ALTER TABLE computation_results ADD CONSTRAINT fk_program_results
+  FOREIGN KEY(program_sha256)
+    REFERENCES programs(sha256);
@@ -0,0 +1 @@
+ALTER TABLE computation_results ALTER COLUMN error TYPE VARCHAR(255) USING left(error, 255);
@@ -0,0 +1,2 @@
+-- execution errors carry URLs and response bodies, which do not fit 255 characters
+ALTER TABLE computation_results ALTER COLUMN error TYPE TEXT;
@@ -0,0 +1,10 @@
+ALTER TABLE notifications ADD COLUMN endpoint TEXT;
+UPDATE notifications n SET endpoint = p.client_endpoint FROM parties p WHERE p.id = n.party_id;
+DELETE FROM notifications WHERE endpoint IS NULL;
+DELETE FROM notifications n USING notifications o
+WHERE o.collab_id = n.collab_id AND o.endpoint = n.endpoint AND o.id < n.id;
+ALTER TABLE notifications ALTER COLUMN endpoint SET NOT NULL;
+ALTER TABLE notifications DROP CONSTRAINT fk_party_notifications;
+ALTER TABLE notifications DROP CONSTRAINT notifications_collab_party;
+ALTER TABLE notifications DROP COLUMN party_id;
+ALTER TABLE notifications ADD CONSTRAINT notifications_collab_endpoint UNIQUE (collab_id, endpoint);
@@ -0,0 +1,19 @@
+-- notifications are addressed to output parties, their endpoint is looked up in the registry when sending
+ALTER TABLE notifications ADD COLUMN party_id INT;
+UPDATE notifications n SET party_id = r.party_id
+FROM (
+  SELECT DISTINCT ON (o.collab_id, p.client_endpoint) o.collab_id, o.party_id, p.client_endpoint
+  FROM output_registrations o JOIN parties p ON p.id = o.party_id
+  ORDER BY o.collab_id, p.client_endpoint, o.party_id
+) r
+WHERE r.collab_id = n.collab_id AND r.client_endpoint = n.endpoint;
+-- no output party of the collaboration is at the endpoint anymore, nobody is left to deliver them to
+DELETE FROM notifications WHERE party_id IS NULL;
+
+ALTER TABLE notifications ALTER COLUMN party_id SET NOT NULL;
+ALTER TABLE notifications DROP CONSTRAINT notifications_collab_endpoint;
+ALTER TABLE notifications DROP COLUMN endpoint;
+ALTER TABLE notifications ADD CONSTRAINT notifications_collab_party UNIQUE (collab_id, party_id);
+ALTER TABLE notifications ADD CONSTRAINT fk_party_notifications
+  FOREIGN KEY(party_id)
+    REFERENCES parties(id);
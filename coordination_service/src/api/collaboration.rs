@@ -1,71 +1,105 @@
 use poem::web::Data;
-use poem_openapi::{param::{Path, Query}, payload::Json, types::multipart::Upload, ApiResponse, Multipart, Object, OpenApi};
+use poem_openapi::{param::{Header, Path, Query}, payload::Json, types::multipart::Upload, ApiResponse, Multipart, Object, OpenApi};
 use serde::{Serialize, Deserialize};
-use base64::prelude::*;
 use tracing::{event, Level};
-use crate::{db::{collab_ops, models::{Collaboration, NewCollaboration}}, error::Result};
-use super::{config::{self, get_config, CarbynestackConfig}, participation};
+use std::sync::Arc;
 
-pub struct CollabApi;
+use crate::{auth::{Auth, Credentials}, program_check::ProgramCheck, db::{collab_ops, invitation_ops, models::{Collaboration, CollaborationStatus, NewCollaboration, NewProgram, PartyRole}, party_ops}, error::{Error, Result}};
+use super::{config::{self, get_config, CarbynestackConfig}, invitation, participation, program, status};
+use cs_interface::CsvSchema;
+
+pub struct CollabApi {
+    auth: Arc<Auth>,
+    program_check: Arc<ProgramCheck>,
+}
+
+impl CollabApi {
+    pub fn new(auth: Arc<Auth>, program_check: Arc<ProgramCheck>) -> CollabApi {
+        CollabApi { auth, program_check }
+    }
+
+    /// Fails unless the caller may act as the party of the invitation.
+    fn require_invited(&self, credentials: &Credentials, collaboration_id: i32, invitation_id: i32, db_url: &str) -> Result<()> {
+        let invitation = invitation_ops::get(collaboration_id, invitation_id, db_url)?;
+        self.auth.authenticate(credentials, db_url)?.require_party(invitation.party_id)
+    }
+}
 
 #[OpenApi(prefix_path = "/collaboration")]
 impl CollabApi {
     /// Create a new Collaboration 
     #[oai(path = "/", method = "post")]
-    async fn add_collaboration(&self, payload: RegisterCollaborationPayload, db_url: Data<&String>) -> Result<RegisterCollaborationResponse> {
-        post(payload, db_url.0).await
+    async fn add_collaboration(&self, credentials: Credentials, payload: RegisterCollaborationPayload, db_url: Data<&String>) -> Result<RegisterCollaborationResponse> {
+        let identity = self.auth.authenticate(&credentials, db_url.0)?;
+        if let Some(party_id) = identity.registry_party {
+            party_ops::require_role(party_id, PartyRole::Creator, db_url.0)?;
+        }
+        post(payload, identity.name, &self.program_check, db_url.0).await
     }
 
     /// input_party registers participation. Return input-specification and compute-party config on success.
     #[oai(path = "/:collaboration_id/register-input-party/:party_id", method = "post")]
     async fn register_participation(&self, 
+        credentials: Credentials,
         /// identifier of collaboration to register
         collaboration_id: Path<i32>,
         /// Identifier of party that is registering
         party_id: Path<i32>,
         db_url: Data<&String>
     ) -> Result<participation::RegisterParticipationResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?.require_party(party_id.0)?;
         participation::register_input_party(collaboration_id.0, party_id.0, db_url.0)
     }
 
     /// output_party registers participation.
     #[oai(path = "/:collaboration_id/register-output-party/:party_id", method = "post")]
     async fn register_output_party(&self, 
+        credentials: Credentials,
         /// identifier of collaboration to register
         collaboration_id: Path<i32>,
         /// Identifier of party that is registering
         party_id: Path<i32>,
-        party_client_endpoint: Query<String>,
+        /// Client service of the party, replaces the endpoint in the party registry if set
+        party_client_endpoint: Query<Option<String>>,
+        /// Secret to sign the notifications of the party with, generated if missing
+        #[oai(name = "X-Notification-Secret")]
+        notification_secret: Header<Option<String>>,
         db_url: Data<&String>
     ) -> Result<participation::RegisterOutputPartyResponse> {
-        participation::register_output_party(collaboration_id.0, party_id.0, party_client_endpoint.0, db_url.0)
+        self.auth.authenticate(&credentials, db_url.0)?.require_party(party_id.0)?;
+        participation::register_output_party(collaboration_id.0, party_id.0, party_client_endpoint.0, notification_secret.0, db_url.0)
     }
 
     /// input_party unregisteres from participation.
     #[oai(path = "/:collaboration_id/register-input-party/:party_id", method = "delete")]
     async fn unregister_participation(&self, 
+        credentials: Credentials,
         /// identifier of collaboration to unregister
         collaboration_id: Path<i32>,
         /// Identifier of party that is unregistering
         party_id: Path<i32>,
         db_url: Data<&String>
      ) -> Result<participation::DeleteParticipationResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?.require_party(party_id.0)?;
         participation::delete(collaboration_id.0, party_id.0, db_url.0)
     }
 
     /// list participations of collaboration
     #[oai(path = "/:collaboration_id/input-parties", method = "get")]
     async fn get_participations(&self, 
+        credentials: Credentials,
         /// identifier of the collaboration
         collaboration_id: Path<i32>,
         db_url: Data<&String>
     ) -> Result<participation::ListParticipationsResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
         participation::list(collaboration_id.0, db_url.0)
     }
 
     /// input_party confirms upload done.
     #[oai(path = "/:collaboration_id/confirm-upload/:party_id", method = "post")]
     async fn register_upload(&self,
+        credentials: Credentials,
         /// identifier of the collaboration
         collaboration_id: Path<i32>,
         /// Identifier of party
@@ -74,6 +108,7 @@ impl CollabApi {
         secret_ids: Json<Vec<String>>,
         db_url: Data<&String>
     ) -> Result<participation::PostRegisterUploadResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?.require_party(party_id.0)?;
         participation::register_upload(collaboration_id.0, party_id.0, secret_ids.0, db_url.0)
     }
 
@@ -81,47 +116,193 @@ impl CollabApi {
     /// Delete Collaboration 
     #[oai(path = "/:collaboration_id", method = "delete")]
     async fn delete_collaboration(&self, 
+        credentials: Credentials,
         /// identifier of the collaboration
         collaboration_id: Path<i32>,
         db_url: Data<&String>
     ) -> Result<DeleteCollaborationResponse> {
+        let identity = self.auth.authenticate(&credentials, db_url.0)?;
+        identity.require_owner(&collab_ops::get(collaboration_id.0, db_url.0)?)?;
         delete(collaboration_id.0, db_url.0)
     }
 
     /// List Collaborations
     #[oai(path = "/", method = "get")]
     async fn list_collaborations(&self,
+        credentials: Credentials,
         db_url: Data<&String>
     ) -> Result<ListCollaborationsResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
         list(db_url.0)
     }
 
     #[oai(path = "/:collaboration_id", method = "get")]
     async fn get_collaboration(&self,
+        credentials: Credentials,
         /// identifier of the collaboration
         collaboration_id: Path<i32>,
         db_url: Data<&String>,
     ) -> Result<GetCollaborationResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
         get(collaboration_id.0, &db_url.0)
     }
 
     /// Get result of collaboration
     #[oai(path = "/:collaboration_id/result_ids", method = "get")]
     async fn get_result_ids(&self, 
+        credentials: Credentials,
         /// identifier of the collaboration
         collaboration_id: Path<i32>,
         db_url: Data<&String>
     ) -> Result<GetResultIdsResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
         get_result_ids(collaboration_id.0, db_url.0)
     }
 
+    /// Get the status of the collaboration and the upload progress of its input parties
+    #[oai(path = "/:collaboration_id/status", method = "get")]
+    async fn get_status(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<status::GetStatusResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
+        status::get(collaboration_id.0, db_url.0)
+    }
+
+    /// Open a draft, cancel or archive the collaboration
+    #[oai(path = "/:collaboration_id/status", method = "put")]
+    async fn change_status(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        payload: Json<status::ChangeStatusPayload>,
+        db_url: Data<&String>
+    ) -> Result<status::ChangeStatusResponse> {
+        let identity = self.auth.authenticate(&credentials, db_url.0)?;
+        identity.require_owner(&collab_ops::get(collaboration_id.0, db_url.0)?)?;
+        status::change(collaboration_id.0, payload.0.status, db_url.0)
+    }
+
+    /// Invite a party as input or output party, only the creator of the collaboration or an admin may invite
+    #[oai(path = "/:collaboration_id/invitations", method = "post")]
+    async fn invite_party(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        payload: Json<invitation::InvitePayload>,
+        db_url: Data<&String>
+    ) -> Result<invitation::InvitationResponse> {
+        let identity = self.auth.authenticate(&credentials, db_url.0)?;
+        identity.require_owner(&collab_ops::get(collaboration_id.0, db_url.0)?)?;
+        invitation::invite(collaboration_id.0, payload.0, identity.name, db_url.0)
+    }
+
+    /// List the invitations of the collaboration and the consent of the invited parties
+    #[oai(path = "/:collaboration_id/invitations", method = "get")]
+    async fn list_invitations(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<invitation::ListInvitationsResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
+        invitation::list(collaboration_id.0, db_url.0)
+    }
+
+    /// The invited party accepts the invitation
+    #[oai(path = "/:collaboration_id/invitations/:invitation_id/accept", method = "post")]
+    async fn accept_invitation(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        /// identifier of the invitation
+        invitation_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<invitation::InvitationResponse> {
+        self.require_invited(&credentials, collaboration_id.0, invitation_id.0, db_url.0)?;
+        invitation::respond(collaboration_id.0, invitation_id.0, true, db_url.0)
+    }
+
+    /// The invited party declines the invitation
+    #[oai(path = "/:collaboration_id/invitations/:invitation_id/decline", method = "post")]
+    async fn decline_invitation(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        /// identifier of the invitation
+        invitation_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<invitation::InvitationResponse> {
+        self.require_invited(&credentials, collaboration_id.0, invitation_id.0, db_url.0)?;
+        invitation::respond(collaboration_id.0, invitation_id.0, false, db_url.0)
+    }
+
+    /// The invited party consents to the program with the given hash, needed before it registers
+    #[oai(path = "/:collaboration_id/invitations/:invitation_id/approve-program", method = "post")]
+    async fn approve_program(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        /// identifier of the invitation
+        invitation_id: Path<i32>,
+        payload: Json<invitation::ApproveProgramPayload>,
+        db_url: Data<&String>
+    ) -> Result<invitation::InvitationResponse> {
+        self.require_invited(&credentials, collaboration_id.0, invitation_id.0, db_url.0)?;
+        invitation::approve_program(collaboration_id.0, invitation_id.0, payload.0, db_url.0)
+    }
+
+    /// Get the MPC program and its hash, which parties approve. The current version unless `version` is given
+    #[oai(path = "/:collaboration_id/program", method = "get")]
+    async fn get_program(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        /// version of the program, counting from 1
+        version: Query<Option<i32>>,
+        db_url: Data<&String>
+    ) -> Result<program::GetProgramResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
+        program::get(collaboration_id.0, version.0, db_url.0)
+    }
+
+    /// List the versions of the program, the first one first
+    #[oai(path = "/:collaboration_id/program/versions", method = "get")]
+    async fn list_program_versions(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<program::ListProgramVersionsResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
+        program::list_versions(collaboration_id.0, db_url.0)
+    }
+
+    /// Replace the program with a new version, parties approve it again and confirmed uploads are dropped
+    #[oai(path = "/:collaboration_id/program", method = "post")]
+    async fn propose_program(&self,
+        credentials: Credentials,
+        /// identifier of the collaboration
+        collaboration_id: Path<i32>,
+        payload: program::ProposeProgramPayload,
+        db_url: Data<&String>
+    ) -> Result<program::ProposeProgramResponse> {
+        let identity = self.auth.authenticate(&credentials, db_url.0)?;
+        identity.require_owner(&collab_ops::get(collaboration_id.0, db_url.0)?)?;
+        program::propose(collaboration_id.0, payload, identity.name, &self.program_check, db_url.0).await
+    }
+
     /// Get Computation Party Config
     #[oai(path = "/:collaboration_id/compute_config", method = "get")]
     async fn get_compute_config(&self, 
+        credentials: Credentials,
         /// identifier of the collaboration
         collaboration_id: Path<i32>,
         db_url: Data<&String>
     ) -> Result<GetConfigResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
         let config = get_config(collaboration_id.0, db_url.0)?;
         Ok(GetConfigResponse::Ok(Json(config)))
     }
@@ -145,10 +326,15 @@ pub struct RegisterCollaborationPayload {
     mpc_program: Upload,
     /// CarbyneStack configuration as described in https://carbynestack.io/documentation/getting-started/cli/
     cs_config: Upload,
-    /// The header-line of the csv
-    csv_header_line: String,
+    /// Schema of the csv as json, e.g. {"columns":[{"name":"age","type":"int","min":0,"max":120}]}.
+    /// Column types are int, fixed_point, boolean and categorical (with a list of values).
+    csv_schema: String,
     /// Number of parties. For now the execution is started if all parties register their secrets
     number_of_parties: i32,
+    /// Number of fractional bits used to encode fixed-point secrets. Defaults to 0 (plain integers)
+    fixed_point_precision: Option<i32>,
+    /// Create the collaboration as draft, parties can only register after it was opened
+    draft: Option<bool>,
 }
 
 #[derive(ApiResponse)]
@@ -163,23 +349,37 @@ pub enum RegisterCollaborationResponse {
     AlreadyAdded(Json<RegisterCollaborationResponseBody>),
 }
 
-/// Post new collaboration
-pub async fn post(collab: RegisterCollaborationPayload, db_url: &str) -> Result<RegisterCollaborationResponse> {
+/// Post new collaboration, created by the identity `created_by`. The program is checked before anything is stored.
+pub async fn post(collab: RegisterCollaborationPayload, created_by: String, program_check: &ProgramCheck, db_url: &str) -> Result<RegisterCollaborationResponse> {
+    let fixed_point_precision = collab.fixed_point_precision.unwrap_or(0);
+    if fixed_point_precision < 0 || fixed_point_precision as u32 > cs_interface::MAX_PRECISION {
+        return Err(Error::Unprocessable {
+            message: format!("fixed_point_precision must be between 0 and {}", cs_interface::MAX_PRECISION),
+        });
+    }
     let csconfig_str = collab.cs_config.into_string().await?;
 
     let csconfig = CarbynestackConfig::from_json(&csconfig_str)?;
 
-    let mpc_program = collab.mpc_program.into_string().await?;
+    let csv_schema = CsvSchema::from_json(&collab.csv_schema).map_err(|err| Error::Unprocessable {
+        message: err.to_string(),
+    })?;
+
+    let mpc_program = collab.mpc_program.into_vec().await?;
+    let mpc_program = NewProgram::new(program_check.check(&mpc_program, collab.number_of_parties, &csv_schema).await?.as_bytes());
     let db_config = config::add_config(csconfig, db_url)?;
     let new_collab = NewCollaboration {
         name: collab.name,
-        mpc_program: BASE64_STANDARD.encode(mpc_program),
-        csv_specification: collab.csv_header_line,
+        csv_specification: csv_schema.header_line(),
         participation_number: collab.number_of_parties,
-        output_parties: None,
         config_id: db_config.id,
+        fixed_point_precision,
+        csv_schema: serde_json::to_value(&csv_schema)?,
+        status: if collab.draft.unwrap_or(false) { CollaborationStatus::Draft } else { CollaborationStatus::Open },
+        created_by: Some(created_by),
+        program_sha256: mpc_program.sha256.clone(),
     };
-    let res = collab_ops::create(new_collab, db_url)?;
+    let res = collab_ops::create(new_collab, mpc_program, db_url)?;
     event!(Level::INFO, "A new collaboration with ID {} was created.", res.id);
     Ok(RegisterCollaborationResponse::OK(Json(res)))
 }
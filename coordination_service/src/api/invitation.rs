@@ -0,0 +1,90 @@
+use chrono::{DateTime, Duration, Utc};
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::{db::{collab_ops, invitation_ops, models::{CollaborationStatus, Invitation, NewInvitation, PartyRole}, participation_ops, party_ops}, error::{Error, Result}};
+
+/// Invitations expire after a week unless an expiry is given
+const DEFAULT_EXPIRY_DAYS: i64 = 7;
+
+/// Payload to invite a party to a collaboration
+#[derive(Object, Deserialize, Serialize)]
+pub struct InvitePayload {
+    pub party_id: i32,
+    /// `input` or `output`, the party needs this role in the registry
+    pub role: PartyRole,
+    /// The invitation can not be accepted afterwards, defaults to a week from now
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Payload to consent to the program of a collaboration
+#[derive(Object, Deserialize, Serialize)]
+pub struct ApproveProgramPayload {
+    /// Hex encoded SHA-256 of the program, see `GET /collaboration/{id}/program`
+    pub program_sha256: String,
+}
+
+#[derive(ApiResponse)]
+pub enum InvitationResponse {
+    #[oai(status = 200)]
+    Ok(Json<Invitation>),
+}
+
+#[derive(ApiResponse)]
+pub enum ListInvitationsResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<Invitation>>),
+}
+
+/// Invite a party on behalf of the identity `invited_by`.
+pub fn invite(collaboration_id: i32, payload: InvitePayload, invited_by: String, db_url: &str) -> Result<InvitationResponse> {
+    if !matches!(payload.role, PartyRole::Input | PartyRole::Output) {
+        return Err(Error::Unprocessable { message: "Parties are invited as input or output party".to_string() });
+    }
+    let expires_at = payload.expires_at.unwrap_or_else(|| Utc::now() + Duration::days(DEFAULT_EXPIRY_DAYS));
+    if expires_at <= Utc::now() {
+        return Err(Error::Unprocessable { message: "The expiry of an invitation has to be in the future".to_string() });
+    }
+    collab_ops::require_status(
+        collaboration_id,
+        &[CollaborationStatus::Draft, CollaborationStatus::Open, CollaborationStatus::Collecting, CollaborationStatus::Ready],
+        "invite parties",
+        db_url,
+    )?;
+    party_ops::require_role(payload.party_id, payload.role, db_url)?;
+    let invitation = invitation_ops::invite(NewInvitation {
+        collab_id: collaboration_id,
+        party_id: payload.party_id,
+        role: payload.role,
+        expires_at,
+        invited_by,
+    }, db_url)?;
+    event!(Level::INFO, "Party {} was invited as {} party to collaboration {}.", invitation.party_id, invitation.role, collaboration_id);
+    Ok(InvitationResponse::Ok(Json(invitation)))
+}
+
+pub fn list(collaboration_id: i32, db_url: &str) -> Result<ListInvitationsResponse> {
+    collab_ops::get(collaboration_id, db_url)?;
+    Ok(ListInvitationsResponse::Ok(Json(invitation_ops::list(collaboration_id, db_url)?)))
+}
+
+pub fn respond(collaboration_id: i32, invitation_id: i32, accept: bool, db_url: &str) -> Result<InvitationResponse> {
+    let invitation = invitation_ops::respond(collaboration_id, invitation_id, accept, db_url)?;
+    event!(Level::INFO, "Party {} {} the invitation to collaboration {}.", invitation.party_id, invitation.status.as_str(), collaboration_id);
+    Ok(InvitationResponse::Ok(Json(invitation)))
+}
+
+/// Consent to the current program. A collaboration that only waited for this consent is executed.
+pub fn approve_program(collaboration_id: i32, invitation_id: i32, payload: ApproveProgramPayload, db_url: &str) -> Result<InvitationResponse> {
+    let collab = collab_ops::get(collaboration_id, db_url)?;
+    if payload.program_sha256.to_lowercase() != collab.program_sha256 {
+        return Err(Error::Unprocessable { message: format!("{} is not the hash of the current program of collaboration {}", payload.program_sha256, collaboration_id) });
+    }
+    let invitation = invitation_ops::approve_program(collaboration_id, invitation_id, &collab.program_sha256, db_url)?;
+    event!(Level::INFO, "Party {} approved version {} of the program of collaboration {}.", invitation.party_id, collab.program_version, collaboration_id);
+    if collab.status == CollaborationStatus::Ready {
+        participation_ops::start_execution(collaboration_id, db_url);
+    }
+    Ok(InvitationResponse::Ok(Json(invitation)))
+}
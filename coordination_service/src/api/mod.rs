@@ -1,4 +1,8 @@
 mod participation;
+mod invitation;
+mod program;
+mod status;
 pub mod collaboration;
 pub mod sys_status;
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub mod notification;pub mod party;
@@ -0,0 +1,71 @@
+use poem::web::Data;
+use poem_openapi::{param::{Path, Query}, payload::Json, ApiResponse, OpenApi};
+use tracing::{event, Level};
+
+use std::sync::Arc;
+
+use crate::{auth::{Auth, Credentials}, db::{models::{Notification, NotificationStatus}, notification_ops}, error::Result, notification_service::{deliver_due, RetryPolicy}};
+
+pub struct NotificationApi {
+    auth: Arc<Auth>,
+}
+
+impl NotificationApi {
+    pub fn new(auth: Arc<Auth>) -> NotificationApi {
+        NotificationApi { auth }
+    }
+}
+
+/// Only admins may manage notifications
+#[OpenApi(prefix_path = "/notifications")]
+impl NotificationApi {
+    /// List result notifications of output parties, e.g. the dead ones with `status=dead`
+    #[oai(path = "/", method = "get")]
+    async fn list_notifications(&self,
+        credentials: Credentials,
+        /// only notifications of this collaboration
+        collaboration_id: Query<Option<i32>>,
+        /// only notifications in this delivery state
+        status: Query<Option<NotificationStatus>>,
+        db_url: Data<&String>
+    ) -> Result<ListNotificationsResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?.require_admin()?;
+        list(collaboration_id.0, status.0, db_url.0)
+    }
+
+    /// Deliver a notification again, with a fresh number of attempts
+    #[oai(path = "/:notification_id/redeliver", method = "post")]
+    async fn redeliver_notification(&self,
+        credentials: Credentials,
+        /// identifier of the notification
+        notification_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<RedeliverNotificationResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?.require_admin()?;
+        redeliver(notification_id.0, db_url.0).await
+    }
+}
+
+#[derive(ApiResponse)]
+pub enum ListNotificationsResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<Notification>>),
+}
+
+pub fn list(collab_id: Option<i32>, status: Option<NotificationStatus>, db_url: &str) -> Result<ListNotificationsResponse> {
+    Ok(ListNotificationsResponse::Ok(Json(notification_ops::list(collab_id, status, db_url)?)))
+}
+
+#[derive(ApiResponse)]
+pub enum RedeliverNotificationResponse {
+    /// The notification is queued again, a delivery was attempted right away
+    #[oai(status = 200)]
+    Ok,
+}
+
+pub async fn redeliver(notification_id: i32, db_url: &str) -> Result<RedeliverNotificationResponse> {
+    let notification = notification_ops::redeliver(notification_id, db_url)?;
+    event!(Level::INFO, "Notification {} to party {} queued for redelivery.", notification.id, notification.party_id);
+    deliver_due(db_url, &RetryPolicy::from_env()).await?;
+    Ok(RedeliverNotificationResponse::Ok)
+}
@@ -1,7 +1,7 @@
 use poem_openapi::{payload::Json , ApiResponse, Object};
 use serde::{Serialize, Deserialize};
 use tracing::{event, Level};
-use crate::{api::config::CarbynestackConfig, db::{collab_ops, models::Participation, participation_ops}, error::Result};
+use crate::{api::config::CarbynestackConfig, db::{collab_ops, models::{Participation, PartyRole}, participation_ops, party_ops}, error::{Error, Result}};
 
 #[derive(Object, Deserialize, Serialize)]
 pub struct RegisterParticipationResponseBody {
@@ -50,18 +50,40 @@ pub fn list(collaboration_id: i32, db_url: &str) -> Result<ListParticipationsRes
     Ok(ListParticipationsResponse::OK(Json(participation_ops::list_participations(collaboration_id, db_url)?)))
 }
 
+/// Response body after an output party was registered
+#[derive(Object, Deserialize, Serialize)]
+pub struct RegisterOutputPartyResponseBody {
+    /// Secret the notifications to the party are signed with, see `cs_interface::signature`
+    pub notification_secret: String,
+}
+
 /// Response on registering parties
 #[derive(ApiResponse)]
 pub enum RegisterOutputPartyResponse {
     /// Party was registered successfully
     #[oai(status = 200)]
-    Ok,
+    Ok(Json<RegisterOutputPartyResponseBody>),
 }
 
-pub fn register_output_party(collaboration_id: i32, party_id: i32, party_client_endpoint: String, db_url: &str) -> Result<RegisterOutputPartyResponse> {
-    let _ = collab_ops::add_output_party(collaboration_id, party_id, party_client_endpoint, db_url)?;
+/// Register an output party. Without a `notification_secret` a random one is generated.
+///
+/// The party is notified at its client endpoint in the registry, `party_client_endpoint` replaces it.
+pub fn register_output_party(collaboration_id: i32, party_id: i32, party_client_endpoint: Option<String>, notification_secret: Option<String>, db_url: &str) -> Result<RegisterOutputPartyResponse> {
+    let notification_secret = match notification_secret {
+        Some(secret) if secret.len() < 16 => {
+            return Err(Error::Unprocessable { message: "The notification secret needs at least 16 characters".to_string() });
+        },
+        Some(secret) => secret,
+        None => cs_interface::signature::generate_secret()?,
+    };
+    let party = party_ops::require_role(party_id, PartyRole::Output, db_url)?;
+    if party.client_endpoint.is_none() && party_client_endpoint.is_none() {
+        return Err(Error::Unprocessable { message: format!("Party {} has no client endpoint to notify", party_id) });
+    }
+    let changed_endpoint = party_client_endpoint.filter(|endpoint| party.client_endpoint.as_ref() != Some(endpoint));
+    collab_ops::add_output_party(collaboration_id, party_id, notification_secret.clone(), changed_endpoint, db_url)?;
     event!(Level::INFO, "Party {} was registered as output-party to the collaboration with ID {}.", party_id, collaboration_id);
-    Ok(RegisterOutputPartyResponse::Ok)
+    Ok(RegisterOutputPartyResponse::Ok(Json(RegisterOutputPartyResponseBody { notification_secret })))
 }
 
 
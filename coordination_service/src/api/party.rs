@@ -0,0 +1,189 @@
+use poem::web::Data;
+use poem_openapi::{param::{Path, Query}, payload::Json, ApiResponse, Object, OpenApi};
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use std::sync::Arc;
+
+use crate::{auth::{self, Auth, Credentials}, db::{models::{NewParty, Party, PartyChanges, PartyRole}, party_ops}, error::{Error, Result}};
+
+pub struct PartyApi {
+    auth: Arc<Auth>,
+}
+
+impl PartyApi {
+    pub fn new(auth: Arc<Auth>) -> PartyApi {
+        PartyApi { auth }
+    }
+}
+
+/// Registry of the parties, managed by admins. Parties may update their own entry except for the roles.
+#[OpenApi(prefix_path = "/party")]
+impl PartyApi {
+    /// Add a party, the response holds its API key which is not shown again
+    #[oai(path = "/", method = "post")]
+    async fn add_party(&self,
+        credentials: Credentials,
+        payload: Json<CreatePartyPayload>,
+        db_url: Data<&String>
+    ) -> Result<CreatePartyResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?.require_admin()?;
+        create(payload.0, db_url.0)
+    }
+
+    /// List the active parties, deactivated ones too with `include_inactive=true`
+    #[oai(path = "/", method = "get")]
+    async fn list_parties(&self,
+        credentials: Credentials,
+        include_inactive: Query<Option<bool>>,
+        db_url: Data<&String>
+    ) -> Result<ListPartiesResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
+        Ok(ListPartiesResponse::Ok(Json(party_ops::list(include_inactive.0.unwrap_or(false), db_url.0)?)))
+    }
+
+    #[oai(path = "/:party_id", method = "get")]
+    async fn get_party(&self,
+        credentials: Credentials,
+        /// identifier of the party
+        party_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<GetPartyResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?;
+        Ok(GetPartyResponse::Ok(Json(party_ops::get(party_id.0, db_url.0)?)))
+    }
+
+    /// Change the given fields of the party
+    #[oai(path = "/:party_id", method = "put")]
+    async fn update_party(&self,
+        credentials: Credentials,
+        /// identifier of the party
+        party_id: Path<i32>,
+        payload: Json<UpdatePartyPayload>,
+        db_url: Data<&String>
+    ) -> Result<GetPartyResponse> {
+        let identity = self.auth.authenticate(&credentials, db_url.0)?;
+        identity.require_party(party_id.0)?;
+        if payload.0.roles.is_some() {
+            identity.require_admin()?;
+        }
+        update(party_id.0, payload.0, db_url.0)
+    }
+
+    /// Deactivate the party, it can neither authenticate nor register for collaborations anymore
+    #[oai(path = "/:party_id", method = "delete")]
+    async fn deactivate_party(&self,
+        credentials: Credentials,
+        /// identifier of the party
+        party_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<GetPartyResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?.require_admin()?;
+        let party = party_ops::deactivate(party_id.0, db_url.0)?;
+        event!(Level::INFO, "Party {} was deactivated.", party.id);
+        Ok(GetPartyResponse::Ok(Json(party)))
+    }
+
+    /// Issue a new API key for the party, the former one is no longer accepted
+    #[oai(path = "/:party_id/api-key", method = "post")]
+    async fn rotate_api_key(&self,
+        credentials: Credentials,
+        /// identifier of the party
+        party_id: Path<i32>,
+        db_url: Data<&String>
+    ) -> Result<CreatePartyResponse> {
+        self.auth.authenticate(&credentials, db_url.0)?.require_party(party_id.0)?;
+        let api_key = cs_interface::signature::generate_secret()?;
+        let party = party_ops::set_api_key(party_id.0, auth::sha256_hex(&api_key), db_url.0)?;
+        event!(Level::INFO, "The API key of party {} was replaced.", party.id);
+        Ok(CreatePartyResponse::Ok(Json(PartyWithApiKey { party, api_key })))
+    }
+}
+
+/// Payload to add a party to the registry
+#[derive(Object, Deserialize, Serialize)]
+pub struct CreatePartyPayload {
+    /// Name shown to the other parties
+    pub display_name: String,
+    pub organisation: Option<String>,
+    /// What the party may do in collaborations
+    pub roles: Vec<PartyRole>,
+    /// Url of the client service of the party
+    pub client_endpoint: Option<String>,
+}
+
+/// Changes of a party, missing fields are kept
+#[derive(Object, Deserialize, Serialize)]
+pub struct UpdatePartyPayload {
+    pub display_name: Option<String>,
+    pub organisation: Option<String>,
+    /// Only admins may change the roles
+    pub roles: Option<Vec<PartyRole>>,
+    pub client_endpoint: Option<String>,
+}
+
+/// A party together with its API key
+#[derive(Object, Serialize)]
+pub struct PartyWithApiKey {
+    pub party: Party,
+    /// Sent as bearer token by the party, only the SHA-256 of it is stored
+    pub api_key: String,
+}
+
+#[derive(ApiResponse)]
+pub enum CreatePartyResponse {
+    #[oai(status = 200)]
+    Ok(Json<PartyWithApiKey>),
+}
+
+#[derive(ApiResponse)]
+pub enum ListPartiesResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<Party>>),
+}
+
+#[derive(ApiResponse)]
+pub enum GetPartyResponse {
+    #[oai(status = 200)]
+    Ok(Json<Party>),
+}
+
+fn validate_display_name(display_name: &str) -> Result<()> {
+    if display_name.trim().is_empty() || display_name.len() > 255 {
+        return Err(Error::Unprocessable { message: "The display name needs between 1 and 255 characters".to_string() });
+    }
+    Ok(())
+}
+
+fn dedup_roles(mut roles: Vec<PartyRole>) -> Vec<PartyRole> {
+    roles.sort_by_key(|role| role.as_str());
+    roles.dedup();
+    roles
+}
+
+pub fn create(payload: CreatePartyPayload, db_url: &str) -> Result<CreatePartyResponse> {
+    validate_display_name(&payload.display_name)?;
+    let api_key = cs_interface::signature::generate_secret()?;
+    let party = party_ops::create(NewParty {
+        display_name: payload.display_name,
+        organisation: payload.organisation,
+        roles: dedup_roles(payload.roles),
+        client_endpoint: payload.client_endpoint,
+        api_key_sha256: Some(auth::sha256_hex(&api_key)),
+    }, db_url)?;
+    event!(Level::INFO, "Party {} ({}) was added to the registry.", party.id, party.display_name);
+    Ok(CreatePartyResponse::Ok(Json(PartyWithApiKey { party, api_key })))
+}
+
+pub fn update(party_id: i32, payload: UpdatePartyPayload, db_url: &str) -> Result<GetPartyResponse> {
+    if let Some(display_name) = &payload.display_name {
+        validate_display_name(display_name)?;
+    }
+    let changes = PartyChanges {
+        display_name: payload.display_name,
+        organisation: payload.organisation,
+        roles: payload.roles.map(dedup_roles),
+        client_endpoint: payload.client_endpoint,
+    };
+    Ok(GetPartyResponse::Ok(Json(party_ops::update(party_id, changes, db_url)?)))
+}
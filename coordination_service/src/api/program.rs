@@ -0,0 +1,74 @@
+use base64::prelude::*;
+use poem_openapi::{payload::Json, types::multipart::Upload, ApiResponse, Multipart, Object};
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::{db::{collab_ops, models::{NewProgram, ProgramVersion}, program_ops}, error::Result, program_check::ProgramCheck};
+use cs_interface::CsvSchema;
+
+/// A version of the program, parties consent to its hash
+#[derive(Object, Deserialize, Serialize)]
+pub struct ProgramBody {
+    pub version: i32,
+    pub program: String,
+    /// Hex encoded SHA-256 of the program
+    pub program_sha256: String,
+}
+
+/// Payload to propose a new version of the program
+#[derive(Debug, Multipart)]
+pub struct ProposeProgramPayload {
+    /// The MPC program that will be executed instead
+    mpc_program: Upload,
+}
+
+#[derive(ApiResponse)]
+pub enum GetProgramResponse {
+    #[oai(status = 200)]
+    Ok(Json<ProgramBody>),
+}
+
+#[derive(ApiResponse)]
+pub enum ListProgramVersionsResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<ProgramVersion>>),
+}
+
+#[derive(ApiResponse)]
+pub enum ProposeProgramResponse {
+    /// The program is the current version now
+    #[oai(status = 200)]
+    Ok(Json<ProgramVersion>),
+}
+
+/// Get the given version of the program, the current one by default.
+pub fn get(collaboration_id: i32, version: Option<i32>, db_url: &str) -> Result<GetProgramResponse> {
+    let collab = collab_ops::get(collaboration_id, db_url)?;
+    let (version, program_sha256) = match version {
+        Some(version) => {
+            let program_version = program_ops::get_version(collaboration_id, version, db_url)?;
+            (program_version.version, program_version.program_sha256)
+        }
+        None => (collab.program_version, collab.program_sha256),
+    };
+    let program = String::from_utf8(BASE64_STANDARD.decode(program_ops::source(&program_sha256, db_url)?)?)?;
+    Ok(GetProgramResponse::Ok(Json(ProgramBody { version, program, program_sha256 })))
+}
+
+pub fn list_versions(collaboration_id: i32, db_url: &str) -> Result<ListProgramVersionsResponse> {
+    collab_ops::get(collaboration_id, db_url)?;
+    Ok(ListProgramVersionsResponse::Ok(Json(program_ops::list_versions(collaboration_id, db_url)?)))
+}
+
+/// Propose a new version of the program on behalf of the identity `proposed_by`, see `program_ops::propose`.
+///
+/// The program is checked against the collaboration like the program it was created with.
+pub async fn propose(collaboration_id: i32, payload: ProposeProgramPayload, proposed_by: String, program_check: &ProgramCheck, db_url: &str) -> Result<ProposeProgramResponse> {
+    let collab = collab_ops::get(collaboration_id, db_url)?;
+    let csv_schema: CsvSchema = serde_json::from_value(collab.csv_schema)?;
+    let mpc_program = payload.mpc_program.into_vec().await?;
+    let mpc_program = program_check.check(&mpc_program, collab.participation_number, &csv_schema).await?;
+    let version = program_ops::propose(collaboration_id, NewProgram::new(mpc_program.as_bytes()), proposed_by, db_url)?;
+    event!(Level::INFO, "Version {} of the program of collaboration {} was proposed.", version.version, collaboration_id);
+    Ok(ProposeProgramResponse::Ok(Json(version)))
+}
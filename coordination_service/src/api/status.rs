@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use tracing::{event, Level};
+
+use crate::{db::{collab_ops, models::CollaborationStatus, participation_ops}, error::{Error, Result}};
+
+/// Upload progress of a single input party
+#[derive(Object)]
+pub struct PartyProgress {
+    pub party_id: i32,
+    /// The party confirmed its upload
+    pub uploaded: bool,
+    pub registered_at: DateTime<Utc>,
+    pub uploaded_at: Option<DateTime<Utc>>,
+}
+
+/// Current state of a collaboration
+#[derive(Object)]
+pub struct CollaborationStatusBody {
+    pub collaboration_id: i32,
+    pub status: CollaborationStatus,
+    /// Number of uploads needed to start the computation
+    pub participation_number: i32,
+    /// Number of confirmed uploads
+    pub uploads_confirmed: i32,
+    pub parties: Vec<PartyProgress>,
+    pub created_at: DateTime<Utc>,
+    /// Time of the last status change
+    pub updated_at: DateTime<Utc>,
+    /// Version of the current program
+    pub program_version: i32,
+    /// SHA-256 of the current program
+    pub program_sha256: String,
+    pub execution_started_at: Option<DateTime<Utc>>,
+    pub execution_finished_at: Option<DateTime<Utc>>,
+    /// SHA-256 of the program the execution ran
+    pub executed_program_sha256: Option<String>,
+    /// Error message of a failed execution
+    pub error: Option<String>,
+}
+
+/// Payload to change the status of a collaboration
+#[derive(Object)]
+pub struct ChangeStatusPayload {
+    /// One of open (from draft), cancelled or archived, all other states are set by the coordinator
+    pub status: CollaborationStatus,
+}
+
+#[derive(ApiResponse)]
+pub enum GetStatusResponse {
+    #[oai(status = 200)]
+    Ok(Json<CollaborationStatusBody>),
+}
+
+pub fn get(collab_id: i32, db_url: &str) -> Result<GetStatusResponse> {
+    Ok(GetStatusResponse::Ok(Json(status_body(collab_id, db_url)?)))
+}
+
+#[derive(ApiResponse)]
+pub enum ChangeStatusResponse {
+    /// Status changed, returns the new state
+    #[oai(status = 200)]
+    Ok(Json<CollaborationStatusBody>),
+}
+
+pub fn change(collab_id: i32, status: CollaborationStatus, db_url: &str) -> Result<ChangeStatusResponse> {
+    if !matches!(status, CollaborationStatus::Open | CollaborationStatus::Cancelled | CollaborationStatus::Archived) {
+        return Err(Error::Unprocessable { message: format!("Status {} is set by the coordinator", status) });
+    }
+    // reopening drops the uploads, only a new program version does that
+    let from = match status {
+        CollaborationStatus::Open => vec![CollaborationStatus::Draft],
+        status => CollaborationStatus::predecessors(status),
+    };
+    collab_ops::transition_from(collab_id, &from, status, db_url)?;
+    event!(Level::INFO, "Collaboration {} changed to {}.", collab_id, status);
+    Ok(ChangeStatusResponse::Ok(Json(status_body(collab_id, db_url)?)))
+}
+
+fn status_body(collab_id: i32, db_url: &str) -> Result<CollaborationStatusBody> {
+    let collab = collab_ops::get(collab_id, db_url)?;
+    let mut parties = participation_ops::list_participations(collab_id, db_url)?
+        .into_iter()
+        .map(|p| PartyProgress {
+            party_id: p.party_id,
+            uploaded: p.secret_ids.is_some(),
+            registered_at: p.registered_at,
+            uploaded_at: p.uploaded_at,
+        })
+        .collect::<Vec<PartyProgress>>();
+    parties.sort_by_key(|p| p.party_id);
+    let result = collab_ops::get_result(collab_id, db_url)?;
+    Ok(CollaborationStatusBody {
+        collaboration_id: collab.id,
+        status: collab.status,
+        participation_number: collab.participation_number,
+        uploads_confirmed: parties.iter().filter(|p| p.uploaded).count() as i32,
+        parties,
+        created_at: collab.created_at,
+        updated_at: collab.updated_at,
+        program_version: collab.program_version,
+        program_sha256: collab.program_sha256,
+        execution_started_at: result.as_ref().map(|r| r.started_at),
+        execution_finished_at: result.as_ref().and_then(|r| r.finished_at),
+        executed_program_sha256: result.as_ref().map(|r| r.program_sha256.clone()),
+        error: result.and_then(|r| r.error),
+    })
+}
@@ -0,0 +1,283 @@
+//! Authentication and authorization of the parties calling the coordinator.
+//!
+//! Parties send `Authorization: Bearer <token>`, the token is either an API key or a JWT signed with HS256.
+//! API keys are configured in the json file `AUTH_API_KEYS_FILE`, which stores the SHA-256 of each key together
+//! with the identity it belongs to:
+//! ```json
+//! [{"name": "hospital-a", "api_key_sha256": "<hex>", "party_ids": [1, 2], "admin": false}]
+//! ```
+//! JWTs are signed with `AUTH_JWT_SECRET` and carry the identity in the claims `sub`, `party_ids`, `admin` and `exp`.
+//! Other API keys are looked up in the party registry, such a key only allows to act as its party `party-{id}`.
+//! Names with this prefix are therefore rejected in the API keys file and in JWTs.
+//! The service refuses to start without either setting, unless `AUTH_DISABLED=true` disables authentication
+//! and every caller may act as admin.
+
+use std::{collections::HashMap, env, fs};
+
+use base64::prelude::*;
+use chrono::Utc;
+use poem_openapi::{auth::Bearer, SecurityScheme};
+use ring::{digest, hmac};
+use serde::Deserialize;
+use tracing::{event, Level};
+
+use crate::{db::{models::{Collaboration, Party}, party_ops}, error::{Error, Result}};
+
+/// Name of the caller while authentication is disabled
+pub const ANONYMOUS: &str = "anonymous";
+/// Prefix of the names of registry parties, reserved so other identities cannot pass as one of them
+pub const PARTY_NAME_PREFIX: &str = "party-";
+
+/// API key or JWT of the calling party
+#[derive(SecurityScheme)]
+#[oai(ty = "bearer", bearer_format = "API key or JWT")]
+pub struct BearerToken(Bearer);
+
+/// Credentials of a request, requests without credentials are only accepted while authentication is disabled
+#[derive(SecurityScheme)]
+pub enum Credentials {
+    Bearer(BearerToken),
+    #[oai(fallback)]
+    Missing,
+}
+
+/// An authenticated caller and the parties it may act for.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Identity {
+    pub name: String,
+    #[serde(default)]
+    pub party_ids: Vec<i32>,
+    /// Admins may act for all parties and manage all collaborations
+    #[serde(default)]
+    pub admin: bool,
+    /// Id of the registry party, if the caller authenticated with the API key of a party
+    #[serde(skip)]
+    pub registry_party: Option<i32>,
+}
+
+impl Identity {
+    /// Identity of a caller using the API key of a registry party.
+    pub fn of_party(party: &Party) -> Identity {
+        Identity { name: format!("{}{}", PARTY_NAME_PREFIX, party.id), party_ids: vec![party.id], admin: false, registry_party: Some(party.id) }
+    }
+
+    /// Fails unless the caller may act as `party_id`.
+    pub fn require_party(&self, party_id: i32) -> Result<()> {
+        if self.admin || self.party_ids.contains(&party_id) {
+            Ok(())
+        } else {
+            Err(Error::Forbidden(format!("{} may not act as party {}", self.name, party_id)))
+        }
+    }
+
+    /// Fails unless the caller created the collaboration or is an admin.
+    pub fn require_owner(&self, collab: &Collaboration) -> Result<()> {
+        if self.admin || collab.created_by.as_deref() == Some(self.name.as_str()) {
+            Ok(())
+        } else {
+            Err(Error::Forbidden(format!("only the creator of collaboration {} or an admin may do this", collab.id)))
+        }
+    }
+
+    pub fn require_admin(&self) -> Result<()> {
+        if self.admin { Ok(()) } else { Err(Error::Forbidden(format!("{} is no admin", self.name))) }
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiKeyEntry {
+    api_key_sha256: String,
+    #[serde(flatten)]
+    identity: Identity,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    sub: String,
+    #[serde(default)]
+    party_ids: Vec<i32>,
+    #[serde(default)]
+    admin: bool,
+    exp: i64,
+}
+
+/// Known API keys and the JWT secret, shared by all apis.
+pub enum Auth {
+    Disabled,
+    Enabled {
+        /// Identities by the hex encoded SHA-256 of their API key
+        api_keys: HashMap<String, Identity>,
+        jwt_key: Option<Box<hmac::Key>>,
+    },
+}
+
+/// Hex encoded SHA-256, of API keys as stored in `AUTH_API_KEYS_FILE` and the party registry, and of programs.
+pub fn sha256_hex(value: impl AsRef<[u8]>) -> String {
+    digest::digest(&digest::SHA256, value.as_ref())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl Auth {
+    /// Read `AUTH_API_KEYS_FILE` and `AUTH_JWT_SECRET`, fails if neither is set.
+    ///
+    /// Authentication is only disabled with `AUTH_DISABLED=true`, which makes every caller an admin.
+    pub fn from_env() -> Result<Auth> {
+        let disabled = match env::var("AUTH_DISABLED") {
+            Ok(value) => value.parse::<bool>().map_err(|_| Error::from(format!("invalid AUTH_DISABLED '{}'", value)))?,
+            Err(_) => false,
+        };
+        let api_keys = match env::var("AUTH_API_KEYS_FILE") {
+            Ok(path) => Some(fs::read_to_string(&path).map_err(|e| Error::from(format!("unable to read {}: {}", path, e)))?),
+            Err(_) => None,
+        };
+        let jwt_secret = env::var("AUTH_JWT_SECRET").ok();
+        match (disabled, api_keys.is_some() || jwt_secret.is_some()) {
+            (true, true) => Err(Error::from("AUTH_DISABLED=true contradicts AUTH_API_KEYS_FILE and AUTH_JWT_SECRET")),
+            (true, false) => {
+                event!(Level::WARN, "Authentication is disabled, every caller acts as admin.");
+                Ok(Auth::Disabled)
+            }
+            (false, false) => Err(Error::from("set AUTH_API_KEYS_FILE or AUTH_JWT_SECRET, or AUTH_DISABLED=true to run without authentication")),
+            (false, true) => Auth::new(api_keys.as_deref(), jwt_secret.as_deref()),
+        }
+    }
+
+    /// Enabled authentication with the API keys json and the JWT secret.
+    pub fn new(api_keys_json: Option<&str>, jwt_secret: Option<&str>) -> Result<Auth> {
+        let entries: Vec<ApiKeyEntry> = match api_keys_json {
+            Some(json) => serde_json::from_str(json)?,
+            None => vec![],
+        };
+        let mut api_keys = HashMap::new();
+        for entry in entries {
+            let hash = entry.api_key_sha256.to_lowercase();
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Error::from(format!("invalid api_key_sha256 of {}", entry.identity.name)));
+            }
+            if entry.identity.name.starts_with(PARTY_NAME_PREFIX) {
+                return Err(Error::from(format!("the name {} is reserved for registry parties", entry.identity.name)));
+            }
+            api_keys.insert(hash, entry.identity);
+        }
+        if jwt_secret.is_some_and(|secret| secret.len() < 32) {
+            return Err(Error::from("AUTH_JWT_SECRET needs at least 32 characters"));
+        }
+        let jwt_key = jwt_secret.map(|secret| Box::new(hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())));
+        Ok(Auth::Enabled { api_keys, jwt_key })
+    }
+
+    /// The identity behind the credentials, fails with `Unauthorized` for missing or invalid credentials.
+    pub fn authenticate(&self, credentials: &Credentials, db_url: &str) -> Result<Identity> {
+        let Auth::Enabled { api_keys, jwt_key } = self else {
+            return Ok(Identity { name: ANONYMOUS.to_string(), party_ids: vec![], admin: true, registry_party: None });
+        };
+        let Credentials::Bearer(BearerToken(Bearer { token })) = credentials else {
+            return Err(Error::Unauthorized("missing bearer token".to_string()));
+        };
+        if token.split('.').count() == 3 {
+            let key = jwt_key.as_ref().ok_or_else(|| Error::Unauthorized("JWTs are not accepted".to_string()))?;
+            return verify_jwt(key, token, Utc::now().timestamp());
+        }
+        let hash = sha256_hex(token);
+        if let Some(identity) = api_keys.get(&hash) {
+            return Ok(identity.clone());
+        }
+        party_ops::find_by_api_key(&hash, db_url)?
+            .map(|party| Identity::of_party(&party))
+            .ok_or_else(|| Error::Unauthorized("unknown API key".to_string()))
+    }
+}
+
+fn verify_jwt(key: &hmac::Key, token: &str, now: i64) -> Result<Identity> {
+    let invalid = |reason: &str| Error::Unauthorized(format!("invalid JWT: {}", reason));
+    let mut parts = token.split('.');
+    let (Some(header), Some(claims), Some(signature)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("malformed"));
+    };
+    let decode = |part: &str| BASE64_URL_SAFE_NO_PAD.decode(part).map_err(|_| invalid("malformed"));
+    let jwt_header: JwtHeader = serde_json::from_slice(&decode(header)?).map_err(|_| invalid("malformed header"))?;
+    if jwt_header.alg != "HS256" {
+        return Err(invalid("only HS256 is supported"));
+    }
+    hmac::verify(key, format!("{}.{}", header, claims).as_bytes(), &decode(signature)?)
+        .map_err(|_| invalid("signature does not match"))?;
+    let claims: JwtClaims = serde_json::from_slice(&decode(claims)?).map_err(|_| invalid("malformed claims"))?;
+    if claims.exp <= now {
+        return Err(invalid("expired"));
+    }
+    if claims.sub.starts_with(PARTY_NAME_PREFIX) {
+        return Err(invalid("the subject is reserved for registry parties"));
+    }
+    Ok(Identity { name: claims.sub, party_ids: claims.party_ids, admin: claims.admin, registry_party: None })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SECRET: &str = "a secret of at least 32 characters";
+    /// Unknown API keys are looked up in the party registry, the tests below never get there
+    const NO_DB: &str = "";
+
+    fn jwt(claims: &str, secret: &str) -> String {
+        let header = BASE64_URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+        let claims = BASE64_URL_SAFE_NO_PAD.encode(claims);
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let signature = hmac::sign(&key, format!("{}.{}", header, claims).as_bytes());
+        format!("{}.{}.{}", header, claims, BASE64_URL_SAFE_NO_PAD.encode(signature.as_ref()))
+    }
+
+    fn bearer(token: &str) -> Credentials {
+        Credentials::Bearer(BearerToken(Bearer { token: token.to_string() }))
+    }
+
+    #[test]
+    fn test_api_keys() -> Result<()> {
+        let json = format!(r#"[{{"name":"a","api_key_sha256":"{}","party_ids":[1]}}]"#, sha256_hex("key-a"));
+        let auth = Auth::new(Some(&json), None)?;
+        let identity = auth.authenticate(&bearer("key-a"), NO_DB)?;
+        assert_eq!(identity, Identity { name: "a".to_string(), party_ids: vec![1], admin: false, registry_party: None });
+        assert!(identity.require_party(1).is_ok());
+        assert!(matches!(identity.require_party(2), Err(Error::Forbidden(_))));
+        assert!(matches!(auth.authenticate(&Credentials::Missing, NO_DB), Err(Error::Unauthorized(_))));
+        assert!(matches!(auth.authenticate(&bearer(&jwt(r#"{"sub":"a","exp":4102444800}"#, SECRET)), NO_DB), Err(Error::Unauthorized(_))));
+        assert!(Auth::new(Some(r#"[{"name":"a","api_key_sha256":"abc"}]"#), None).is_err());
+        let spoofing = format!(r#"[{{"name":"party-7","api_key_sha256":"{}"}}]"#, sha256_hex("key-b"));
+        assert!(Auth::new(Some(&spoofing), None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt() -> Result<()> {
+        let auth = Auth::new(None, Some(SECRET))?;
+        let identity = auth.authenticate(&bearer(&jwt(r#"{"sub":"b","party_ids":[3],"admin":true,"exp":4102444800}"#, SECRET)), NO_DB)?;
+        assert_eq!(identity, Identity { name: "b".to_string(), party_ids: vec![3], admin: true, registry_party: None });
+        for token in [
+            jwt(r#"{"sub":"b","exp":4102444800}"#, "another secret of at least 32 chars"),
+            jwt(r#"{"sub":"b","exp":1000}"#, SECRET),
+            jwt(r#"{"sub":"b"}"#, SECRET),
+            jwt(r#"{"sub":"party-7","exp":4102444800}"#, SECRET),
+            "a.b.c".to_string(),
+        ] {
+            assert!(matches!(auth.authenticate(&bearer(&token), NO_DB), Err(Error::Unauthorized(_))), "{}", token);
+        }
+        assert!(Auth::new(None, Some("short")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_disabled() -> Result<()> {
+        let identity = Auth::Disabled.authenticate(&Credentials::Missing, NO_DB)?;
+        assert!(identity.admin);
+        assert!(identity.require_party(7).is_ok());
+        Ok(())
+    }
+}
@@ -1,18 +1,30 @@
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use chrono::Utc;
+use diesel::{Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use tracing::{event, Level};
 
 use crate::error::{Error, Result};
 
-use crate::db::{establish_connection, models::{Collaboration, ComputationResult, NewCollaboration}};
+use crate::db::{establish_connection, invitation_ops, models::{Collaboration, CollaborationStatus, ComputationResult, NewCollaboration, NewOutputRegistration, NewProgram, NewProgramVersion, PartyRole}, program_ops};
 
-/// Create new participation between user and collaboration
-pub fn create(collaboration: NewCollaboration, db_url: &str) -> Result<Collaboration> {
+/// Create a collaboration with `program` as first version of its program
+pub fn create(collaboration: NewCollaboration, program: NewProgram, db_url: &str) -> Result<Collaboration> {
     use crate::schema::collaborations;
 
     let mut connection = establish_connection(db_url)?;
-    let collab = diesel::insert_into(collaborations::table)
-        .values(&collaboration)
-        .get_result(&mut connection)?;
-    Ok(collab)
+    connection.transaction::<_, Error, _>(|conn| {
+        program_ops::store(conn, &program)?;
+        let collab: Collaboration = diesel::insert_into(collaborations::table)
+            .values(&collaboration)
+            .get_result(conn)?;
+        program_ops::add_version(conn, NewProgramVersion {
+            collab_id: collab.id,
+            version: collab.program_version,
+            program_sha256: collab.program_sha256.clone(),
+            proposed_by: collab.created_by.clone(),
+        })?;
+        Ok(collab)
+    })
 }
 
 pub fn list(db_url: &str) -> Result<Vec<Collaboration>> {
@@ -60,18 +72,109 @@ pub fn result_ids(collab_id: i32, db_url: &str) -> Result<Vec<String>> {
     return Err(Error::ProcessingNotFinished);
 }
 
-pub fn add_started_result(collab_id: i32, db_url: &str) -> Result<()> {
+/// Get the result of the execution, if it was started.
+pub fn get_result(collab_id: i32, db_url: &str) -> Result<Option<ComputationResult>> {
+    use crate::schema::computation_results;
+    let mut connection = establish_connection(db_url)?;
+
+    let result = computation_results::dsl::computation_results.find(collab_id)
+        .get_result::<ComputationResult>(&mut connection)
+        .optional()?;
+    Ok(result)
+}
+
+/// Get the collaboration, failing with `InvalidStatus` unless its status is one of `allowed`.
+pub fn require_status(collab_id: i32, allowed: &[CollaborationStatus], action: &str, db_url: &str) -> Result<Collaboration> {
+    let collab = get(collab_id, db_url)?;
+    if !allowed.contains(&collab.status) {
+        return Err(Error::InvalidStatus { collab_id, status: collab.status, action: action.to_string() });
+    }
+    Ok(collab)
+}
+
+/// Lock the collaboration row until the end of the surrounding transaction.
+///
+/// Uploads and the start of the execution hold this lock, so they see a consistent status and upload count.
+pub fn lock(connection: &mut PgConnection, collab_id: i32) -> Result<Collaboration> {
+    use crate::schema::collaborations::dsl::*;
+    let collab = collaborations.find(collab_id)
+        .select(Collaboration::as_select())
+        .for_update()
+        .first(connection)?;
+    Ok(collab)
+}
+
+/// Change the status of a collaboration locked with `lock`.
+pub fn set_status(connection: &mut PgConnection, collab: &mut Collaboration, next: CollaborationStatus) -> Result<()> {
+    use crate::schema::collaborations::dsl::*;
+
+    if !collab.status.can_transition_to(next) {
+        return Err(Error::InvalidStatus { collab_id: collab.id, status: collab.status, action: format!("change to {}", next) });
+    }
+    let now = Utc::now();
+    diesel::update(collaborations.find(collab.id))
+        .set((status.eq(next), updated_at.eq(now)))
+        .execute(connection)?;
+    collab.status = next;
+    collab.updated_at = now;
+    Ok(())
+}
+
+/// Claim the execution of a `Ready` collaboration: move it to `Running` and add the started result, which records
+/// the hash of the program to run.
+///
+/// Returns `None` if the collaboration is not ready, e.g. because a concurrent task claimed it first, or if
+/// a registered party has not approved the program yet.
+/// This is the only place an execution is started, so every collaboration runs at most once.
+pub fn claim_execution(collab_id: i32, db_url: &str) -> Result<Option<Collaboration>> {
     use crate::schema::computation_results;
+
     let mut connection = establish_connection(db_url)?;
-    let comp_result = ComputationResult {
-        collab_id,
-        result_ids: None,
-        finished: false,
-        error: None
-    };
-    let _ = diesel::insert_into(computation_results::table)
-        .values(&comp_result)
+    connection.transaction::<_, Error, _>(|conn| {
+        let mut collab = lock(conn, collab_id)?;
+        if collab.status != CollaborationStatus::Ready {
+            return Ok(None);
+        }
+        let missing = invitation_ops::missing_consents(conn, collab_id, &collab.program_sha256)?;
+        if !missing.is_empty() {
+            event!(Level::INFO, "Collaboration {} waits for the consent of parties {:?}.", collab_id, missing);
+            return Ok(None);
+        }
+        set_status(conn, &mut collab, CollaborationStatus::Running)?;
+        let comp_result = ComputationResult {
+            collab_id,
+            result_ids: None,
+            finished: false,
+            error: None,
+            started_at: Utc::now(),
+            finished_at: None,
+            program_sha256: collab.program_sha256.clone(),
+        };
+        diesel::insert_into(computation_results::table)
+            .values(&comp_result)
+            .execute(conn)?;
+        Ok(Some(collab))
+    })
+}
+
+/// Move the collaboration to `next`, failing with `InvalidStatus` if its current status does not allow it.
+pub fn transition(collab_id: i32, next: CollaborationStatus, db_url: &str) -> Result<()> {
+    transition_from(collab_id, &CollaborationStatus::predecessors(next), next, db_url)
+}
+
+/// Move the collaboration from one of the states `from` to `next`, only the edges of the lifecycle are taken.
+pub fn transition_from(collab_id: i32, from: &[CollaborationStatus], next: CollaborationStatus, db_url: &str) -> Result<()> {
+    use crate::schema::collaborations::dsl::*;
+
+    let from = from.iter().copied().filter(|state| state.can_transition_to(next)).collect::<Vec<CollaborationStatus>>();
+    let mut connection = establish_connection(db_url)?;
+    let updated = diesel::update(collaborations.find(collab_id).filter(status.eq_any(from)))
+        .set((status.eq(next), updated_at.eq(Utc::now())))
         .execute(&mut connection)?;
+    if updated == 0 {
+        let collab = get(collab_id, db_url)?;
+        return Err(Error::InvalidStatus { collab_id, status: collab.status, action: format!("change to {}", next) });
+    }
     Ok(())
 }
 
@@ -80,7 +183,7 @@ pub fn set_result_failed(id_of_collaboration: i32, message: String, db_url: &str
     let mut connection = establish_connection(db_url)?;
 
     diesel::update(computation_results.find(id_of_collaboration))
-        .set((finished.eq(true), error.eq(Some(message))))
+        .set((finished.eq(true), error.eq(Some(message)), finished_at.eq(Some(Utc::now()))))
         .execute(&mut connection)?;
     Ok(())
 }
@@ -90,27 +193,67 @@ pub fn set_result_finished(id_of_collaboration: i32, ids_of_result: Vec<Option<S
     let mut connection = establish_connection(db_url)?;
 
     diesel::update(computation_results.find(id_of_collaboration))
-        .set((finished.eq(true), result_ids.eq(Some(ids_of_result))))
+        .set((finished.eq(true), result_ids.eq(Some(ids_of_result)), finished_at.eq(Some(Utc::now()))))
         .execute(&mut connection)?;
     Ok(())
 }
 
-pub fn add_output_party(collab_id: i32, _party_id: i32, party_client_endpoint: String, db_url: &str) -> Result<()> {
-    use crate::schema::collaborations::dsl::*;
+/// Register an output party and the secret its notifications are signed with, once it approved the current program.
+///
+/// Registering the same party again replaces its secret. A `client_endpoint` replaces the endpoint of the party in
+/// the registry along with the registration, so the party is never notified with the new secret at a former endpoint.
+pub fn add_output_party(collab_id: i32, party_id: i32, secret: String, client_endpoint: Option<String>, db_url: &str) -> Result<()> {
+    use crate::schema::{output_registrations, parties};
 
     let mut connection = establish_connection(db_url)?;
-    let old_array: Collaboration = collaborations
-        .find(collab_id)
-        .get_result::<Collaboration>(&mut connection)?;
-    let output = match old_array.output_parties {
-        Some(mut parties) => {
-            parties.push(Some(party_client_endpoint));
-            parties
-        },
-        None => vec![Some(party_client_endpoint)],
-    };
-    diesel::update(collaborations.find(collab_id))
-        .set(output_parties.eq(Some(output)))
-        .execute(&mut connection)?;
-    Ok(())
-}
\ No newline at end of file
+    connection.transaction::<_, Error, _>(|conn| {
+        let collab = lock(conn, collab_id)?;
+        if collab.status.is_terminal() {
+            return Err(Error::InvalidStatus {
+                collab_id,
+                status: collab.status,
+                action: "register output parties".to_string(),
+            });
+        }
+        invitation_ops::require_consent(conn, collab_id, party_id, PartyRole::Output, &collab.program_sha256)?;
+        diesel::insert_into(output_registrations::table)
+            .values(&NewOutputRegistration { collab_id, party_id, secret: secret.clone() })
+            .on_conflict((output_registrations::collab_id, output_registrations::party_id))
+            .do_update()
+            .set(output_registrations::secret.eq(&secret))
+            .execute(conn)?;
+        if let Some(endpoint) = client_endpoint {
+            diesel::update(parties::table.find(party_id))
+                .set((parties::client_endpoint.eq(Some(endpoint)), parties::updated_at.eq(Utc::now())))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+/// Parties registered to receive the result of the collaboration.
+pub fn output_parties(collaboration: i32, db_url: &str) -> Result<Vec<i32>> {
+    use crate::schema::output_registrations;
+
+    let mut connection = establish_connection(db_url)?;
+    let party_ids = output_registrations::table
+        .filter(output_registrations::collab_id.eq(collaboration))
+        .select(output_registrations::party_id)
+        .load::<i32>(&mut connection)?;
+    Ok(party_ids)
+}
+
+/// Current client endpoint of the output party and the secret agreed on with it, `None` if the party is no output party of the collaboration.
+pub fn output_party_address(collaboration: i32, party: i32, db_url: &str) -> Result<Option<(Option<String>, String)>> {
+    use crate::schema::{output_registrations, parties};
+
+    let mut connection = establish_connection(db_url)?;
+    let address = output_registrations::table
+        .inner_join(parties::table)
+        .filter(output_registrations::collab_id.eq(collaboration))
+        .filter(output_registrations::party_id.eq(party))
+        .select((parties::client_endpoint, output_registrations::secret))
+        .first::<(Option<String>, String)>(&mut connection)
+        .optional()?;
+    Ok(address)
+}
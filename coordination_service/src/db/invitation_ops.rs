@@ -0,0 +1,176 @@
+use chrono::{DateTime, Utc};
+use diesel::{dsl::exists, select, Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::error::{Error, Result};
+
+use crate::db::{establish_connection, models::{Invitation, InvitationStatus, NewInvitation, PartyRole}};
+
+/// Pending invitations past their expiry are shown as expired.
+fn with_expiry(mut invitation: Invitation, now: DateTime<Utc>) -> Invitation {
+    if invitation.status == InvitationStatus::Pending && invitation.expires_at <= now {
+        invitation.status = InvitationStatus::Expired;
+    }
+    invitation
+}
+
+/// Invite a party. Inviting it again for the same role renews the invitation and drops its former answer and consent,
+/// which is rejected once the party is registered in this role.
+pub fn invite(invitation: NewInvitation, db_url: &str) -> Result<Invitation> {
+    use crate::schema::{invitations::dsl::*, output_registrations, participations};
+
+    let mut connection = establish_connection(db_url)?;
+    connection.transaction::<_, Error, _>(|conn| {
+        let registered = match invitation.role {
+            PartyRole::Input => select(exists(participations::table.find((invitation.collab_id, invitation.party_id)))).get_result(conn)?,
+            PartyRole::Output => select(exists(output_registrations::table.find((invitation.collab_id, invitation.party_id)))).get_result(conn)?,
+            PartyRole::Creator => false,
+        };
+        if registered {
+            return Err(Error::Unprocessable {
+                message: format!("Party {} is registered as {} party of collaboration {} already", invitation.party_id, invitation.role, invitation.collab_id),
+            });
+        }
+        let invited = diesel::insert_into(invitations)
+            .values(&invitation)
+            .on_conflict((collab_id, party_id, role))
+            .do_update()
+            .set((
+                status.eq(InvitationStatus::Pending),
+                expires_at.eq(invitation.expires_at),
+                invited_by.eq(&invitation.invited_by),
+                created_at.eq(Utc::now()),
+                responded_at.eq(None::<DateTime<Utc>>),
+                approved_program_sha256.eq(None::<String>),
+                approved_at.eq(None::<DateTime<Utc>>),
+            ))
+            .returning(Invitation::as_returning())
+            .get_result(conn)?;
+        Ok(invited)
+    })
+}
+
+pub fn list(collaboration: i32, db_url: &str) -> Result<Vec<Invitation>> {
+    use crate::schema::invitations::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let now = Utc::now();
+    let invited = invitations
+        .filter(collab_id.eq(collaboration))
+        .order(id)
+        .select(Invitation::as_select())
+        .load(&mut connection)?;
+    Ok(invited.into_iter().map(|invitation| with_expiry(invitation, now)).collect())
+}
+
+pub fn get(collaboration: i32, invitation_id: i32, db_url: &str) -> Result<Invitation> {
+    use crate::schema::invitations::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let invitation = invitations
+        .find(invitation_id)
+        .filter(collab_id.eq(collaboration))
+        .select(Invitation::as_select())
+        .first(&mut connection)
+        .optional()?
+        .ok_or(Error::InvitationNotFound { invitation_id })?;
+    Ok(with_expiry(invitation, Utc::now()))
+}
+
+/// Accept or decline a pending invitation.
+pub fn respond(collaboration: i32, invitation_id: i32, accept: bool, db_url: &str) -> Result<Invitation> {
+    use crate::schema::invitations::dsl::*;
+
+    let invitation = get(collaboration, invitation_id, db_url)?;
+    if invitation.status != InvitationStatus::Pending {
+        return Err(Error::Unprocessable { message: format!("Invitation {} is {}", invitation_id, invitation.status.as_str()) });
+    }
+    let answer = if accept { InvitationStatus::Accepted } else { InvitationStatus::Declined };
+    let mut connection = establish_connection(db_url)?;
+    // the status filter keeps concurrent answers from overwriting each other
+    let answered = diesel::update(invitations.find(invitation_id).filter(status.eq(InvitationStatus::Pending)))
+        .set((status.eq(answer), responded_at.eq(Some(Utc::now()))))
+        .returning(Invitation::as_returning())
+        .get_result(&mut connection)
+        .optional()?
+        .ok_or_else(|| Error::Unprocessable { message: format!("Invitation {} was answered already", invitation_id) })?;
+    Ok(answered)
+}
+
+/// Record the consent of the invited party to the program with the hash `program_sha256`.
+pub fn approve_program(collaboration: i32, invitation_id: i32, program_sha256: &str, db_url: &str) -> Result<Invitation> {
+    use crate::schema::invitations::dsl::*;
+
+    let invitation = get(collaboration, invitation_id, db_url)?;
+    if invitation.status != InvitationStatus::Accepted {
+        return Err(Error::Unprocessable { message: format!("Invitation {} is {}, accept it first", invitation_id, invitation.status.as_str()) });
+    }
+    let mut connection = establish_connection(db_url)?;
+    let approved = diesel::update(invitations.find(invitation_id))
+        .set((approved_program_sha256.eq(Some(program_sha256)), approved_at.eq(Some(Utc::now()))))
+        .returning(Invitation::as_returning())
+        .get_result(&mut connection)?;
+    Ok(approved)
+}
+
+/// Fails with `Forbidden` unless the party accepted an invitation for `as_role` and approved the program `program_sha256`.
+pub fn require_consent(connection: &mut PgConnection, collaboration: i32, party: i32, as_role: PartyRole, program_sha256: &str) -> Result<()> {
+    use crate::schema::invitations::dsl::*;
+
+    let invitation = invitations
+        .filter(collab_id.eq(collaboration))
+        .filter(party_id.eq(party))
+        .filter(role.eq(as_role))
+        .select(Invitation::as_select())
+        .first(connection)
+        .optional()?;
+    match invitation {
+        Some(invitation) if invitation.status == InvitationStatus::Accepted => {
+            if invitation.approved_program_sha256.as_deref() == Some(program_sha256) {
+                Ok(())
+            } else {
+                Err(Error::Forbidden(format!("party {} has not approved the current program of collaboration {}", party, collaboration)))
+            }
+        }
+        _ => Err(Error::Forbidden(format!("party {} has no accepted invitation as {} party to collaboration {}", party, as_role, collaboration))),
+    }
+}
+
+/// Registered input and output parties without an accepted invitation for their role that approved the program
+/// `program_sha256`.
+pub fn missing_consents(connection: &mut PgConnection, collaboration: i32, program_sha256: &str) -> Result<Vec<i32>> {
+    use crate::schema::{invitations, output_registrations, participations};
+
+    let consents = invitations::table
+        .filter(invitations::collab_id.eq(collaboration))
+        .filter(invitations::status.eq(InvitationStatus::Accepted))
+        .filter(invitations::approved_program_sha256.eq(program_sha256))
+        .select((invitations::party_id, invitations::role))
+        .load::<(i32, PartyRole)>(connection)?;
+    let inputs = participations::table
+        .filter(participations::collaboration_id.eq(collaboration))
+        .select(participations::party_id)
+        .load::<i32>(connection)?;
+    let outputs = output_registrations::table
+        .filter(output_registrations::collab_id.eq(collaboration))
+        .select(output_registrations::party_id)
+        .load::<i32>(connection)?;
+    let registered = inputs.into_iter().map(|party| (party, PartyRole::Input))
+        .chain(outputs.into_iter().map(|party| (party, PartyRole::Output)));
+    let mut missing = registered
+        .filter(|registration| !consents.contains(registration))
+        .map(|(party, _)| party)
+        .collect::<Vec<i32>>();
+    missing.sort();
+    missing.dedup();
+    Ok(missing)
+}
+
+/// Drop the approvals of the program of the collaboration, e.g. because a new version replaced it.
+pub fn revoke_approvals(connection: &mut PgConnection, collaboration: i32) -> Result<usize> {
+    use crate::schema::invitations::dsl::*;
+
+    let revoked = diesel::update(invitations.filter(collab_id.eq(collaboration)).filter(approved_program_sha256.is_not_null()))
+        .set((approved_program_sha256.eq(None::<String>), approved_at.eq(None::<DateTime<Utc>>)))
+        .execute(connection)?;
+    Ok(revoked)
+}
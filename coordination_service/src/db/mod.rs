@@ -7,6 +7,10 @@ pub mod models;
 pub mod participation_ops;
 pub mod collab_ops;
 pub mod csconfig_ops;
+pub mod notification_ops;
+pub mod invitation_ops;
+pub mod party_ops;
+pub mod program_ops;
 
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
 
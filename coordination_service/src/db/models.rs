@@ -1,7 +1,12 @@
-use poem_openapi::Object;
+use std::{fmt, io::Write};
+
+use base64::prelude::*;
+use chrono::{DateTime, Utc};
+use diesel::{deserialize::{self, FromSql, FromSqlRow}, expression::AsExpression, pg::{Pg, PgValue}, serialize::{self, Output, ToSql}, sql_types::Text};
+use poem_openapi::{Enum, Object};
 use serde::{Deserialize, Serialize};
 
-use crate::schema::{participations, collaborations, computation_results, csconfig, csprovider};
+use crate::schema::{participations, collaborations, computation_results, csconfig, csprovider, invitations, notifications, output_registrations, parties, program_versions, programs};
 use diesel::prelude::*;
 
 #[derive(Insertable, Object)]
@@ -46,16 +51,22 @@ pub struct CsProvider {
 pub struct NewCollaboration {
     /// Name of the collaboration
     pub name: String,
-    /// SPDZ Programm (base64 encoded)
-    pub mpc_program: String,
-    /// input parameter cs specification (base64 encoded)
+    /// csv header line, derived from the csv schema
     pub csv_specification: String,
     // Number of participating parties
     pub participation_number: i32,
     // id of cs configuration
     pub config_id: i32,
-    // endpoints of output parties
-    pub output_parties: Option<Vec<Option<String>>>
+    /// Number of fractional bits of fixed-point encoded secrets, 0 for plain integers
+    pub fixed_point_precision: i32,
+    /// Columns of the csv input parties upload, see `cs_interface::CsvSchema`
+    pub csv_schema: serde_json::Value,
+    /// Initial status, `Draft` or `Open`
+    pub status: CollaborationStatus,
+    /// Name of the identity that created the collaboration
+    pub created_by: Option<String>,
+    /// SHA-256 of the first version of the program
+    pub program_sha256: String,
 }
 
 #[derive(Queryable, Selectable, AsChangeset, Object)]
@@ -64,16 +75,27 @@ pub struct Collaboration {
     pub id: i32,
     /// Name of the collaboration
     pub name: String,
-    /// SPDZ Programm (base64 encoded)
-    pub mpc_program: String,
-    /// input parameter cs specification (base64 encoded)
+    /// csv header line, derived from the csv schema
     pub csv_specification: String,
     // Number of participating parties
     pub participation_number: i32,
     // id of cs configuration
     pub config_id: i32,
-    // endpoints of output parties
-    pub output_parties: Option<Vec<Option<String>>>
+    /// Number of fractional bits of fixed-point encoded secrets, 0 for plain integers
+    pub fixed_point_precision: i32,
+    /// Columns of the csv input parties upload, see `cs_interface::CsvSchema`
+    pub csv_schema: serde_json::Value,
+    /// Current step in the lifecycle of the collaboration
+    pub status: CollaborationStatus,
+    pub created_at: DateTime<Utc>,
+    /// Time of the last status change
+    pub updated_at: DateTime<Utc>,
+    /// Name of the identity that created the collaboration, it may delete it
+    pub created_by: Option<String>,
+    /// Hex encoded SHA-256 of the current program, parties consent to this hash
+    pub program_sha256: String,
+    /// Version of the current program, counting from 1
+    pub program_version: i32,
 }
 
 #[derive(Insertable)]
@@ -92,6 +114,9 @@ pub struct Participation {
     pub collaboration_id: i32,
     pub party_id: i32,
     pub secret_ids: Option<Vec<Option<String>>>,
+    pub registered_at: DateTime<Utc>,
+    /// Time the party confirmed its upload
+    pub uploaded_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Queryable, Selectable, AsChangeset, Insertable)]
@@ -102,5 +127,404 @@ pub struct ComputationResult {
     pub collab_id: i32,
     pub result_ids: Option<Vec<Option<String>>>,
     pub finished: bool,
-    pub error: Option<String>
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// SHA-256 of the program that was executed
+    pub program_sha256: String,
+}
+
+/// Lifecycle of a collaboration.
+///
+/// `Draft` collaborations are not visible to input parties yet. Parties register and upload while the
+/// collaboration is `Open` or `Collecting`, it becomes `Ready` once all uploads are confirmed and is
+/// `Running` during the MPC execution.
+#[derive(Enum, Serialize, Deserialize, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CollaborationStatus {
+    Draft,
+    Open,
+    Collecting,
+    Ready,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    Archived,
+}
+
+impl CollaborationStatus {
+    pub const ALL: [CollaborationStatus; 9] = [
+        CollaborationStatus::Draft,
+        CollaborationStatus::Open,
+        CollaborationStatus::Collecting,
+        CollaborationStatus::Ready,
+        CollaborationStatus::Running,
+        CollaborationStatus::Succeeded,
+        CollaborationStatus::Failed,
+        CollaborationStatus::Cancelled,
+        CollaborationStatus::Archived,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CollaborationStatus::Draft => "draft",
+            CollaborationStatus::Open => "open",
+            CollaborationStatus::Collecting => "collecting",
+            CollaborationStatus::Ready => "ready",
+            CollaborationStatus::Running => "running",
+            CollaborationStatus::Succeeded => "succeeded",
+            CollaborationStatus::Failed => "failed",
+            CollaborationStatus::Cancelled => "cancelled",
+            CollaborationStatus::Archived => "archived",
+        }
+    }
+
+    /// Whether the lifecycle allows to go from `self` to `next`.
+    ///
+    /// `Collecting` and `Ready` reopen when a new program version drops the uploads, see `program_ops::propose`.
+    pub fn can_transition_to(&self, next: CollaborationStatus) -> bool {
+        use CollaborationStatus::*;
+        matches!(
+            (self, next),
+            (Draft, Open)
+                | (Collecting | Ready, Open)
+                | (Open, Collecting)
+                | (Collecting, Ready)
+                | (Ready, Running)
+                | (Running, Succeeded)
+                | (Running, Failed)
+                | (Draft | Open | Collecting | Ready, Cancelled)
+                | (Succeeded | Failed | Cancelled, Archived)
+        )
+    }
+
+    /// All states `next` can be reached from.
+    pub fn predecessors(next: CollaborationStatus) -> Vec<CollaborationStatus> {
+        Self::ALL.into_iter().filter(|s| s.can_transition_to(next)).collect()
+    }
+
+    /// No computation will happen anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            CollaborationStatus::Succeeded
+                | CollaborationStatus::Failed
+                | CollaborationStatus::Cancelled
+                | CollaborationStatus::Archived
+        )
+    }
+}
+
+impl fmt::Display for CollaborationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ToSql<Text, Pg> for CollaborationStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for CollaborationStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = std::str::from_utf8(bytes.as_bytes())?;
+        CollaborationStatus::ALL
+            .into_iter()
+            .find(|s| s.as_str() == value)
+            .ok_or_else(|| format!("Unknown collaboration status {}", value).into())
+    }
+}
+
+/// Registration of an output party, it is notified at the client endpoint of the party
+#[derive(Insertable)]
+#[diesel(table_name = output_registrations)]
+pub struct NewOutputRegistration {
+    pub collab_id: i32,
+    pub party_id: i32,
+    /// HMAC key of the notifications to this output party
+    pub secret: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = parties)]
+pub struct NewParty {
+    pub display_name: String,
+    pub organisation: Option<String>,
+    pub roles: Vec<PartyRole>,
+    pub client_endpoint: Option<String>,
+    pub api_key_sha256: Option<String>,
+}
+
+/// Changes of a party, unset fields are kept
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = parties)]
+pub struct PartyChanges {
+    pub display_name: Option<String>,
+    pub organisation: Option<String>,
+    pub roles: Option<Vec<PartyRole>>,
+    pub client_endpoint: Option<String>,
+}
+
+/// A party of the registry, participations and output registrations refer to it by id
+#[derive(Queryable, Selectable, Object, Serialize, Debug)]
+#[diesel(table_name = parties)]
+pub struct Party {
+    pub id: i32,
+    /// Name shown to the other parties
+    pub display_name: String,
+    pub organisation: Option<String>,
+    /// What the party may do in collaborations
+    pub roles: Vec<PartyRole>,
+    /// Url of the client service of the party, output parties are notified there
+    pub client_endpoint: Option<String>,
+    #[oai(skip)]
+    #[serde(skip)]
+    pub api_key_sha256: Option<String>,
+    /// Deactivated parties can neither authenticate nor register for collaborations
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Party {
+    pub fn has_role(&self, role: PartyRole) -> bool {
+        self.roles.contains(&role)
+    }
+}
+
+/// Role of a party in collaborations.
+#[derive(Enum, Serialize, Deserialize, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PartyRole {
+    /// Registers as input party and uploads secrets
+    Input,
+    /// Registers as output party and is notified about results
+    Output,
+    /// Creates collaborations
+    Creator,
+}
+
+impl PartyRole {
+    pub const ALL: [PartyRole; 3] = [PartyRole::Input, PartyRole::Output, PartyRole::Creator];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PartyRole::Input => "input",
+            PartyRole::Output => "output",
+            PartyRole::Creator => "creator",
+        }
+    }
+}
+
+impl fmt::Display for PartyRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ToSql<Text, Pg> for PartyRole {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for PartyRole {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = std::str::from_utf8(bytes.as_bytes())?;
+        PartyRole::ALL
+            .into_iter()
+            .find(|r| r.as_str() == value)
+            .ok_or_else(|| format!("Unknown party role {}", value).into())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = notifications)]
+pub struct NewNotification {
+    pub collab_id: i32,
+    pub party_id: i32,
+    pub payload: String,
+}
+
+/// A result notice for an output party, kept until it was delivered
+#[derive(Queryable, Selectable, Object, Serialize)]
+#[diesel(table_name = notifications)]
+#[diesel(belongs_to(Collaboration, foreign_key = collab_id))]
+pub struct Notification {
+    pub id: i32,
+    pub collab_id: i32,
+    /// Output party notified at its registry endpoint
+    pub party_id: i32,
+    /// Json body sent to `{client_endpoint}/notify`
+    pub payload: String,
+    pub status: NotificationStatus,
+    /// Number of failed and successful delivery attempts
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Delivery state of a notification.
+#[derive(Enum, Serialize, Deserialize, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationStatus {
+    /// Waiting for the next delivery attempt
+    Pending,
+    Delivered,
+    /// All attempts failed, only delivered again on request
+    Dead,
+}
+
+impl NotificationStatus {
+    pub const ALL: [NotificationStatus; 3] = [NotificationStatus::Pending, NotificationStatus::Delivered, NotificationStatus::Dead];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationStatus::Pending => "pending",
+            NotificationStatus::Delivered => "delivered",
+            NotificationStatus::Dead => "dead",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for NotificationStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for NotificationStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = std::str::from_utf8(bytes.as_bytes())?;
+        NotificationStatus::ALL
+            .into_iter()
+            .find(|s| s.as_str() == value)
+            .ok_or_else(|| format!("Unknown notification status {}", value).into())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = invitations)]
+pub struct NewInvitation {
+    pub collab_id: i32,
+    pub party_id: i32,
+    pub role: PartyRole,
+    pub expires_at: DateTime<Utc>,
+    pub invited_by: String,
+}
+
+/// Invitation of a party to a collaboration, the party accepts it and approves the program before it registers
+#[derive(Queryable, Selectable, Object, Serialize)]
+#[diesel(table_name = invitations)]
+#[diesel(belongs_to(Collaboration, foreign_key = collab_id))]
+pub struct Invitation {
+    pub id: i32,
+    pub collab_id: i32,
+    pub party_id: i32,
+    /// `input` or `output`
+    pub role: PartyRole,
+    pub status: InvitationStatus,
+    /// Pending invitations can not be accepted after this time
+    pub expires_at: DateTime<Utc>,
+    /// Name of the identity that invited the party
+    pub invited_by: String,
+    pub created_at: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+    /// SHA-256 of the program the party consented to
+    pub approved_program_sha256: Option<String>,
+    pub approved_at: Option<DateTime<Utc>>,
+}
+
+/// Answer of a party to an invitation.
+#[derive(Enum, Serialize, Deserialize, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Text)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum InvitationStatus {
+    Pending,
+    Accepted,
+    Declined,
+    /// Pending past its expiry, never stored
+    Expired,
+}
+
+impl InvitationStatus {
+    pub const ALL: [InvitationStatus; 4] = [InvitationStatus::Pending, InvitationStatus::Accepted, InvitationStatus::Declined, InvitationStatus::Expired];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvitationStatus::Pending => "pending",
+            InvitationStatus::Accepted => "accepted",
+            InvitationStatus::Declined => "declined",
+            InvitationStatus::Expired => "expired",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for InvitationStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for InvitationStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = std::str::from_utf8(bytes.as_bytes())?;
+        InvitationStatus::ALL
+            .into_iter()
+            .find(|s| s.as_str() == value)
+            .ok_or_else(|| format!("Unknown invitation status {}", value).into())
+    }
+}
+
+/// SPDZ program (base64 encoded), stored once per content and never changed
+#[derive(Insertable)]
+#[diesel(table_name = programs)]
+pub struct NewProgram {
+    pub sha256: String,
+    pub source: String,
+}
+
+impl NewProgram {
+    pub fn new(program: &[u8]) -> NewProgram {
+        NewProgram { sha256: crate::auth::sha256_hex(program), source: BASE64_STANDARD.encode(program) }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = program_versions)]
+pub struct NewProgramVersion {
+    pub collab_id: i32,
+    pub version: i32,
+    pub program_sha256: String,
+    pub proposed_by: Option<String>,
+}
+
+/// A version of the program of a collaboration
+#[derive(Queryable, Selectable, Object, Serialize)]
+#[diesel(table_name = program_versions)]
+#[diesel(belongs_to(Collaboration, foreign_key = collab_id))]
+pub struct ProgramVersion {
+    pub collab_id: i32,
+    pub version: i32,
+    /// Hex encoded SHA-256 of the program
+    pub program_sha256: String,
+    /// Name of the identity that proposed this version
+    pub proposed_by: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
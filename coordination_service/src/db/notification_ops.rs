@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use diesel::{upsert::excluded, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::error::{Error, Result};
+
+use crate::db::{establish_connection, models::{NewNotification, Notification, NotificationStatus}};
+
+/// Add a pending notification for each party. A former notification of the collaboration to the same party is replaced.
+pub fn enqueue(collaboration: i32, mut parties: Vec<i32>, body: String, db_url: &str) -> Result<Vec<Notification>> {
+    use crate::schema::notifications::dsl::*;
+
+    parties.sort();
+    parties.dedup();
+    if parties.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut connection = establish_connection(db_url)?;
+    let new_notifications = parties
+        .into_iter()
+        .map(|party| NewNotification { collab_id: collaboration, party_id: party, payload: body.clone() })
+        .collect::<Vec<NewNotification>>();
+    let queued = diesel::insert_into(notifications)
+        .values(&new_notifications)
+        .on_conflict((collab_id, party_id))
+        .do_update()
+        .set((
+            payload.eq(excluded(payload)),
+            status.eq(NotificationStatus::Pending),
+            attempts.eq(0),
+            next_attempt_at.eq(Utc::now()),
+            last_error.eq(None::<String>),
+            delivered_at.eq(None::<DateTime<Utc>>),
+        ))
+        .returning(Notification::as_returning())
+        .get_results(&mut connection)?;
+    Ok(queued)
+}
+
+/// Take up to `limit` due notifications for delivery.
+///
+/// Their next attempt is moved to `lease_until`, so concurrent workers skip them while they are delivered.
+pub fn claim_due(limit: i64, lease_until: DateTime<Utc>, db_url: &str) -> Result<Vec<Notification>> {
+    use crate::schema::notifications::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    connection.transaction::<_, Error, _>(|conn| {
+        let due = notifications
+            .filter(status.eq(NotificationStatus::Pending))
+            .filter(next_attempt_at.le(Utc::now()))
+            .order(next_attempt_at)
+            .limit(limit)
+            .select(id)
+            .for_update()
+            .skip_locked()
+            .load::<i32>(conn)?;
+        let claimed = diesel::update(notifications.filter(id.eq_any(due)))
+            .set(next_attempt_at.eq(lease_until))
+            .returning(Notification::as_returning())
+            .get_results(conn)?;
+        Ok(claimed)
+    })
+}
+
+pub fn mark_delivered(notification_id: i32, db_url: &str) -> Result<()> {
+    use crate::schema::notifications::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    diesel::update(notifications.find(notification_id))
+        .set((
+            status.eq(NotificationStatus::Delivered),
+            attempts.eq(attempts + 1),
+            last_error.eq(None::<String>),
+            delivered_at.eq(Some(Utc::now())),
+        ))
+        .execute(&mut connection)?;
+    Ok(())
+}
+
+/// Record a failed attempt. Without a next attempt the notification is dead.
+pub fn mark_failed(notification_id: i32, message: String, next_attempt: Option<DateTime<Utc>>, db_url: &str) -> Result<()> {
+    use crate::schema::notifications::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let (next_status, next_at) = match next_attempt {
+        Some(at) => (NotificationStatus::Pending, at),
+        None => (NotificationStatus::Dead, Utc::now()),
+    };
+    diesel::update(notifications.find(notification_id))
+        .set((
+            status.eq(next_status),
+            attempts.eq(attempts + 1),
+            next_attempt_at.eq(next_at),
+            last_error.eq(Some(message)),
+        ))
+        .execute(&mut connection)?;
+    Ok(())
+}
+
+pub fn list(collaboration: Option<i32>, with_status: Option<NotificationStatus>, db_url: &str) -> Result<Vec<Notification>> {
+    use crate::schema::notifications::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let mut query = notifications.select(Notification::as_select()).order(id).into_boxed();
+    if let Some(collaboration) = collaboration {
+        query = query.filter(collab_id.eq(collaboration));
+    }
+    if let Some(with_status) = with_status {
+        query = query.filter(status.eq(with_status));
+    }
+    Ok(query.load(&mut connection)?)
+}
+
+/// Queue a notification again with a fresh number of attempts.
+pub fn redeliver(notification_id: i32, db_url: &str) -> Result<Notification> {
+    use crate::schema::notifications::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let notification = diesel::update(notifications.find(notification_id))
+        .set((
+            status.eq(NotificationStatus::Pending),
+            attempts.eq(0),
+            next_attempt_at.eq(Utc::now()),
+        ))
+        .returning(Notification::as_returning())
+        .get_result(&mut connection)?;
+    Ok(notification)
+}
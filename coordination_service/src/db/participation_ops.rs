@@ -1,12 +1,13 @@
-use cs_interface::{CsClient, JavaCsClient};
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use chrono::Utc;
+use cs_interface::{CsClient, NativeCsClient};
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
 use poem_openapi::Object;
 use tracing::{event, Level};
 
 use crate::{
     api::config::get_config, db::{
-        collab_ops, establish_connection, models::{NewParticipation, Participation}
-    }, error::Result, notification_service::notify_parties
+        collab_ops, establish_connection, invitation_ops, models::{CollaborationStatus, NewParticipation, Participation, PartyRole}, party_ops, program_ops
+    }, error::{Error, Result}, notification_service::notify_parties
 };
 
 #[derive(Object)]
@@ -24,8 +25,14 @@ pub fn create_participation(collaboration_id: i32, party_id: i32, db_url: &str)
 
     let mut connection = establish_connection(db_url)?;
 
-    // try to get collaboration
-    let _ = collab_ops::get(collaboration_id, db_url)?;
+    let collab = collab_ops::require_status(
+        collaboration_id,
+        &[CollaborationStatus::Open, CollaborationStatus::Collecting],
+        "register input parties",
+        db_url,
+    )?;
+    party_ops::require_role(party_id, PartyRole::Input, db_url)?;
+    invitation_ops::require_consent(&mut connection, collaboration_id, party_id, PartyRole::Input, &collab.program_sha256)?;
 
     let new_participation = NewParticipation {
         collaboration_id,
@@ -49,6 +56,11 @@ pub fn list_participations(collab_id: i32, db_url: &str) -> Result<Vec<Participa
 
 pub fn delete_participation(collaboration: i32, party: i32, db_url: &str) -> Result<()> {
     use crate::schema::participations::dsl::*;
+    // the uploads of all parties are used while the computation is prepared and running
+    let collab = collab_ops::get(collaboration, db_url)?;
+    if matches!(collab.status, CollaborationStatus::Ready | CollaborationStatus::Running) {
+        return Err(Error::InvalidStatus { collab_id: collaboration, status: collab.status, action: "remove input parties".to_string() });
+    }
     let mut connection = establish_connection(db_url)?;
     diesel::delete(participations.find((collaboration, party))).execute(&mut connection)?;
     Ok(())
@@ -57,86 +69,112 @@ pub fn delete_participation(collaboration: i32, party: i32, db_url: &str) -> Res
 pub fn upload_done(collaboration: i32, party: i32, ids: Vec<String>, db_url: &str) -> Result<Participation> {
     use crate::schema::participations::dsl::*;
     let mut connection = establish_connection(db_url)?;
-    let update_participation = diesel::update(participations.find((collaboration, party)))
-        .set(
-            secret_ids.eq(Some(
-                ids.into_iter()
-                    .map(|secret_id| Some(secret_id))
-                    .collect::<Vec<Option<String>>>(),
-            )),
-        )
-        .get_result::<Participation>(&mut connection)?;
-    let database_string = db_url.to_string();
-    tokio::spawn(async move { check_and_execute(collaboration, &database_string).await });
+    // concurrent uploads to the same collaboration are serialized by the lock, so exactly one of them sees all uploads
+    let (update_participation, ready) = connection.transaction::<_, Error, _>(|conn| {
+        let mut collab = collab_ops::lock(conn, collaboration)?;
+        if !matches!(collab.status, CollaborationStatus::Open | CollaborationStatus::Collecting) {
+            return Err(Error::InvalidStatus { collab_id: collaboration, status: collab.status, action: "confirm uploads".to_string() });
+        }
+        let update_participation = diesel::update(participations.find((collaboration, party)))
+            .set((
+                secret_ids.eq(Some(
+                    ids.into_iter()
+                        .map(|secret_id| Some(secret_id))
+                        .collect::<Vec<Option<String>>>(),
+                )),
+                uploaded_at.eq(Some(Utc::now())),
+            ))
+            .get_result::<Participation>(conn)?;
+        // uploads are bound to the current program, see `program_ops::propose`
+        invitation_ops::require_consent(conn, collaboration, party, PartyRole::Input, &collab.program_sha256)?;
+        if collab.status == CollaborationStatus::Open {
+            collab_ops::set_status(conn, &mut collab, CollaborationStatus::Collecting)?;
+        }
+        let uploads: i64 = participations
+            .filter(collaboration_id.eq(collaboration))
+            .filter(secret_ids.is_not_null())
+            .count()
+            .get_result(conn)?;
+        event!(
+            Level::INFO,
+            "Collaboration {} has {} of {} uploads.",
+            collaboration,
+            uploads,
+            collab.participation_number
+        );
+        let ready = uploads >= collab.participation_number as i64;
+        if ready {
+            collab_ops::set_status(conn, &mut collab, CollaborationStatus::Ready)?;
+        }
+        Ok((update_participation, ready))
+    })?;
+    if ready {
+        start_execution(collaboration, db_url);
+    }
     Ok(update_participation)
 }
 
-async fn check_and_execute(collab_id: i32, db_url: &str) -> Result<()> {
-    event!(
-        Level::INFO,
-        "Checking if collaboration {} is ready for execution.",
-        collab_id
-    );
-
-    let collab = collab_ops::get(collab_id, db_url)?;
+/// Start the execution of a `Ready` collaboration in the background, see `check_and_execute`.
+pub fn start_execution(collab_id: i32, db_url: &str) {
+    let database_string = db_url.to_string();
+    tokio::spawn(async move { check_and_execute(collab_id, &database_string).await });
+}
 
-    let current_participations = list_participations(collab_id, db_url)?;
-    let participation_nr = current_participations.iter().filter(|p| p.secret_ids.is_some()).count();
+/// Record the failed execution of a `Running` collaboration and move it to `Failed`.
+///
+/// Best effort, errors are logged: a step failing must not keep the other from leaving the collaboration `Running`.
+pub fn fail_execution(collab_id: i32, message: &str, db_url: &str) {
+    if let Err(err) = collab_ops::set_result_failed(collab_id, message.to_string(), db_url) {
+        event!(Level::ERROR, "Unable to record the error of collaboration {}: {}", collab_id, err);
+    }
+    if let Err(err) = collab_ops::transition(collab_id, CollaborationStatus::Failed, db_url) {
+        event!(Level::ERROR, "Unable to mark collaboration {} as failed: {}", collab_id, err);
+    }
+}
 
-    if participation_nr < collab.participation_number as usize {
-        event!(
-            Level::INFO,
-            "Insufficient participants to start computation. Current cout: {} of {}.",
-            participation_nr,
-            collab.participation_number as i64
-        );
+async fn check_and_execute(collab_id: i32, db_url: &str) -> Result<()> {
+    let Some(collab) = collab_ops::claim_execution(collab_id, db_url)? else {
+        event!(Level::INFO, "Execution of collaboration {} was not claimed.", collab_id);
         return Ok(());
-    }
+    };
     event!(
         Level::INFO,
         "Starting MPC execution of collaboration {}.",
         collab.id
     );
-
-    // Set execution result as started
-    collab_ops::add_started_result(collab_id, db_url)?;
-
-    // collect secret ids
-    let secret_ids = current_participations
-        .into_iter()
-        .filter_map(|p| p.secret_ids)
-        .flatten()
-        .filter_map(|secret_id| secret_id)
-        .collect::<Vec<String>>();
-
-    let output_parties = match collab.output_parties {
-        Some(parties) => parties
+    // every failure from here on has to end in the failed state, the collaboration is running
+    let prepared = list_participations(collab_id, db_url).and_then(|current_participations| {
+        // collect secret ids
+        let secret_ids = current_participations
             .into_iter()
-            .filter_map(|e| e)
-            .collect::<Vec<String>>(),
-        None => vec![],
+            .filter_map(|p| p.secret_ids)
+            .flatten()
+            .filter_map(|secret_id| secret_id)
+            .collect::<Vec<String>>();
+        Ok((get_config(collab_id, db_url)?, program_ops::source(&collab.program_sha256, db_url)?, secret_ids))
+    });
+    let result = match prepared {
+        Ok((config, program, secret_ids)) => async { NativeCsClient::new(config)?.execute_program(program, secret_ids).await }
+            .await
+            .map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
     };
-    //let (res, res_ids) =
-    //    execute_program(collab.mpc_program, collab.id, secret_ids, collab.config_id, db_url);
-    let config = get_config(collab_id, db_url)?;
-    let result = JavaCsClient::new(config)?.execute_program(collab.mpc_program, secret_ids);
+    let result = result.and_then(|res_ids| {
+        // write results
+        collab_ops::set_result_finished(collab_id, res_ids.iter().cloned().map(Some).collect(), db_url)
+            .and_then(|_| collab_ops::transition(collab_id, CollaborationStatus::Succeeded, db_url))
+            .map(|_| res_ids)
+            .map_err(|err| format!("Unable to store the result: {}", err))
+    });
     let res = match result {
-        Ok(res_id) => {
-            // write results
-            collab_ops::set_result_finished(
-                collab_id,
-                vec![Some(res_id.clone())], 
-                db_url)?;
-            ExecutionResult {
-                message: "Success".to_string(),
-                code: 200,
-                collaboration_id: collab_id,
-                secret_id: Some(res_id)
-            }
+        Ok(res_ids) => ExecutionResult {
+            message: "Success".to_string(),
+            code: 200,
+            collaboration_id: collab_id,
+            secret_id: res_ids.first().cloned()
         },
-        Err(err) => {
-            let err_message = err.to_string();
-            collab_ops::set_result_failed(collab_id, err_message.clone(), db_url)?;
+        Err(err_message) => {
+            fail_execution(collab_id, &err_message, db_url);
             ExecutionResult {
                 message: err_message,
                 code: 500,
@@ -145,6 +183,13 @@ async fn check_and_execute(collab_id: i32, db_url: &str) -> Result<()> {
             }
         }
     };
-    notify_parties(output_parties, res).await?;
+    let output_parties = match collab_ops::output_parties(collab_id, db_url) {
+        Ok(output_parties) => output_parties,
+        Err(err) => {
+            event!(Level::ERROR, "Unable to notify the output parties of collaboration {}: {}", collab_id, err);
+            return Ok(());
+        }
+    };
+    notify_parties(collab_id, output_parties, res, db_url).await?;
     Ok(())
 }
@@ -0,0 +1,105 @@
+use chrono::Utc;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::error::{Error, Result};
+
+use crate::db::{establish_connection, models::{NewParty, Party, PartyChanges, PartyRole}};
+
+/// Add a party to the registry
+pub fn create(party: NewParty, db_url: &str) -> Result<Party> {
+    use crate::schema::parties;
+
+    let mut connection = establish_connection(db_url)?;
+    let party = diesel::insert_into(parties::table)
+        .values(&party)
+        .returning(Party::as_returning())
+        .get_result(&mut connection)?;
+    Ok(party)
+}
+
+pub fn list(include_inactive: bool, db_url: &str) -> Result<Vec<Party>> {
+    use crate::schema::parties::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let mut query = parties.select(Party::as_select()).order(id).into_boxed();
+    if !include_inactive {
+        query = query.filter(active.eq(true));
+    }
+    Ok(query.load(&mut connection)?)
+}
+
+pub fn get(party_id: i32, db_url: &str) -> Result<Party> {
+    use crate::schema::parties::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    parties.find(party_id)
+        .select(Party::as_select())
+        .first(&mut connection)
+        .optional()?
+        .ok_or(Error::PartyNotFound { party_id })
+}
+
+/// Get the party, failing unless it is active and has `role`.
+pub fn require_role(party_id: i32, role: PartyRole, db_url: &str) -> Result<Party> {
+    let party = get(party_id, db_url)?;
+    if !party.active {
+        return Err(Error::Unprocessable { message: format!("Party {} is deactivated", party_id) });
+    }
+    if !party.has_role(role) {
+        return Err(Error::Unprocessable { message: format!("Party {} does not have the role {}", party_id, role) });
+    }
+    Ok(party)
+}
+
+/// Apply the set fields of `changes`.
+pub fn update(party_id: i32, changes: PartyChanges, db_url: &str) -> Result<Party> {
+    use crate::schema::parties::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    diesel::update(parties.find(party_id))
+        .set((&changes, updated_at.eq(Utc::now())))
+        .returning(Party::as_returning())
+        .get_result(&mut connection)
+        .optional()?
+        .ok_or(Error::PartyNotFound { party_id })
+}
+
+/// Deactivate the party, it stays in the registry as former collaborations refer to it.
+pub fn deactivate(party_id: i32, db_url: &str) -> Result<Party> {
+    use crate::schema::parties::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    diesel::update(parties.find(party_id))
+        .set((active.eq(false), updated_at.eq(Utc::now())))
+        .returning(Party::as_returning())
+        .get_result(&mut connection)
+        .optional()?
+        .ok_or(Error::PartyNotFound { party_id })
+}
+
+/// Replace the API key of the party, the former key is no longer accepted.
+pub fn set_api_key(party_id: i32, key_sha256: String, db_url: &str) -> Result<Party> {
+    use crate::schema::parties::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    diesel::update(parties.find(party_id))
+        .set((api_key_sha256.eq(Some(key_sha256)), updated_at.eq(Utc::now())))
+        .returning(Party::as_returning())
+        .get_result(&mut connection)
+        .optional()?
+        .ok_or(Error::PartyNotFound { party_id })
+}
+
+/// The active party with the API key of the given hash.
+pub fn find_by_api_key(key_sha256: &str, db_url: &str) -> Result<Option<Party>> {
+    use crate::schema::parties::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let party = parties
+        .filter(api_key_sha256.eq(key_sha256))
+        .filter(active.eq(true))
+        .select(Party::as_select())
+        .first(&mut connection)
+        .optional()?;
+    Ok(party)
+}
@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use diesel::{Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+use tracing::{event, Level};
+
+use crate::error::{Error, Result};
+
+use crate::db::{collab_ops, establish_connection, invitation_ops, models::{CollaborationStatus, NewProgram, NewProgramVersion, ProgramVersion}};
+
+/// Store the program, a program with the same hash is kept as it is.
+pub fn store(connection: &mut PgConnection, program: &NewProgram) -> Result<()> {
+    use crate::schema::programs;
+
+    diesel::insert_into(programs::table)
+        .values(program)
+        .on_conflict_do_nothing()
+        .execute(connection)?;
+    Ok(())
+}
+
+/// The base64 encoded program with the given hash.
+pub fn source(program_sha256: &str, db_url: &str) -> Result<String> {
+    use crate::schema::programs::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let program = programs.find(program_sha256)
+        .select(source)
+        .first(&mut connection)?;
+    Ok(program)
+}
+
+pub fn add_version(connection: &mut PgConnection, version: NewProgramVersion) -> Result<ProgramVersion> {
+    use crate::schema::program_versions;
+
+    let added = diesel::insert_into(program_versions::table)
+        .values(&version)
+        .returning(ProgramVersion::as_returning())
+        .get_result(connection)?;
+    Ok(added)
+}
+
+/// All versions of the program of the collaboration, the first one first.
+pub fn list_versions(collaboration: i32, db_url: &str) -> Result<Vec<ProgramVersion>> {
+    use crate::schema::program_versions::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    let versions = program_versions
+        .filter(collab_id.eq(collaboration))
+        .order(version)
+        .select(ProgramVersion::as_select())
+        .load(&mut connection)?;
+    Ok(versions)
+}
+
+pub fn get_version(collaboration: i32, program_version: i32, db_url: &str) -> Result<ProgramVersion> {
+    use crate::schema::program_versions::dsl::*;
+
+    let mut connection = establish_connection(db_url)?;
+    program_versions.find((collaboration, program_version))
+        .select(ProgramVersion::as_select())
+        .first(&mut connection)
+        .optional()?
+        .ok_or(Error::ProgramVersionNotFound { collab_id: collaboration, version: program_version })
+}
+
+/// Make `program` the current program of the collaboration as its next version.
+///
+/// Approvals of the former program no longer count, parties approve the new version before the collaboration runs.
+/// Uploads confirmed for the former program are dropped and a `Collecting` or `Ready` collaboration is `Open` again,
+/// the input parties stay registered and confirm their uploads anew.
+pub fn propose(collab_id: i32, program: NewProgram, proposed_by: String, db_url: &str) -> Result<ProgramVersion> {
+    use crate::schema::{collaborations, participations};
+
+    let mut connection = establish_connection(db_url)?;
+    connection.transaction::<_, Error, _>(|conn| {
+        let mut collab = collab_ops::lock(conn, collab_id)?;
+        if !matches!(collab.status, CollaborationStatus::Draft | CollaborationStatus::Open | CollaborationStatus::Collecting | CollaborationStatus::Ready) {
+            return Err(Error::InvalidStatus { collab_id, status: collab.status, action: "propose a new program".to_string() });
+        }
+        if program.sha256 == collab.program_sha256 {
+            return Err(Error::Unprocessable { message: format!("The program is version {} of collaboration {} already", collab.program_version, collab_id) });
+        }
+        store(conn, &program)?;
+        let proposed = add_version(conn, NewProgramVersion {
+            collab_id,
+            version: collab.program_version + 1,
+            program_sha256: program.sha256.clone(),
+            proposed_by: Some(proposed_by),
+        })?;
+        let revoked = invitation_ops::revoke_approvals(conn, collab_id)?;
+
+        // this is the only place a collaboration reopens
+        if matches!(collab.status, CollaborationStatus::Collecting | CollaborationStatus::Ready) {
+            let dropped = diesel::update(participations::table.filter(participations::collaboration_id.eq(collab_id)))
+                .set((participations::secret_ids.eq(None::<Vec<Option<String>>>), participations::uploaded_at.eq(None::<DateTime<Utc>>)))
+                .execute(conn)?;
+            event!(Level::INFO, "Dropped the uploads of {} parties of collaboration {}.", dropped, collab_id);
+            collab_ops::set_status(conn, &mut collab, CollaborationStatus::Open)?;
+        }
+        diesel::update(collaborations::table.find(collab_id))
+            .set((
+                collaborations::program_sha256.eq(&proposed.program_sha256),
+                collaborations::program_version.eq(proposed.version),
+                collaborations::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)?;
+        event!(Level::INFO, "Collaboration {} has program version {}, {} approvals were revoked.", collab_id, proposed.version, revoked);
+        Ok(proposed)
+    })
+}
@@ -7,6 +7,8 @@ use poem_openapi::{ApiResponse, registry::Registry, registry::{MetaResponses, Me
 
 use tracing::{event, Level};
 
+use crate::db::models::CollaborationStatus;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug)]
@@ -23,13 +25,28 @@ pub enum Error {
     #[error("MPC execution failed: {0}")]
     MPCExecutionFailed(String),
 
-    #[error("Forbidden!")]
-    Forbidden,
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Collaboration {collab_id} is {status}, unable to {action}")]
+    InvalidStatus{collab_id: i32, status: CollaborationStatus, action: String},
 
     // -- CS-Client errors
     #[error("Collaboration with id {collab_id} not found")]
     CollaborationNotFound{collab_id: i32},
 
+    #[error("Party with id {party_id} not found")]
+    PartyNotFound{party_id: i32},
+
+    #[error("Invitation with id {invitation_id} not found")]
+    InvitationNotFound{invitation_id: i32},
+
+    #[error("Collaboration {collab_id} has no program version {version}")]
+    ProgramVersionNotFound{collab_id: i32, version: i32},
+
     // -- Externals
     #[error("io error {0}")]
     Io(#[from] std::io::Error), // as example
@@ -82,6 +99,12 @@ impl From<cs_interface::Error> for Error {
             cs_interface::Error::HttpError { code, message } => Self::HttpError{ code, message},
             cs_interface::Error::StdError(e) => Self::StdError(e),
             cs_interface::Error::B64DecodeError(decode_error) => Self::Custom(format!("Decode error: {}", decode_error.to_string())),
+            cs_interface::Error::InvalidNumber(e) => Self::Unprocessable { message: e },
+            cs_interface::Error::InvalidShare(e) => Self::MPCExecutionFailed(e),
+            cs_interface::Error::IntegrityError(e) => Self::MPCExecutionFailed(e),
+            cs_interface::Error::ExecutionFailed(e) => Self::MPCExecutionFailed(e),
+            cs_interface::Error::InvalidSchema(e) => Self::Unprocessable { message: e },
+            cs_interface::Error::InvalidSignature(e) => Self::Custom(e),
         }
     }
 }
@@ -93,9 +116,14 @@ impl ResponseError for Error {
         event!(Level::INFO, "Getting status");
         match self {
             Error::CollaborationNotFound { collab_id: _ } => StatusCode::NOT_FOUND,
+            Error::PartyNotFound { .. } => StatusCode::NOT_FOUND,
+            Error::InvitationNotFound { .. } => StatusCode::NOT_FOUND,
+            Error::ProgramVersionNotFound { .. } => StatusCode::NOT_FOUND,
             Error::Unprocessable{message: _} => StatusCode::UNPROCESSABLE_ENTITY,
             Error::ProcessingNotFinished => StatusCode::from_u16(409).unwrap(),
-            Error::Forbidden => StatusCode::FORBIDDEN,
+            Error::InvalidStatus { .. } => StatusCode::CONFLICT,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
             Error::HttpError { code, message: _ } => StatusCode::from_u16(*code).unwrap(),
             Error::DieselError(err) => {
                 event!(Level::INFO, "Diesel error: {:?}", err);
@@ -149,6 +177,13 @@ impl ApiResponse for Error {
                     headers: vec![],
                     status_range: None
                 },
+                MetaResponse {
+                    description: "Unauthorized",
+                    status: Some(401),
+                    content: vec![],
+                    headers: vec![],
+                    status_range: None
+                },
                 MetaResponse {
                     description: "Forbidden",
                     status: Some(403),
@@ -1,5 +1,7 @@
 pub mod api;
+pub mod auth;
 pub mod db;
 mod schema;
-mod notification_service;
+pub mod notification_service;
+pub mod program_check;
 pub mod error;
\ No newline at end of file
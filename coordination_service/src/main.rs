@@ -1,13 +1,15 @@
 mod api;
+mod auth;
 mod db;
 mod schema;
 mod notification_service;
+mod program_check;
 mod error;
 
 use poem::{listener::TcpListener, Route, Server, EndpointExt, middleware::Cors};
 use poem_openapi::OpenApiService;
 use tracing::{event, Level};
-use std::env;
+use std::{env, sync::Arc};
 use error::Result;
 use dotenv::dotenv;
 
@@ -69,11 +71,17 @@ async fn main() -> Result<()>{
 
     event!(Level::INFO, "Starting coordination service on {}:{}", addr, port);
 
+    let auth = Arc::new(auth::Auth::from_env()?);
+    let program_check = Arc::new(program_check::ProgramCheck::from_env()?);
+
     let api_service =
-        OpenApiService::new((api::collaboration::CollabApi, api::sys_status::SysStatusApi), "Coordination Service", "1.0")
+        OpenApiService::new((api::collaboration::CollabApi::new(auth.clone(), program_check), api::party::PartyApi::new(auth.clone()), api::notification::NotificationApi::new(auth), api::sys_status::SysStatusApi), "Coordination Service", "1.0")
             .description("Coordination Service to coordinate MPC computations")
             .server(oas_server);
 
+    // retry notifications that could not be delivered right away
+    tokio::spawn(notification_service::run_delivery_loop(database_url.clone()));
+
     let ui = api_service.swagger_ui();
     let spec_endpoint = api_service.spec_endpoint_yaml();
     let app = Route::new()
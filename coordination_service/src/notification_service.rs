@@ -1,25 +1,159 @@
+use std::{env, time::Duration};
+
+use chrono::{DateTime, Utc};
 use poem_openapi::types::ToJSON;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use tracing::{event, Level};
 
-use crate::{db::participation_ops::ExecutionResult, error::Result};
+use cs_interface::signature;
+
+use crate::{db::{collab_ops, models::Notification, notification_ops, participation_ops::ExecutionResult}, error::Result};
+
+/// Timeout of a single delivery attempt
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of notifications taken per delivery round
+const BATCH_SIZE: i64 = 20;
+
+/// Reason a delivery attempt failed
+#[derive(Debug)]
+enum DeliveryFailure {
+    /// The party may accept the notification later, e.g. it was not reachable
+    Transient(String),
+    /// The party rejected the notification, further attempts would be rejected as well
+    Permanent(String),
+}
+
+/// Retry behaviour of notification deliveries.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Number of attempts before a notification is dead
+    pub max_attempts: i32,
+    /// Delay after the first failed attempt, doubled for each further one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
 
-pub async fn notify_parties(output_parties: Vec<String>, result: ExecutionResult) -> Result<()> {
+impl RetryPolicy {
+    /// Read `NOTIFICATION_MAX_ATTEMPTS` and `NOTIFICATION_RETRY_SECONDS`, defaults are 8 attempts starting at 5 seconds.
+    pub fn from_env() -> RetryPolicy {
+        let read = |name: &str, default: u64| env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default);
+        RetryPolicy {
+            max_attempts: read("NOTIFICATION_MAX_ATTEMPTS", 8) as i32,
+            base_delay: Duration::from_secs(read("NOTIFICATION_RETRY_SECONDS", 5)),
+            max_delay: Duration::from_secs(3600),
+        }
+    }
+
+    /// Time of the next attempt after `failed` failed attempts, `None` if there are no attempts left.
+    pub fn next_attempt(&self, failed: i32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if failed >= self.max_attempts {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(failed.saturating_sub(1) as u32);
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        Some(now + delay)
+    }
+}
+
+/// Queue the result notice for all output parties and try to deliver it right away.
+///
+/// Failed deliveries stay in the outbox and are retried by `run_delivery_loop`.
+pub async fn notify_parties(collab_id: i32, output_parties: Vec<i32>, result: ExecutionResult, db_url: &str) -> Result<()> {
     event!(Level::INFO, "Notifying output parties: {:?}", output_parties);
-    for party in output_parties {
-        let response = Client::new()
-            .put(format!("{}/notify", party))
-            .body(result.to_json_string())
-            .header("accepts", "application/json")
-            .header("Content-Type","application/json")
-            .send().await?;
-            if response.status().is_success() {
-                event!(Level::INFO,"Output party {} was notified.", party);
-            } else {
-                let body = response.bytes().await?.to_vec();
-                let s = String::from_utf8_lossy(&body);
-                event!(Level::WARN,"Unable to notify output party {}", s);
+    notification_ops::enqueue(collab_id, output_parties, result.to_json_string(), db_url)?;
+    deliver_due(db_url, &RetryPolicy::from_env()).await?;
+    Ok(())
+}
+
+/// Deliver a batch of due notifications, returns the number of successful deliveries.
+pub async fn deliver_due(db_url: &str, policy: &RetryPolicy) -> Result<usize> {
+    let lease_until = Utc::now() + DELIVERY_TIMEOUT * 2;
+    let due = notification_ops::claim_due(BATCH_SIZE, lease_until, db_url)?;
+    let mut delivered = 0;
+    for notification in due {
+        match deliver_to_party(&notification, db_url).await? {
+            Ok(()) => {
+                event!(Level::INFO, "Output party {} was notified.", notification.party_id);
+                notification_ops::mark_delivered(notification.id, db_url)?;
+                delivered += 1;
             }
+            Err(failure) => {
+                let (message, next_attempt) = match failure {
+                    DeliveryFailure::Transient(message) => (message, policy.next_attempt(notification.attempts + 1, Utc::now())),
+                    DeliveryFailure::Permanent(message) => (message, None),
+                };
+                match next_attempt {
+                    Some(at) => event!(Level::WARN, "Unable to notify output party {}, retrying at {}: {}", notification.party_id, at, message),
+                    None => event!(Level::ERROR, "Giving up to notify output party {} after {} attempts: {}", notification.party_id, notification.attempts + 1, message),
+                }
+                notification_ops::mark_failed(notification.id, message, next_attempt, db_url)?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+/// Send the notification to the endpoint the party currently has in the registry, signed with the secret of its registration.
+async fn deliver_to_party(notification: &Notification, db_url: &str) -> Result<std::result::Result<(), DeliveryFailure>> {
+    let address = collab_ops::output_party_address(notification.collab_id, notification.party_id, db_url)?;
+    let (endpoint, secret) = match address {
+        Some((Some(endpoint), secret)) => (endpoint, secret),
+        Some((None, _)) => return Ok(Err(DeliveryFailure::Transient(format!("Output party {} has no client endpoint", notification.party_id)))),
+        None => return Ok(Err(DeliveryFailure::Permanent(format!("Party {} is no output party of collaboration {}", notification.party_id, notification.collab_id)))),
+    };
+    Ok(deliver(notification, &endpoint, &secret).await)
+}
+
+/// Client errors are permanent, except for timeouts and rate limits.
+async fn deliver(notification: &Notification, endpoint: &str, secret: &str) -> std::result::Result<(), DeliveryFailure> {
+    let timestamp = Utc::now().timestamp();
+    let response = Client::new()
+        .put(format!("{}/notify", endpoint))
+        .body(notification.payload.clone())
+        .header("accepts", "application/json")
+        .header("Content-Type", "application/json")
+        .header(signature::TIMESTAMP_HEADER, timestamp)
+        .header(signature::SIGNATURE_HEADER, signature::sign(secret, timestamp, notification.payload.as_bytes()))
+        .timeout(DELIVERY_TIMEOUT)
+        .send()
+        .await
+        .map_err(|err| DeliveryFailure::Transient(err.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = response.bytes().await.map(|b| b.to_vec()).unwrap_or_default();
+    let message = format!("status {}: {}", status.as_u16(), String::from_utf8_lossy(&body));
+    if status.is_client_error() && status != StatusCode::REQUEST_TIMEOUT && status != StatusCode::TOO_MANY_REQUESTS {
+        Err(DeliveryFailure::Permanent(message))
+    } else {
+        Err(DeliveryFailure::Transient(message))
+    }
+}
+
+/// Retry pending notifications until the service stops, `NOTIFICATION_POLL_SECONDS` apart (default 10).
+pub async fn run_delivery_loop(db_url: String) {
+    let interval = env::var("NOTIFICATION_POLL_SECONDS").ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(10);
+    let policy = RetryPolicy::from_env();
+    loop {
+        if let Err(err) = deliver_due(&db_url, &policy).await {
+            event!(Level::ERROR, "Notification delivery failed: {}", err);
+        }
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next_attempt() {
+        let policy = RetryPolicy { max_attempts: 4, base_delay: Duration::from_secs(5), max_delay: Duration::from_secs(12) };
+        let now = Utc::now();
+        assert_eq!(policy.next_attempt(1, now), Some(now + Duration::from_secs(5)));
+        assert_eq!(policy.next_attempt(2, now), Some(now + Duration::from_secs(10)));
+        assert_eq!(policy.next_attempt(3, now), Some(now + Duration::from_secs(12)));
+        assert_eq!(policy.next_attempt(4, now), None);
     }
-    Ok(())
 }
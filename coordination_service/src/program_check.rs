@@ -0,0 +1,263 @@
+//! Checks MPC programs before they are accepted, so a broken program is rejected before parties upload data.
+//!
+//! Programs are checked for their size and encoding, the number of values they read from the clients is compared
+//! with the uploads of the collaboration and they are compiled, by default with a stub that accepts every program.
+use std::{env, fs, process::Stdio, time::Duration};
+
+use cs_interface::CsvSchema;
+use tokio::process::Command;
+use tracing::{event, Level};
+
+use crate::error::{Error, Result};
+
+/// Programs above 1 MiB are rejected unless `PROGRAM_MAX_BYTES` is set
+pub const DEFAULT_MAX_PROGRAM_BYTES: usize = 1 << 20;
+pub const DEFAULT_COMPILE_TIMEOUT_SECS: u64 = 60;
+/// Name of the program in the working directory of the compiler
+const PROGRAM_NAME: &str = "check";
+
+/// Backend compiling programs before they are accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Compiler {
+    /// Accepts every program, for tests and setups without MP-SPDZ
+    Stub,
+    /// Runs `command` followed by the program name, e.g. `python3 /root/MP-SPDZ/compile.py -F 128`. The command
+    /// runs in a new directory holding the program as `Programs/Source/{name}.mpc`.
+    MpSpdz { command: Vec<String>, timeout: Duration },
+}
+
+/// Settings of the program checks, read at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramCheck {
+    pub max_bytes: usize,
+    pub compiler: Compiler,
+}
+
+impl Default for ProgramCheck {
+    fn default() -> Self {
+        ProgramCheck { max_bytes: DEFAULT_MAX_PROGRAM_BYTES, compiler: Compiler::Stub }
+    }
+}
+
+impl ProgramCheck {
+    /// Read `PROGRAM_MAX_BYTES`, `MPSPDZ_COMPILE` and `MPSPDZ_COMPILE_TIMEOUT_SECS`, programs are not compiled
+    /// unless `MPSPDZ_COMPILE` is set.
+    pub fn from_env() -> Result<ProgramCheck> {
+        let number = |name: &str| -> Result<Option<u64>> {
+            match env::var(name) {
+                Ok(value) => value.parse().map(Some).map_err(|_| Error::from(format!("invalid {} '{}'", name, value))),
+                Err(_) => Ok(None),
+            }
+        };
+        let max_bytes = number("PROGRAM_MAX_BYTES")?.map_or(DEFAULT_MAX_PROGRAM_BYTES, |max| max as usize);
+        let command = env::var("MPSPDZ_COMPILE")
+            .map(|command| command.split_whitespace().map(String::from).collect::<Vec<String>>())
+            .unwrap_or_default();
+        let compiler = if command.is_empty() {
+            event!(Level::WARN, "Programs are not compiled before they are accepted, set MPSPDZ_COMPILE to compile them.");
+            Compiler::Stub
+        } else {
+            let timeout = Duration::from_secs(number("MPSPDZ_COMPILE_TIMEOUT_SECS")?.unwrap_or(DEFAULT_COMPILE_TIMEOUT_SECS));
+            Compiler::MpSpdz { command, timeout }
+        };
+        Ok(ProgramCheck { max_bytes, compiler })
+    }
+
+    /// Check the program of a collaboration with `number_of_parties` input parties uploading csv files of `schema`.
+    ///
+    /// Every failed check is `Unprocessable`, returns the program as text.
+    pub async fn check<'a>(&self, program: &'a [u8], number_of_parties: i32, schema: &CsvSchema) -> Result<&'a str> {
+        if program.is_empty() {
+            return Err(Error::Unprocessable { message: "The program is empty".to_string() });
+        }
+        if program.len() > self.max_bytes {
+            return Err(Error::Unprocessable { message: format!("The program has {} bytes, at most {} are allowed", program.len(), self.max_bytes) });
+        }
+        let program = std::str::from_utf8(program)
+            .ok()
+            .filter(|program| !program.contains('\0'))
+            .ok_or_else(|| Error::Unprocessable { message: "The program is not UTF-8 encoded text".to_string() })?;
+        if number_of_parties < 1 {
+            return Err(Error::Unprocessable { message: "A collaboration needs at least one input party".to_string() });
+        }
+        // parties upload any number of whole rows, so the program reads a multiple of the columns
+        let values_per_row = schema.columns.len() as u64;
+        match count_inputs(program) {
+            Some(0) => return Err(Error::Unprocessable { message: "The program reads no inputs from the clients".to_string() }),
+            Some(inputs) if inputs % values_per_row != 0 => {
+                return Err(Error::Unprocessable {
+                    message: format!("The program reads {} inputs, rows of {} columns need a multiple of {}", inputs, values_per_row, values_per_row),
+                });
+            }
+            Some(_) => {}
+            None => event!(Level::INFO, "The number of inputs of the program depends on its execution and is not checked."),
+        }
+        if let Compiler::MpSpdz { command, timeout } = &self.compiler {
+            compile(command, *timeout, program).await?;
+        }
+        Ok(program)
+    }
+}
+
+/// Last lines of the compiler output, for error messages.
+fn tail(log: &str) -> String {
+    let lines = log.trim_end().lines().collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(5)..].join("\n")
+}
+
+async fn compile(command: &[String], timeout: Duration, program: &str) -> Result<()> {
+    let dir = env::temp_dir().join(format!("coordinator-compile-{}", cs_interface::signature::generate_secret()?));
+    fs::create_dir_all(dir.join("Programs/Source"))?;
+    fs::write(dir.join(format!("Programs/Source/{}.mpc", PROGRAM_NAME)), program)?;
+    let output = tokio::time::timeout(
+        timeout,
+        Command::new(&command[0])
+            .args(&command[1..])
+            .arg(PROGRAM_NAME)
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+    if let Err(err) = fs::remove_dir_all(&dir) {
+        event!(Level::WARN, "Unable to remove {}: {}", dir.display(), err);
+    }
+    match output {
+        Err(_) => Err(Error::Unprocessable { message: format!("Compiling the program took longer than {} seconds", timeout.as_secs()) }),
+        Ok(Err(err)) => Err(Error::from(format!("unable to run the compiler {}: {}", command[0], err))),
+        Ok(Ok(output)) if !output.status.success() => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let log = if stderr.trim().is_empty() { String::from_utf8_lossy(&output.stdout) } else { stderr };
+            Err(Error::Unprocessable { message: format!("The program does not compile: {}", tail(&log)) })
+        }
+        Ok(Ok(_)) => Ok(()),
+    }
+}
+
+/// Split the arguments of a call, `args` starts after the opening parenthesis. `None` if the call does not end.
+fn call_arguments(args: &str) -> Option<Vec<&str>> {
+    let mut depth = 0;
+    let mut start = 0;
+    let mut arguments = Vec::new();
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ',' if depth == 0 => {
+                arguments.push(args[start..i].trim());
+                start = i + 1;
+            }
+            ')' | ']' if depth > 0 => depth -= 1,
+            ')' => {
+                arguments.push(args[start..i].trim());
+                return Some(arguments.into_iter().filter(|a| !a.is_empty()).collect());
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The argument at `position` or given as `name=`.
+fn argument<'a>(arguments: &[&'a str], position: usize, name: &str) -> Option<&'a str> {
+    let keyword = arguments.iter().find_map(|a| a.split_once('=').filter(|(key, _)| key.trim() == name).map(|(_, value)| value.trim()));
+    keyword.or_else(|| arguments.iter().filter(|a| !a.contains('=')).nth(position).copied())
+}
+
+/// Number of values the program reads with `sint.receive_from_client(n, socket)` and
+/// `sint.read_from_socket(socket, n)`, `None` if it depends on the execution, e.g. because a read is part of a
+/// loop or the number is not a literal.
+pub fn count_inputs(program: &str) -> Option<u64> {
+    // (method, position of the number of values, keyword of it, default)
+    const READS: [(&str, usize, &str, Option<u64>); 2] = [
+        (".receive_from_client(", 0, "n", None),
+        (".read_from_socket(", 1, "n", Some(1)),
+    ];
+    let mut inputs = 0;
+    for line in program.lines() {
+        let code = line.split('#').next().unwrap_or_default();
+        for (method, position, keyword, default) in READS {
+            for (start, _) in code.match_indices(method) {
+                // reads in blocks may run several times or not at all
+                if code.starts_with(char::is_whitespace) {
+                    return None;
+                }
+                let arguments = call_arguments(&code[start + method.len()..])?;
+                let count = match argument(&arguments, position, keyword) {
+                    Some(count) => count.parse::<u64>().ok()?,
+                    None => default?,
+                };
+                inputs += count;
+            }
+        }
+    }
+    Some(inputs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn schema(columns: usize) -> CsvSchema {
+        let columns = (0..columns).map(|i| format!(r#"{{"name":"c{}","type":"int"}}"#, i)).collect::<Vec<String>>();
+        CsvSchema::from_json(&format!(r#"{{"columns":[{}]}}"#, columns.join(","))).unwrap()
+    }
+
+    fn unprocessable(result: Result<&str>, expected: &str) {
+        assert!(matches!(&result, Err(Error::Unprocessable { message }) if message.contains(expected)), "{:?}", result);
+    }
+
+    #[test]
+    fn test_count_inputs() {
+        assert_eq!(count_inputs(include_str!("../../smoketesting/mpc_program.mpc")), Some(1));
+        assert_eq!(count_inputs(include_str!("../../cs_mock/mpspdz/source/secure-comparison.mpc")), Some(2));
+        assert_eq!(count_inputs("a = sint.read_from_socket(s)\nb = sint.receive_from_client(3, s) # read_from_socket(s, 9)"), Some(4));
+        assert_eq!(count_inputs("v = sint.read_from_socket(s, n=4)\nw = sint.receive_from_client(sum([1, 2]), s)"), None);
+        assert_eq!(count_inputs("for i in range(3):\n    v = sint.read_from_socket(s, 1)"), None);
+        assert_eq!(count_inputs("v = sint.read_from_socket(s, N)"), None);
+        assert_eq!(count_inputs("v = sint.read_from_socket(s, n=4)"), Some(4));
+        assert_eq!(count_inputs("print_ln('no inputs')"), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let check = ProgramCheck { max_bytes: 100, ..ProgramCheck::default() };
+        let program = "v = sint.read_from_socket(s, 6)";
+        assert_eq!(check.check(program.as_bytes(), 3, &schema(2)).await.unwrap(), program);
+        assert!(check.check(program.as_bytes(), 1, &schema(3)).await.is_ok());
+
+        unprocessable(check.check(b"", 1, &schema(1)).await, "empty");
+        unprocessable(check.check(&[b'#'; 101], 1, &schema(1)).await, "101 bytes");
+        unprocessable(check.check(&[0xff, 0xfe], 1, &schema(1)).await, "UTF-8");
+        unprocessable(check.check(b"\0", 1, &schema(1)).await, "UTF-8");
+        unprocessable(check.check(program.as_bytes(), 0, &schema(1)).await, "at least one");
+        unprocessable(check.check(b"print_ln('x')", 1, &schema(1)).await, "no inputs");
+        // the parties may upload different numbers of rows
+        assert!(check.check(program.as_bytes(), 4, &schema(1)).await.is_ok());
+        unprocessable(check.check(program.as_bytes(), 1, &schema(4)).await, "reads 6 inputs, rows of 4 columns need a multiple of 4");
+        // unknown numbers of inputs are left to the compiler and the execution
+        assert!(check.check(b"v = sint.read_from_socket(s, N)", 4, &schema(1)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_compile() {
+        let compiler = |script: &str, timeout: Duration| ProgramCheck {
+            max_bytes: DEFAULT_MAX_PROGRAM_BYTES,
+            compiler: Compiler::MpSpdz { command: vec!["sh".to_string(), "-c".to_string(), script.to_string(), "compile".to_string()], timeout },
+        };
+        let program = b"v = sint.read_from_socket(s, 1)";
+        let secs = Duration::from_secs(5);
+        let check = compiler(r#"grep -q read_from_socket "Programs/Source/$1.mpc""#, secs);
+        assert!(check.check(program, 1, &schema(1)).await.is_ok());
+
+        let check = compiler("echo 'Compiling check'; echo 'SyntaxError: invalid syntax' >&2; exit 1", secs);
+        unprocessable(check.check(program, 1, &schema(1)).await, "does not compile: SyntaxError: invalid syntax");
+        unprocessable(compiler("sleep 10", Duration::from_millis(200)).check(program, 1, &schema(1)).await, "longer than");
+
+        let missing = ProgramCheck {
+            compiler: Compiler::MpSpdz { command: vec!["/nonexistent/compile.py".to_string()], timeout: secs },
+            ..ProgramCheck::default()
+        };
+        assert!(matches!(missing.check(program, 1, &schema(1)).await, Err(Error::Custom(_))));
+    }
+}
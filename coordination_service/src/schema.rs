@@ -5,11 +5,19 @@ diesel::table! {
         id -> Int4,
         #[max_length = 255]
         name -> Varchar,
-        mpc_program -> Text,
         csv_specification -> Text,
         participation_number -> Int4,
         config_id -> Int4,
-        output_parties -> Nullable<Array<Nullable<Text>>>,
+        fixed_point_precision -> Int4,
+        csv_schema -> Jsonb,
+        #[max_length = 32]
+        status -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        created_by -> Nullable<Text>,
+        #[max_length = 64]
+        program_sha256 -> Varchar,
+        program_version -> Int4,
     }
 }
 
@@ -18,8 +26,11 @@ diesel::table! {
         collab_id -> Int4,
         result_ids -> Nullable<Array<Nullable<Text>>>,
         finished -> Bool,
-        #[max_length = 255]
-        error -> Nullable<Varchar>,
+        error -> Nullable<Text>,
+        started_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+        #[max_length = 64]
+        program_sha256 -> Varchar,
     }
 }
 
@@ -51,23 +62,122 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    invitations (id) {
+        id -> Int4,
+        collab_id -> Int4,
+        party_id -> Int4,
+        role -> Text,
+        #[max_length = 32]
+        status -> Varchar,
+        expires_at -> Timestamptz,
+        invited_by -> Text,
+        created_at -> Timestamptz,
+        responded_at -> Nullable<Timestamptz>,
+        #[max_length = 64]
+        approved_program_sha256 -> Nullable<Varchar>,
+        approved_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Int4,
+        collab_id -> Int4,
+        payload -> Text,
+        #[max_length = 32]
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        delivered_at -> Nullable<Timestamptz>,
+        party_id -> Int4,
+    }
+}
+
+diesel::table! {
+    output_registrations (collab_id, party_id) {
+        collab_id -> Int4,
+        party_id -> Int4,
+        secret -> Text,
+        registered_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    parties (id) {
+        id -> Int4,
+        #[max_length = 255]
+        display_name -> Varchar,
+        #[max_length = 255]
+        organisation -> Nullable<Varchar>,
+        roles -> Array<Text>,
+        client_endpoint -> Nullable<Text>,
+        #[max_length = 64]
+        api_key_sha256 -> Nullable<Varchar>,
+        active -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     participations (collaboration_id, party_id) {
         collaboration_id -> Int4,
         party_id -> Int4,
         secret_ids -> Nullable<Array<Nullable<Bpchar>>>,
+        registered_at -> Timestamptz,
+        uploaded_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    program_versions (collab_id, version) {
+        collab_id -> Int4,
+        version -> Int4,
+        #[max_length = 64]
+        program_sha256 -> Varchar,
+        proposed_by -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    programs (sha256) {
+        #[max_length = 64]
+        sha256 -> Varchar,
+        source -> Text,
+        created_at -> Timestamptz,
     }
 }
 
 diesel::joinable!(collaborations -> csconfig (config_id));
+diesel::joinable!(collaborations -> programs (program_sha256));
 diesel::joinable!(computation_results -> collaborations (collab_id));
+diesel::joinable!(computation_results -> programs (program_sha256));
 diesel::joinable!(csprovider -> csconfig (config_id));
+diesel::joinable!(invitations -> collaborations (collab_id));
+diesel::joinable!(invitations -> parties (party_id));
+diesel::joinable!(notifications -> collaborations (collab_id));
+diesel::joinable!(notifications -> parties (party_id));
+diesel::joinable!(output_registrations -> collaborations (collab_id));
+diesel::joinable!(output_registrations -> parties (party_id));
 diesel::joinable!(participations -> collaborations (collaboration_id));
+diesel::joinable!(participations -> parties (party_id));
+diesel::joinable!(program_versions -> collaborations (collab_id));
+diesel::joinable!(program_versions -> programs (program_sha256));
 
 diesel::allow_tables_to_appear_in_same_query!(
     collaborations,
     computation_results,
     csconfig,
     csprovider,
+    invitations,
+    notifications,
+    output_registrations,
+    parties,
     participations,
+    program_versions,
+    programs,
 );
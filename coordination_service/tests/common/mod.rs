@@ -1,3 +1,6 @@
+// Not every test binary uses every helper
+#![allow(dead_code)]
+
 
 #[cfg(test)]
 use std::env;
@@ -5,17 +8,23 @@ use std::env;
 use diesel::{Connection, PgConnection, RunQueryDsl};
 use poem::{middleware::AddDataEndpoint, test::{TestClient, TestForm, TestFormField, TestResponse}, EndpointExt, Route};
 use poem_openapi::OpenApiService;
-use coordination_service::db::{self, establish_connection};
+use coordination_service::{auth::Auth, db::{self, establish_connection}, program_check::ProgramCheck};
 use rand::Rng;
 use tempfile::NamedTempFile;
 
-use std::{io::Write, iter};
+use std::{io::Write, iter, sync::Arc};
 
 pub fn test_client(db_url: &str) -> TestClient<AddDataEndpoint<Route, std::string::String>> {
+    test_client_with_auth(db_url, Auth::Disabled)
+}
 
+pub fn test_client_with_auth(db_url: &str, auth: Auth) -> TestClient<AddDataEndpoint<Route, std::string::String>> {
+    let auth = Arc::new(auth);
     let api_service = OpenApiService::new(
         (
-            coordination_service::api::collaboration::CollabApi,
+            coordination_service::api::collaboration::CollabApi::new(auth.clone(), Arc::new(ProgramCheck::default())),
+            coordination_service::api::party::PartyApi::new(auth.clone()),
+            coordination_service::api::notification::NotificationApi::new(auth),
             coordination_service::api::sys_status::SysStatusApi,
         ),
         "",
@@ -90,10 +99,58 @@ WHERE datname = '{}';",
 
 
 pub async fn create_correct_collaboration(client: &TestClient<AddDataEndpoint<Route, std::string::String>>) -> TestResponse {
+    create_collaboration_with_precision(client, None).await
+}
+
+pub async fn create_collaboration_with_precision(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, precision: Option<&str>) -> TestResponse {
+    create_collaboration_with(client, precision, r#"{"columns":[{"name":"data","type":"int"}]}"#).await
+}
+
+pub async fn create_collaboration_with(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, precision: Option<&str>, csv_schema: &str) -> TestResponse {
+    let mut fields = vec![("csv_schema", csv_schema)];
+    if let Some(precision) = precision {
+        fields.push(("fixed_point_precision", precision));
+    }
+    create_collaboration_with_fields(client, &fields).await
+}
+
+/// Create a collaboration with the given text fields, the name, schema and number of parties default to a single int column for one party.
+pub async fn create_collaboration_with_fields(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, fields: &[(&str, &str)]) -> TestResponse {
+    client.post("/collaboration")
+        .multipart(collaboration_form(fields).await).send().await
+}
+
+/// Program reading `inputs` values from the clients and returning them.
+pub fn test_program(inputs: usize) -> String {
+    format!("socket_id = regint()\nv = sint.read_from_socket(socket_id, {})\nsint.write_to_socket(socket_id, v)\n", inputs)
+}
+
+/// Form to create a collaboration with, see `create_collaboration_with_fields`.
+///
+/// The program reads one row of every party unless `fields` sets the `mpc_program`.
+pub async fn collaboration_form(fields: &[(&str, &str)]) -> TestForm {
+    let field = |name: &str| fields.iter().find(|(field, _)| *field == name).map(|(_, value)| *value);
+    let program = match field("mpc_program") {
+        Some(program) => program.to_string(),
+        None => {
+            let parties = field("number_of_parties").and_then(|parties| parties.parse().ok()).unwrap_or(1);
+            let columns = field("csv_schema")
+                .and_then(|schema| serde_json::from_str::<serde_json::Value>(schema).ok())
+                .and_then(|schema| schema["columns"].as_array().map(Vec::len))
+                .unwrap_or(1);
+            test_program(parties * columns)
+        }
+    };
+    let fields = fields.iter().filter(|(name, _)| *name != "mpc_program").copied().collect::<Vec<_>>();
+    collaboration_form_with_program(&fields, program.as_bytes()).await
+}
+
+/// Form to create a collaboration with the given program, which need not be text.
+pub async fn collaboration_form_with_program(fields: &[(&str, &str)], program: &[u8]) -> TestForm {
     let mut tmp_program = NamedTempFile::new().unwrap();
     let mut tmp_config = NamedTempFile::new().unwrap();
 
-    tmp_program.write_all(b"this is just some data").unwrap();
+    tmp_program.write_all(program).unwrap();
 
     // prime is missing in config
     tmp_config.write_all(
@@ -124,12 +181,79 @@ pub async fn create_correct_collaboration(client: &TestClient<AddDataEndpoint<Ro
     let config_field = TestFormField::async_reader(tokio::io::BufReader::new(config_file))
         .filename("cs_config")
         .name("cs_config");
-    client.post("/collaboration")
-        .multipart(TestForm::new()
-            .field(TestFormField::text("demo").name("name"))
-            .field(TestFormField::text("data").name("csv_header_line"))
-            .field(TestFormField::text("1").name("number_of_parties"))
-            .field(program_field)
-            .field(config_field)
-        ).send().await
-}
\ No newline at end of file
+    let mut form = TestForm::new()
+        .field(program_field)
+        .field(config_field);
+    let defaults = [("name", "demo"), ("csv_schema", r#"{"columns":[{"name":"data","type":"int"}]}"#), ("number_of_parties", "1")];
+    for (name, value) in defaults {
+        if !fields.iter().any(|(field, _)| *field == name) {
+            form = form.field(TestFormField::text(value).name(name));
+        }
+    }
+    for (name, value) in fields {
+        form = form.field(TestFormField::text(*value).name(*name));
+    }
+    form
+}
+/// Add a party with the given roles to the registry, returns its id and API key.
+pub async fn create_party(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, roles: &[&str]) -> (i32, String) {
+    let resp = client.post("/party")
+        .body_json(&serde_json::json!({"display_name": "test party", "roles": roles, "client_endpoint": "http://localhost:1"}))
+        .send().await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let body = json.value().object();
+    (body.get("party").object().get("id").i64() as i32, body.get("api_key").string().to_string())
+}
+
+/// Add `count` parties with all roles, in a fresh database their ids are 1 to `count`.
+pub async fn create_parties(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, count: usize) {
+    for _ in 0..count {
+        create_party(client, &["input", "output", "creator"]).await;
+    }
+}
+
+/// Invite the party as `role`, accept the invitation and approve the program, so the party may register.
+pub async fn join_collaboration(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, collab_id: i64, party_id: i32, role: &str) {
+    let resp = client.post(format!("/collaboration/{}/invitations", collab_id))
+        .body_json(&serde_json::json!({"party_id": party_id, "role": role}))
+        .send().await;
+    resp.assert_status_is_ok();
+    let invitation_id = resp.json().await.value().object().get("id").i64();
+    client.post(format!("/collaboration/{}/invitations/{}/accept", collab_id, invitation_id))
+        .send().await
+        .assert_status_is_ok();
+    approve_current_program(client, collab_id, invitation_id).await;
+}
+
+/// Approve the current program with the accepted invitation.
+pub async fn approve_current_program(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, collab_id: i64, invitation_id: i64) {
+    let resp = client.get(format!("/collaboration/{}/program", collab_id)).send().await;
+    resp.assert_status_is_ok();
+    let program_sha256 = resp.json().await.value().object().get("program_sha256").string().to_string();
+    client.post(format!("/collaboration/{}/invitations/{}/approve-program", collab_id, invitation_id))
+        .body_json(&serde_json::json!({"program_sha256": program_sha256}))
+        .send().await
+        .assert_status_is_ok();
+}
+
+/// Id of the invitation of the party as `role`.
+pub async fn invitation_id(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, collab_id: i64, party_id: i32, role: &str) -> i64 {
+    let resp = client.get(format!("/collaboration/{}/invitations", collab_id)).send().await;
+    resp.assert_status_is_ok();
+    let body = resp.0.into_body().into_json::<serde_json::Value>().await.unwrap();
+    let invitation = body.as_array().unwrap().iter()
+        .find(|invitation| invitation["party_id"] == party_id && invitation["role"] == role)
+        .expect("The party was not invited.");
+    invitation["id"].as_i64().unwrap()
+}
+
+/// Propose `program` as new version of the program of the collaboration.
+pub async fn propose_program(client: &TestClient<AddDataEndpoint<Route, std::string::String>>, collab_id: i64, program: &str) -> TestResponse {
+    let program_field = TestFormField::bytes(program.as_bytes().to_vec())
+        .filename("mpc_program")
+        .name("mpc_program");
+    client.post(format!("/collaboration/{}/program", collab_id))
+        .multipart(TestForm::new().field(program_field))
+        .send().await
+}
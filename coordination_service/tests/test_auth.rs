@@ -0,0 +1,218 @@
+mod common;
+
+#[cfg(test)]
+mod test {
+    use coordination_service::auth::Auth;
+    use poem::{middleware::AddDataEndpoint, test::TestClient, Route};
+    use reqwest::StatusCode;
+
+    use crate::common::{self, collaboration_form, DBTestContext};
+
+    fn sha256_hex(value: &str) -> String {
+        ring::digest::digest(&ring::digest::SHA256, value.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// API keys "key-creator" for party 1, "key-party" for party 2 and the admin key "key-admin"
+    fn auth() -> Auth {
+        let json = format!(
+            r#"[{{"name": "creator", "api_key_sha256": "{}", "party_ids": [1]}},
+                {{"name": "party", "api_key_sha256": "{}", "party_ids": [2]}},
+                {{"name": "admin", "api_key_sha256": "{}", "admin": true}}]"#,
+            sha256_hex("key-creator"), sha256_hex("key-party"), sha256_hex("key-admin"));
+        Auth::new(Some(&json), None).unwrap()
+    }
+
+    fn bearer(key: &str) -> String {
+        format!("Bearer {}", key)
+    }
+
+    /// Add a party as admin, returns its id and API key
+    async fn create_party(client: &TestClient<AddDataEndpoint<Route, String>>, roles: &[&str]) -> (i64, String) {
+        let resp = client.post("/party")
+            .header("Authorization", bearer("key-admin"))
+            .body_json(&serde_json::json!({"display_name": "party", "roles": roles}))
+            .send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        (json.value().object().get("party").object().get("id").i64(), json.value().object().get("api_key").string().to_string())
+    }
+
+    /// Invite the party as input party with the key of the owner, then accept and approve the program with `party_key`
+    async fn join(client: &TestClient<AddDataEndpoint<Route, String>>, id: i64, party: i64, owner_key: &str, party_key: &str) {
+        let resp = client.post(format!("/collaboration/{}/invitations", id))
+            .header("Authorization", bearer(owner_key))
+            .body_json(&serde_json::json!({"party_id": party, "role": "input"}))
+            .send().await;
+        resp.assert_status_is_ok();
+        let invitation = resp.json().await.value().object().get("id").i64();
+        client.post(format!("/collaboration/{}/invitations/{}/accept", id, invitation))
+            .header("Authorization", bearer(party_key))
+            .send().await
+            .assert_status_is_ok();
+        let resp = client.get(format!("/collaboration/{}/program", id)).header("Authorization", bearer(party_key)).send().await;
+        resp.assert_status_is_ok();
+        let program_sha256 = resp.json().await.value().object().get("program_sha256").string().to_string();
+        client.post(format!("/collaboration/{}/invitations/{}/approve-program", id, invitation))
+            .header("Authorization", bearer(party_key))
+            .body_json(&serde_json::json!({"program_sha256": program_sha256}))
+            .send().await
+            .assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn requests_need_credentials() {
+        let db = DBTestContext::new();
+        let client = common::test_client_with_auth(&db.db_url, auth());
+
+        client.get("/collaboration").send().await.assert_status(StatusCode::UNAUTHORIZED);
+        client.get("/collaboration").header("Authorization", bearer("key-unknown")).send().await.assert_status(StatusCode::UNAUTHORIZED);
+        client.get("/collaboration").header("Authorization", bearer("key-party")).send().await.assert_status_is_ok();
+        let resp = client.post("/collaboration").multipart(collaboration_form(&[]).await).send().await;
+        resp.assert_status(StatusCode::UNAUTHORIZED);
+        client.get("/notifications").header("Authorization", bearer("key-party")).send().await.assert_status(StatusCode::FORBIDDEN);
+        client.get("/notifications").header("Authorization", bearer("key-admin")).send().await.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn parties_act_only_for_themselves() {
+        let db = DBTestContext::new();
+        let client = common::test_client_with_auth(&db.db_url, auth());
+        for _ in 0..3 {
+            create_party(&client, &["input", "output"]).await;
+        }
+        let resp = client.post("/collaboration")
+            .header("Authorization", bearer("key-creator"))
+            .multipart(collaboration_form(&[]).await)
+            .send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().object().get("created_by").assert_string("creator");
+        let id = json.value().object().get("id").i64();
+
+        // only the owner invites and only the invited party answers
+        let resp = client.post(format!("/collaboration/{}/invitations", id))
+            .header("Authorization", bearer("key-party"))
+            .body_json(&serde_json::json!({"party_id": 2, "role": "input"}))
+            .send().await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+        let resp = client.post(format!("/collaboration/{}/invitations", id))
+            .header("Authorization", bearer("key-creator"))
+            .body_json(&serde_json::json!({"party_id": 2, "role": "input"}))
+            .send().await;
+        resp.assert_status_is_ok();
+        let invitation = resp.json().await.value().object().get("id").i64();
+        client.post(format!("/collaboration/{}/invitations/{}/accept", id, invitation))
+            .header("Authorization", bearer("key-creator"))
+            .send().await
+            .assert_status(StatusCode::FORBIDDEN);
+        join(&client, id, 2, "key-creator", "key-party").await;
+        join(&client, id, 3, "key-creator", "key-admin").await;
+
+        let register = |party: i32, key: &'static str| client
+            .post(format!("/collaboration/{}/register-input-party/{}", id, party))
+            .header("Authorization", bearer(key))
+            .send();
+        register(2, "key-creator").await.assert_status(StatusCode::FORBIDDEN);
+        register(2, "key-party").await.assert_status_is_ok();
+        register(3, "key-admin").await.assert_status_is_ok();
+
+        let confirm = |party: i32, key: &'static str| client
+            .post(format!("/collaboration/{}/confirm-upload/{}", id, party))
+            .header("Authorization", bearer(key))
+            .body_json(&vec!["secret"])
+            .send();
+        confirm(2, "key-creator").await.assert_status(StatusCode::FORBIDDEN);
+        confirm(2, "key-party").await.assert_status_is_ok();
+
+        let resp = client.post(format!("/collaboration/{}/register-output-party/2?party_client_endpoint=abc", id))
+            .header("Authorization", bearer("key-creator"))
+            .send().await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn only_creator_or_admin_deletes() {
+        let db = DBTestContext::new();
+        let client = common::test_client_with_auth(&db.db_url, auth());
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let resp = client.post("/collaboration")
+                .header("Authorization", bearer("key-creator"))
+                .multipart(collaboration_form(&[]).await)
+                .send().await;
+            resp.assert_status_is_ok();
+            ids.push(resp.json().await.value().object().get("id").i64());
+        }
+        let delete = |id: i64, key: &'static str| client
+            .delete(format!("/collaboration/{}", id))
+            .header("Authorization", bearer(key))
+            .send();
+        delete(ids[0], "key-party").await.assert_status(StatusCode::FORBIDDEN);
+        let resp = client.put(format!("/collaboration/{}/status", ids[0]))
+            .header("Authorization", bearer("key-party"))
+            .body_json(&serde_json::json!({"status": "cancelled"}))
+            .send().await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+        delete(ids[0], "key-creator").await.assert_status_is_ok();
+        delete(ids[1], "key-admin").await.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn registry_parties_use_their_api_key() {
+        let db = DBTestContext::new();
+        let client = common::test_client_with_auth(&db.db_url, auth());
+        client.post("/party")
+            .header("Authorization", bearer("key-party"))
+            .body_json(&serde_json::json!({"display_name": "party", "roles": ["input"]}))
+            .send().await
+            .assert_status(StatusCode::FORBIDDEN);
+        let (creator, creator_key) = create_party(&client, &["creator"]).await;
+        let (input, input_key) = create_party(&client, &["input"]).await;
+
+        let resp = client.post("/collaboration")
+            .header("Authorization", bearer(&input_key))
+            .multipart(collaboration_form(&[]).await)
+            .send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = client.post("/collaboration")
+            .header("Authorization", bearer(&creator_key))
+            .multipart(collaboration_form(&[]).await)
+            .send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().object().get("created_by").assert_string(&format!("party-{}", creator));
+        let id = json.value().object().get("id").i64();
+
+        let register = |party: i64, key: String| client
+            .post(format!("/collaboration/{}/register-input-party/{}", id, party))
+            .header("Authorization", bearer(&key))
+            .send();
+        join(&client, id, input, &creator_key, &input_key).await;
+        register(input, creator_key.clone()).await.assert_status(StatusCode::FORBIDDEN);
+        register(input, input_key.clone()).await.assert_status_is_ok();
+
+        // a party may change its endpoint but not its roles
+        let update = |body: serde_json::Value| client
+            .put(format!("/party/{}", input))
+            .header("Authorization", bearer(&input_key))
+            .body_json(&body)
+            .send();
+        update(serde_json::json!({"client_endpoint": "http://party:8080"})).await.assert_status_is_ok();
+        update(serde_json::json!({"roles": ["input", "creator"]})).await.assert_status(StatusCode::FORBIDDEN);
+
+        // a new key replaces the former one
+        let resp = client.post(format!("/party/{}/api-key", input)).header("Authorization", bearer(&input_key)).send().await;
+        resp.assert_status_is_ok();
+        let new_key = resp.json().await.value().object().get("api_key").string().to_string();
+        client.get("/party").header("Authorization", bearer(&input_key)).send().await.assert_status(StatusCode::UNAUTHORIZED);
+        client.get("/party").header("Authorization", bearer(&new_key)).send().await.assert_status_is_ok();
+
+        // deactivated parties can not authenticate anymore
+        client.delete(format!("/party/{}", input)).header("Authorization", bearer("key-admin")).send().await.assert_status_is_ok();
+        client.get("/party").header("Authorization", bearer(&new_key)).send().await.assert_status(StatusCode::UNAUTHORIZED);
+    }
+}
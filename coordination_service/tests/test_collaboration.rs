@@ -3,9 +3,10 @@ mod common;
 #[cfg(test)]
 mod test {
     use std::{io::Write, str::FromStr};
-    use crate::common::{self, DBTestContext, create_correct_collaboration};
+    use crate::common::{self, DBTestContext, create_collaboration_with, create_collaboration_with_fields, create_collaboration_with_precision, create_correct_collaboration};
     use claim::assert_some;
-    use coordination_service::db::models::Participation;
+    use coordination_service::db::{collab_ops, establish_connection, participation_ops, models::{CollaborationStatus, Participation}};
+    use diesel::RunQueryDsl;
     use poem::test::{TestForm, TestFormField};
     use reqwest::StatusCode;
     use tempfile::NamedTempFile;
@@ -22,7 +23,7 @@ mod test {
         let mut tmp_program = NamedTempFile::new().unwrap();
         let mut tmp_config = NamedTempFile::new().unwrap();
 
-        tmp_program.write_all(b"this is just some data").unwrap();
+        tmp_program.write_all(common::test_program(1).as_bytes()).unwrap();
         tmp_config.write_all(
         br#"{
         "noSslValidation":true,
@@ -53,7 +54,7 @@ mod test {
         let resp = client.post("/collaboration")
             .multipart(TestForm::new()
                 .field(TestFormField::text("demo").name("name"))
-                .field(TestFormField::text("data").name("csv_header_line"))
+                .field(TestFormField::text(r#"{"columns":[{"name":"data","type":"int"}]}"#).name("csv_schema"))
                 .field(TestFormField::text("1").name("number_of_parties"))
                 .field(program_field)
                 .field(config_field)
@@ -61,6 +62,57 @@ mod test {
         resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    #[tokio::test]
+    async fn create_collaboration_fixed_point_precision() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+
+        let resp = create_collaboration_with_precision(&client, Some("16")).await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let id = json.value().object().get("id").i64();
+
+        let resp = client.get(format!("/collaboration/{}", id)).send().await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().object().get("fixed_point_precision").assert_i64(16);
+
+        let resp = create_collaboration_with_precision(&client, Some("-1")).await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = create_collaboration_with_precision(&client, Some("33")).await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn create_collaboration_csv_schema() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+
+        let schema = r#"{"columns":[
+            {"name":"age","type":"int","min":0,"max":120},
+            {"name":"smoker","type":"boolean","nullable":true},
+            {"name":"region","type":"categorical","values":["north","south"]}]}"#;
+        let resp = create_collaboration_with(&client, None, schema).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+
+        let resp = client.get(format!("/collaboration/{}", id)).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let collab = json.value().object();
+        collab.get("csv_specification").assert_string("age,smoker,region");
+        let columns = collab.get("csv_schema").object().get("columns").array();
+        columns.assert_len(3);
+        columns.get(0).object().get("type").assert_string("int");
+        columns.get(0).object().get("max").assert_f64(120.0);
+        columns.get(1).object().get("nullable").assert_bool(true);
+        columns.get(2).object().get("values").assert_string_array(&["north", "south"]);
+
+        let resp = create_collaboration_with(&client, None, r#"{"columns":[{"name":"a","type":"categorical"}]}"#).await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = create_collaboration_with(&client, None, "data").await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
     #[tokio::test]
     async fn create_collaboration() {
         let db = DBTestContext::new();
@@ -74,10 +126,15 @@ mod test {
         let resp_object = json.value().object();
 
         // test some parameters
-        resp_object.assert_len(7);
+        resp_object.assert_len(13);
         resp_object.get_opt("id").expect("id not found");
         resp_object.get_opt("name").expect("name not found").assert_string("demo");
         resp_object.get_opt("participation_number").expect("name not found").assert_i64(1);
+        resp_object.get_opt("fixed_point_precision").expect("fixed_point_precision not found").assert_i64(0);
+        resp_object.get_opt("status").expect("status not found").assert_string("open");
+        resp_object.get_opt("created_by").expect("created_by not found").assert_string("anonymous");
+        resp_object.get_opt("program_version").expect("program_version not found").assert_i64(1);
+        resp_object.get_opt("program_sha256").expect("program_sha256 not found").string();
     }
 
     #[tokio::test]
@@ -107,11 +164,18 @@ mod test {
     async fn register_input_party() {
         let db = DBTestContext::new();
         let client = common::test_client(&db.db_url);
+        common::create_parties(&client, 1).await;
         let collab_resp = create_correct_collaboration(&client).await;
         collab_resp.assert_status_is_ok();
         let resp = collab_resp.json().await;
         let id = resp.value().object().get("id").i64();
 
+        // the party needs an accepted invitation and has to approve the program
+        let resp = client.post(format!("/collaboration/{}/register-input-party/1", id))
+            .send().await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+        common::join_collaboration(&client, id, 1, "input").await;
+
         let resp = client.post(format!("/collaboration/{}/register-input-party/1", id))
             .send().await;
         resp.assert_status_is_ok();
@@ -130,11 +194,13 @@ mod test {
     async fn register_output_party() {
         let db = DBTestContext::new();
         let client = common::test_client(&db.db_url);
+        common::create_parties(&client, 1).await;
 
         let collab_resp = create_correct_collaboration(&client).await;
         collab_resp.assert_status_is_ok();
         let resp = collab_resp.json().await;
         let id = resp.value().object().get("id").i64();
+        common::join_collaboration(&client, id, 1, "output").await;
 
         let resp = client.post(format!("/collaboration/{}/register-output-party/1?party_client_endpoint=abc123", id))
             .send().await;
@@ -155,12 +221,14 @@ mod test {
         // Setup
         let db = DBTestContext::new();
         let client = common::test_client(&db.db_url);
+        common::create_parties(&client, 1).await;
         let collab_resp = create_correct_collaboration(&client).await;
         collab_resp.assert_status_is_ok();
         let resp = collab_resp.json().await;
         let id = resp.value().object().get("id").i64();
 
         // Register input party
+        common::join_collaboration(&client, id, 1, "input").await;
         let resp = client.post(format!("/collaboration/{}/register-input-party/1", id))
             .send().await;
         resp.assert_status_is_ok();
@@ -238,4 +306,157 @@ mod test {
         let res_resp = client.get(format!("/collaboration/{}/compute_config", 1)).send().await;
         res_resp.assert_status(StatusCode::NOT_FOUND);
     }
+
+    #[tokio::test]
+    async fn collaboration_status() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        common::create_parties(&client, 2).await;
+        let resp = create_collaboration_with_fields(&client, &[("number_of_parties", "2"), ("draft", "true")]).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        let status = |status: &str| serde_json::json!({ "status": status });
+
+        // drafts are not open for registration
+        let resp = client.post(format!("/collaboration/{}/register-input-party/1", id)).send().await;
+        resp.assert_status(StatusCode::CONFLICT);
+        let resp = client.put(format!("/collaboration/{}/status", id)).body_json(&status("open")).send().await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().object().get("status").assert_string("open");
+
+        for party in [1, 2] {
+            common::join_collaboration(&client, id, party, "input").await;
+            client.post(format!("/collaboration/{}/register-input-party/{}", id, party)).send().await.assert_status_is_ok();
+        }
+        client.post(format!("/collaboration/{}/confirm-upload/1", id))
+            .body_json(&vec![Uuid::new_v4().to_string()])
+            .send().await
+            .assert_status_is_ok();
+
+        let resp = client.get(format!("/collaboration/{}/status", id)).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let body = json.value().object();
+        body.get("status").assert_string("collecting");
+        body.get("participation_number").assert_i64(2);
+        body.get("uploads_confirmed").assert_i64(1);
+        body.get("execution_started_at").assert_null();
+        let parties = body.get("parties").array();
+        parties.assert_len(2);
+        parties.get(0).object().get("uploaded").assert_bool(true);
+        parties.get(0).object().get("uploaded_at").string();
+        parties.get(1).object().get("uploaded").assert_bool(false);
+
+        // states of the execution are only set by the coordinator
+        let resp = client.put(format!("/collaboration/{}/status", id)).body_json(&status("running")).send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = client.put(format!("/collaboration/{}/status", id)).body_json(&status("archived")).send().await;
+        resp.assert_status(StatusCode::CONFLICT);
+        // only a new program version reopens a collaboration, as it drops the uploads
+        let resp = client.put(format!("/collaboration/{}/status", id)).body_json(&status("open")).send().await;
+        resp.assert_status(StatusCode::CONFLICT);
+
+        let resp = client.put(format!("/collaboration/{}/status", id)).body_json(&status("cancelled")).send().await;
+        resp.assert_status_is_ok();
+        let resp = client.post(format!("/collaboration/{}/confirm-upload/2", id))
+            .body_json(&vec![Uuid::new_v4().to_string()])
+            .send().await;
+        resp.assert_status(StatusCode::CONFLICT);
+        let resp = client.put(format!("/collaboration/{}/status", id)).body_json(&status("archived")).send().await;
+        resp.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn collaboration_status_not_found() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = client.get("/collaboration/1/status").send().await;
+        resp.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn concurrent_upload_confirmations() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        common::create_parties(&client, 2).await;
+        let resp = create_collaboration_with_fields(&client, &[("number_of_parties", "2")]).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        for party in [1, 2] {
+            common::join_collaboration(&client, id, party, "input").await;
+            client.post(format!("/collaboration/{}/register-input-party/{}", id, party)).send().await.assert_status_is_ok();
+        }
+
+        let confirm = |party: i32| client.post(format!("/collaboration/{}/confirm-upload/{}", id, party))
+            .body_json(&vec![Uuid::new_v4().to_string()])
+            .send();
+        let (first, second) = tokio::join!(confirm(1), confirm(2));
+        first.assert_status_is_ok();
+        second.assert_status_is_ok();
+
+        // wait for the spawned execution to start
+        let mut started = false;
+        for _ in 0..50 {
+            let resp = client.get(format!("/collaboration/{}/status", id)).send().await;
+            let body = assert_ok!(resp.0.into_body().into_json::<serde_json::Value>().await);
+            assert_eq!(body["uploads_confirmed"], 2);
+            if !body["execution_started_at"].is_null() {
+                started = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert!(started, "The execution was not started.");
+        let claim = assert_ok!(collab_ops::claim_execution(id as i32, &db.db_url));
+        assert!(claim.is_none(), "The execution was claimed twice.");
+    }
+
+    #[tokio::test]
+    async fn claim_execution_once() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64() as i32;
+        let mut connection = assert_ok!(establish_connection(&db.db_url));
+        assert_ok!(diesel::sql_query(format!("UPDATE collaborations SET status = 'ready' WHERE id = {}", id)).execute(&mut connection));
+
+        let claims = (0..8)
+            .map(|_| {
+                let db_url = db.db_url.clone();
+                std::thread::spawn(move || collab_ops::claim_execution(id, &db_url).unwrap().is_some())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|claim| claim.join().unwrap())
+            .filter(|claimed| *claimed)
+            .count();
+        assert_eq!(claims, 1, "Expected exactly one claim, got {}", claims);
+        let collab = assert_ok!(collab_ops::get(id, &db.db_url));
+        assert_eq!(collab.status, CollaborationStatus::Running);
+        assert!(assert_ok!(collab_ops::get_result(id, &db.db_url)).is_some());
+    }
+
+    #[tokio::test]
+    async fn long_execution_errors_fail_the_collaboration() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64() as i32;
+        let mut connection = assert_ok!(establish_connection(&db.db_url));
+        assert_ok!(diesel::sql_query(format!("UPDATE collaborations SET status = 'ready' WHERE id = {}", id)).execute(&mut connection));
+        assert_some!(assert_ok!(collab_ops::claim_execution(id, &db.db_url)));
+
+        let message = format!("error sending request for url (http://csmock/0/amphora/{})", "x".repeat(300));
+        participation_ops::fail_execution(id, &message, &db.db_url);
+        let collab = assert_ok!(collab_ops::get(id, &db.db_url));
+        assert_eq!(collab.status, CollaborationStatus::Failed);
+        let result = assert_ok!(collab_ops::get_result(id, &db.db_url)).unwrap();
+        assert_eq!(result.error, Some(message));
+
+        // failing it again is logged, the collaboration stays failed
+        participation_ops::fail_execution(id, "again", &db.db_url);
+        assert_eq!(assert_ok!(collab_ops::get(id, &db.db_url)).status, CollaborationStatus::Failed);
+    }
 }
@@ -0,0 +1,177 @@
+mod common;
+
+#[cfg(test)]
+mod test {
+    use crate::common::{self, create_collaboration_with_fields, create_correct_collaboration, create_party, propose_program, DBTestContext};
+    use coordination_service::db::{collab_ops, establish_connection};
+    use diesel::RunQueryDsl;
+    use reqwest::StatusCode;
+    use tokio_test::assert_ok;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn invite_accept_and_decline() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        let (input, _) = create_party(&client, &["input"]).await;
+        let (output, _) = create_party(&client, &["output"]).await;
+
+        let invite = |party: i32, role: &'static str| client.post(format!("/collaboration/{}/invitations", id))
+            .body_json(&serde_json::json!({"party_id": party, "role": role}))
+            .send();
+        invite(input, "output").await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        invite(input, "creator").await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        invite(42, "input").await.assert_status(StatusCode::NOT_FOUND);
+        let resp = invite(input, "input").await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().object().get("status").assert_string("pending");
+        json.value().object().get("invited_by").assert_string("anonymous");
+        let accepted = json.value().object().get("id").i64();
+        let resp = invite(output, "output").await;
+        resp.assert_status_is_ok();
+        let declined = resp.json().await.value().object().get("id").i64();
+
+        let answer = |invitation: i64, answer: &'static str| client
+            .post(format!("/collaboration/{}/invitations/{}/{}", id, invitation, answer))
+            .send();
+        answer(accepted, "accept").await.assert_status_is_ok();
+        answer(declined, "decline").await.assert_status_is_ok();
+        answer(declined, "accept").await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        answer(42, "accept").await.assert_status(StatusCode::NOT_FOUND);
+
+        let resp = client.get(format!("/collaboration/{}/invitations", id)).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let invitations = json.value().array();
+        invitations.assert_len(2);
+        invitations.get(0).object().get("status").assert_string("accepted");
+        invitations.get(0).object().get("approved_program_sha256").assert_null();
+        invitations.get(1).object().get("status").assert_string("declined");
+
+        // a declined party may register only after it is invited again
+        let resp = client.post(format!("/collaboration/{}/register-output-party/{}", id, output)).send().await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+        invite(output, "output").await.json().await.value().object().get("status").assert_string("pending");
+    }
+
+    #[tokio::test]
+    async fn expired_invitations_can_not_be_accepted() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        let (party, _) = create_party(&client, &["input"]).await;
+
+        let resp = client.post(format!("/collaboration/{}/invitations", id))
+            .body_json(&serde_json::json!({"party_id": party, "role": "input", "expires_at": "2020-01-01T00:00:00Z"}))
+            .send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = client.post(format!("/collaboration/{}/invitations", id))
+            .body_json(&serde_json::json!({"party_id": party, "role": "input"}))
+            .send().await;
+        resp.assert_status_is_ok();
+        let invitation = resp.json().await.value().object().get("id").i64();
+
+        let mut connection = assert_ok!(establish_connection(&db.db_url));
+        assert_ok!(diesel::sql_query(format!("UPDATE invitations SET expires_at = now() - interval '1 minute' WHERE id = {}", invitation)).execute(&mut connection));
+        let resp = client.get(format!("/collaboration/{}/invitations", id)).send().await;
+        resp.json().await.value().array().get(0).object().get("status").assert_string("expired");
+        let resp = client.post(format!("/collaboration/{}/invitations/{}/accept", id, invitation)).send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn parties_approve_the_current_program() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        let (party, _) = create_party(&client, &["input"]).await;
+
+        let resp = client.get(format!("/collaboration/{}/program", id)).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().object().get("program").assert_string(&common::test_program(1));
+        let program_sha256 = json.value().object().get("program_sha256").string().to_string();
+        let collab = assert_ok!(collab_ops::get(id as i32, &db.db_url));
+        assert_eq!(collab.program_sha256, program_sha256);
+
+        let resp = client.post(format!("/collaboration/{}/invitations", id))
+            .body_json(&serde_json::json!({"party_id": party, "role": "input"}))
+            .send().await;
+        let invitation = resp.json().await.value().object().get("id").i64();
+        let approve = |sha256: String| client
+            .post(format!("/collaboration/{}/invitations/{}/approve-program", id, invitation))
+            .body_json(&serde_json::json!({"program_sha256": sha256}))
+            .send();
+        // only accepted invitations approve programs
+        approve(program_sha256.clone()).await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        client.post(format!("/collaboration/{}/invitations/{}/accept", id, invitation)).send().await.assert_status_is_ok();
+
+        let resp = client.post(format!("/collaboration/{}/register-input-party/{}", id, party)).send().await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+        approve("0".repeat(64)).await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = approve(program_sha256.to_uppercase()).await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().object().get("approved_program_sha256").assert_string(&program_sha256);
+        let resp = client.post(format!("/collaboration/{}/register-input-party/{}", id, party)).send().await;
+        resp.assert_status_is_ok();
+    }
+
+    #[tokio::test]
+    async fn execution_waits_for_every_consent() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_collaboration_with_fields(&client, &[("number_of_parties", "1")]).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        let (input, _) = create_party(&client, &["input"]).await;
+        let (output, _) = create_party(&client, &["output"]).await;
+        common::join_collaboration(&client, id, input, "input").await;
+        client.post(format!("/collaboration/{}/register-input-party/{}", id, input)).send().await.assert_status_is_ok();
+        common::join_collaboration(&client, id, output, "output").await;
+        client.post(format!("/collaboration/{}/register-output-party/{}", id, output)).send().await.assert_status_is_ok();
+
+        // a new version needs the consent of both registered parties again
+        propose_program(&client, id, &common::test_program(2)).await.assert_status_is_ok();
+        let input_invitation = common::invitation_id(&client, id, input, "input").await;
+        common::approve_current_program(&client, id, input_invitation).await;
+        client.post(format!("/collaboration/{}/confirm-upload/{}", id, input))
+            .body_json(&vec![Uuid::new_v4().to_string()])
+            .send().await
+            .assert_status_is_ok();
+        assert!(assert_ok!(collab_ops::claim_execution(id as i32, &db.db_url)).is_none(), "The execution was claimed without consent.");
+        let resp = client.get(format!("/collaboration/{}/status", id)).send().await;
+        let json = resp.json().await;
+        json.value().object().get("status").assert_string("ready");
+        json.value().object().get("execution_started_at").assert_null();
+
+        // inviting the registered output party again must not drop it from the consents
+        let resp = client.post(format!("/collaboration/{}/invitations", id))
+            .body_json(&serde_json::json!({"party_id": output, "role": "output"}))
+            .send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(assert_ok!(collab_ops::claim_execution(id as i32, &db.db_url)).is_none(), "The execution was claimed without consent.");
+
+        // the approval starts the execution
+        let output_invitation = common::invitation_id(&client, id, output, "output").await;
+        common::approve_current_program(&client, id, output_invitation).await;
+        let mut started = false;
+        for _ in 0..50 {
+            let resp = client.get(format!("/collaboration/{}/status", id)).send().await;
+            let body = assert_ok!(resp.0.into_body().into_json::<serde_json::Value>().await);
+            if !body["execution_started_at"].is_null() {
+                started = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert!(started, "The execution was not started.");
+    }
+}
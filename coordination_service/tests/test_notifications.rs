@@ -0,0 +1,182 @@
+mod common;
+
+#[cfg(test)]
+mod test {
+    use std::{sync::{Arc, Mutex}, time::Duration};
+
+    use crate::common::{self, DBTestContext, create_correct_collaboration, create_party};
+    use coordination_service::{db::{models::NotificationStatus, notification_ops}, notification_service::{deliver_due, RetryPolicy}};
+    use cs_interface::signature;
+    use poem::{handler, listener::{Acceptor, Listener, TcpListener}, middleware::AddDataEndpoint, put, test::TestClient, web::Data, EndpointExt, Request, Route, Server};
+    use reqwest::StatusCode;
+    use tokio_test::assert_ok;
+
+    /// Body and signature headers of the received notifications
+    type Received = Arc<Mutex<Vec<(String, Option<String>, Option<String>)>>>;
+
+    #[handler]
+    fn notify(req: &Request, body: String, received: Data<&Received>) {
+        let header = |name: &str| req.header(name).map(|v| v.to_string());
+        received.0.lock().unwrap().push((body, header(signature::TIMESTAMP_HEADER), header(signature::SIGNATURE_HEADER)));
+    }
+
+    /// Start an output party that records all notifications, returns its endpoint.
+    async fn spawn_output_party(received: Received) -> String {
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await.unwrap();
+        let addr = acceptor.local_addr()[0].as_socket_addr().cloned().unwrap();
+        let app = Route::new().at("/notify", put(notify)).data(received);
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+        format!("http://{}", addr)
+    }
+
+    #[handler]
+    fn reject() -> poem::http::StatusCode {
+        poem::http::StatusCode::UNPROCESSABLE_ENTITY
+    }
+
+    /// Start an output party that rejects all notifications, returns its endpoint.
+    async fn spawn_rejecting_party() -> String {
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await.unwrap();
+        let addr = acceptor.local_addr()[0].as_socket_addr().cloned().unwrap();
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(Route::new().at("/notify", put(reject))));
+        format!("http://{}", addr)
+    }
+
+    /// Register a new output party of the collaboration at `endpoint`, returns its id.
+    async fn party_at(client: &TestClient<AddDataEndpoint<Route, String>>, collab_id: i32, endpoint: &str) -> i32 {
+        let (party, _) = create_party(client, &["output"]).await;
+        common::join_collaboration(client, collab_id as i64, party, "output").await;
+        client.post(format!("/collaboration/{}/register-output-party/{}", collab_id, party))
+            .query("party_client_endpoint", &endpoint)
+            .send().await
+            .assert_status_is_ok();
+        party
+    }
+
+    fn retry_now(max_attempts: i32) -> RetryPolicy {
+        RetryPolicy { max_attempts, base_delay: Duration::ZERO, max_delay: Duration::ZERO }
+    }
+
+    #[tokio::test]
+    async fn notifications_are_retried_and_redelivered() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64() as i32;
+
+        let received = Received::default();
+        let available = spawn_output_party(received.clone()).await;
+        // nothing listens on the discard port
+        let available = party_at(&client, id, &available).await;
+        let down = party_at(&client, id, "http://127.0.0.1:9").await;
+        assert_ok!(notification_ops::enqueue(id, vec![available, down], r#"{"code":200}"#.to_string(), &db.db_url));
+
+        let policy = retry_now(2);
+        assert_eq!(assert_ok!(deliver_due(&db.db_url, &policy).await), 1);
+        assert_eq!(received.lock().unwrap()[0].0, r#"{"code":200}"#);
+        let pending = assert_ok!(notification_ops::list(Some(id), Some(NotificationStatus::Pending), &db.db_url));
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].party_id, down);
+        assert_eq!(pending[0].attempts, 1);
+        assert!(pending[0].last_error.is_some());
+
+        // the second failure exhausts the attempts
+        assert_eq!(assert_ok!(deliver_due(&db.db_url, &policy).await), 0);
+        let resp = client.get("/notifications").query("status", &"dead").send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let dead = json.value().array();
+        dead.assert_len(1);
+        dead.get(0).object().get("party_id").assert_i64(down as i64);
+        dead.get(0).object().get("attempts").assert_i64(2);
+        let dead_id = dead.get(0).object().get("id").i64();
+        assert_eq!(assert_ok!(deliver_due(&db.db_url, &policy).await), 0);
+
+        let resp = client.post(format!("/notifications/{}/redeliver", dead_id)).send().await;
+        resp.assert_status_is_ok();
+        let all = assert_ok!(notification_ops::list(Some(id), None, &db.db_url));
+        assert_eq!(all.len(), 2);
+        let redelivered = all.iter().find(|n| n.party_id == down).unwrap();
+        assert_eq!(redelivered.status, NotificationStatus::Pending);
+        assert_eq!(redelivered.attempts, 1);
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejected_notifications_are_not_retried() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64() as i32;
+
+        let rejecting = spawn_rejecting_party().await;
+        let party = party_at(&client, id, &rejecting).await;
+        assert_ok!(notification_ops::enqueue(id, vec![party], r#"{"code":500}"#.to_string(), &db.db_url));
+
+        assert_eq!(assert_ok!(deliver_due(&db.db_url, &retry_now(5)).await), 0);
+        let dead = assert_ok!(notification_ops::list(Some(id), Some(NotificationStatus::Dead), &db.db_url));
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, 1);
+        assert!(dead[0].last_error.as_deref().unwrap().starts_with("status 422"));
+    }
+
+    #[tokio::test]
+    async fn redeliver_unknown_notification() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = client.post("/notifications/1/redeliver").send().await;
+        resp.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn notifications_are_signed() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        common::create_parties(&client, 1).await;
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64() as i32;
+        common::join_collaboration(&client, id as i64, 1, "output").await;
+
+        let received = Received::default();
+        let endpoint = spawn_output_party(received.clone()).await;
+        let secret = "0123456789abcdef0123456789abcdef";
+        let resp = client.post(format!("/collaboration/{}/register-output-party/1", id))
+            .query("party_client_endpoint", &endpoint)
+            .header("X-Notification-Secret", secret)
+            .send().await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().object().get("notification_secret").assert_string(secret);
+
+        let body = r#"{"code":200,"collaborationId":1}"#;
+        assert_ok!(notification_ops::enqueue(id, vec![1], body.to_string(), &db.db_url));
+        assert_eq!(assert_ok!(deliver_due(&db.db_url, &retry_now(1)).await), 1);
+        let (received_body, timestamp, signature) = received.lock().unwrap()[0].clone();
+        assert_eq!(received_body, body);
+        let now = chrono::Utc::now().timestamp();
+        assert_ok!(signature::verify(secret, &timestamp.unwrap(), &signature.unwrap(), body.as_bytes(), now));
+    }
+
+    #[tokio::test]
+    async fn output_party_secret_is_generated() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        common::create_parties(&client, 1).await;
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        common::join_collaboration(&client, id, 1, "output").await;
+
+        let resp = client.post(format!("/collaboration/{}/register-output-party/1?party_client_endpoint=abc123", id)).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        assert_eq!(json.value().object().get("notification_secret").string().len(), 64);
+
+        let resp = client.post(format!("/collaboration/{}/register-output-party/1?party_client_endpoint=abc123", id))
+            .header("X-Notification-Secret", "short")
+            .send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,119 @@
+mod common;
+
+#[cfg(test)]
+mod test {
+    use crate::common::{self, create_correct_collaboration, create_party, DBTestContext};
+    use coordination_service::db::{collab_ops, party_ops};
+    use reqwest::StatusCode;
+    use tokio_test::assert_ok;
+
+    #[tokio::test]
+    async fn manage_parties() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+
+        let resp = client.post("/party")
+            .body_json(&serde_json::json!({"display_name": "Hospital A", "organisation": "Hospitals", "roles": ["input", "output", "input"]}))
+            .send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let party = json.value().object().get("party").object();
+        let id = party.get("id").i64();
+        party.get("roles").assert_string_array(&["input", "output"]);
+        party.get("active").assert_bool(true);
+        assert!(party.get_opt("api_key_sha256").is_none());
+        assert_eq!(json.value().object().get("api_key").string().len(), 64);
+
+        let resp = client.put(format!("/party/{}", id))
+            .body_json(&serde_json::json!({"client_endpoint": "http://hospital-a:8080"}))
+            .send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().object().get("client_endpoint").assert_string("http://hospital-a:8080");
+        json.value().object().get("organisation").assert_string("Hospitals");
+
+        let resp = client.get(format!("/party/{}", id)).send().await;
+        resp.assert_status_is_ok();
+        resp.json().await.value().object().get("display_name").assert_string("Hospital A");
+
+        client.delete(format!("/party/{}", id)).send().await.assert_status_is_ok();
+        client.get("/party").send().await.json().await.value().array().assert_len(0);
+        let resp = client.get("/party").query("include_inactive", &true).send().await;
+        resp.json().await.value().array().get(0).object().get("active").assert_bool(false);
+
+        let resp = client.post("/party")
+            .body_json(&serde_json::json!({"display_name": " ", "roles": []}))
+            .send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = client.post("/party")
+            .body_json(&serde_json::json!({"display_name": "B", "roles": ["owner"]}))
+            .send().await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+        client.get("/party/42").send().await.assert_status(StatusCode::NOT_FOUND);
+        client.put("/party/42").body_json(&serde_json::json!({})).send().await.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn registrations_need_registered_parties() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        let (output_only, _) = create_party(&client, &["output"]).await;
+
+        let resp = client.post(format!("/collaboration/{}/register-input-party/42", id)).send().await;
+        resp.assert_status(StatusCode::NOT_FOUND);
+        let resp = client.post(format!("/collaboration/{}/register-input-party/{}", id, output_only)).send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (input, _) = create_party(&client, &["input"]).await;
+        client.delete(format!("/party/{}", input)).send().await.assert_status_is_ok();
+        let resp = client.post(format!("/collaboration/{}/register-input-party/{}", id, input)).send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = client.post(format!("/collaboration/{}/register-output-party/{}", id, input)).send().await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn output_parties_are_notified_at_their_registry_endpoint() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64() as i32;
+        let (party, _) = create_party(&client, &["output"]).await;
+        common::join_collaboration(&client, id as i64, party, "output").await;
+
+        let resp = client.post(format!("/collaboration/{}/register-output-party/{}", id, party)).send().await;
+        resp.assert_status_is_ok();
+        assert_eq!(assert_ok!(collab_ops::output_parties(id, &db.db_url)), vec![party]);
+        let endpoint = || assert_ok!(party_ops::get(party, &db.db_url)).client_endpoint;
+        assert_eq!(endpoint(), Some("http://localhost:1".to_string()));
+
+        // registering again with an endpoint moves the party
+        let resp = client.post(format!("/collaboration/{}/register-output-party/{}", id, party))
+            .query("party_client_endpoint", &"http://moved:8080")
+            .send().await;
+        resp.assert_status_is_ok();
+        let secret = resp.json().await.value().object().get("notification_secret").string().to_string();
+        assert_eq!(endpoint(), Some("http://moved:8080".to_string()));
+        assert_eq!(assert_ok!(collab_ops::output_party_address(id, party, &db.db_url)), Some((Some("http://moved:8080".to_string()), secret.clone())));
+
+        // one update of the registry applies to all collaborations
+        client.put(format!("/party/{}", party))
+            .body_json(&serde_json::json!({"client_endpoint": "http://again:8080"}))
+            .send().await
+            .assert_status_is_ok();
+        assert_eq!(endpoint(), Some("http://again:8080".to_string()));
+        assert_eq!(assert_ok!(collab_ops::output_party_address(id, party, &db.db_url)), Some((Some("http://again:8080".to_string()), secret)));
+
+        // a rejected registration leaves the endpoint as it is
+        let other = create_correct_collaboration(&client).await.json().await.value().object().get("id").i64();
+        let resp = client.post(format!("/collaboration/{}/register-output-party/{}", other, party))
+            .query("party_client_endpoint", &"http://rejected:8080")
+            .send().await;
+        resp.assert_status(StatusCode::FORBIDDEN);
+        assert_eq!(endpoint(), Some("http://again:8080".to_string()));
+    }
+}
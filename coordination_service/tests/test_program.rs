@@ -0,0 +1,170 @@
+mod common;
+
+#[cfg(test)]
+mod test {
+    use crate::common::{self, collaboration_form_with_program, create_collaboration_with_fields, create_correct_collaboration, create_party, propose_program, DBTestContext};
+    use coordination_service::db::{collab_ops, establish_connection};
+    use diesel::RunQueryDsl;
+    use reqwest::StatusCode;
+    use tokio_test::assert_ok;
+    use uuid::Uuid;
+
+    fn sha256_hex(value: &str) -> String {
+        ring::digest::digest(&ring::digest::SHA256, value.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn programs_are_versioned() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let id = json.value().object().get("id").i64();
+        json.value().object().get("program_sha256").assert_string(&sha256_hex(&common::test_program(1)));
+
+        // the same program is not a new version
+        propose_program(&client, id, &common::test_program(1)).await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = propose_program(&client, id, &common::test_program(2)).await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().object().get("version").assert_i64(2);
+        json.value().object().get("program_sha256").assert_string(&sha256_hex(&common::test_program(2)));
+        json.value().object().get("proposed_by").assert_string("anonymous");
+
+        let resp = client.get(format!("/collaboration/{}/program", id)).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().object().get("version").assert_i64(2);
+        json.value().object().get("program").assert_string(&common::test_program(2));
+        let resp = client.get(format!("/collaboration/{}/program", id)).query("version", &"1").send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        json.value().object().get("program").assert_string(&common::test_program(1));
+        json.value().object().get("program_sha256").assert_string(&sha256_hex(&common::test_program(1)));
+        client.get(format!("/collaboration/{}/program", id)).query("version", &"3").send().await.assert_status(StatusCode::NOT_FOUND);
+
+        // going back to a former program is a new version as well
+        propose_program(&client, id, &common::test_program(1)).await.assert_status_is_ok();
+        let resp = client.get(format!("/collaboration/{}/program/versions", id)).send().await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let versions = json.value().array();
+        versions.assert_len(3);
+        versions.get(0).object().get("program_sha256").assert_string(&sha256_hex(&common::test_program(1)));
+        versions.get(2).object().get("version").assert_i64(3);
+        versions.get(2).object().get("program_sha256").assert_string(&sha256_hex(&common::test_program(1)));
+
+        // programs are stored once per content
+        let other = create_correct_collaboration(&client).await.json().await.value().object().get("id").i64();
+        propose_program(&client, other, &common::test_program(2)).await.assert_status_is_ok();
+        let mut connection = assert_ok!(establish_connection(&db.db_url));
+        #[derive(diesel::QueryableByName)]
+        struct Count {
+            #[diesel(sql_type = diesel::sql_types::BigInt)]
+            count: i64,
+        }
+        let programs = assert_ok!(diesel::sql_query("SELECT count(*) AS count FROM programs").get_result::<Count>(&mut connection));
+        assert_eq!(programs.count, 2);
+
+        propose_program(&client, 42, &common::test_program(2)).await.assert_status(StatusCode::NOT_FOUND);
+        client.get("/collaboration/42/program/versions").send().await.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn new_versions_reset_consents_and_uploads() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_collaboration_with_fields(&client, &[("number_of_parties", "2")]).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        let mut parties = Vec::new();
+        for _ in 0..2 {
+            let (party, _) = create_party(&client, &["input"]).await;
+            common::join_collaboration(&client, id, party, "input").await;
+            client.post(format!("/collaboration/{}/register-input-party/{}", id, party)).send().await.assert_status_is_ok();
+            parties.push(party);
+        }
+        let confirm = |party: i32| client.post(format!("/collaboration/{}/confirm-upload/{}", id, party))
+            .body_json(&vec![Uuid::new_v4().to_string()])
+            .send();
+        confirm(parties[0]).await.assert_status_is_ok();
+
+        propose_program(&client, id, &common::test_program(4)).await.assert_status_is_ok();
+        let resp = client.get(format!("/collaboration/{}/status", id)).send().await;
+        let json = resp.json().await;
+        let body = json.value().object();
+        body.get("status").assert_string("open");
+        body.get("uploads_confirmed").assert_i64(0);
+        body.get("parties").array().assert_len(2);
+        body.get("program_version").assert_i64(2);
+        body.get("program_sha256").assert_string(&sha256_hex(&common::test_program(4)));
+        let resp = client.get(format!("/collaboration/{}/invitations", id)).send().await;
+        resp.json().await.value().array().get(0).object().get("approved_program_sha256").assert_null();
+
+        // uploads need the approval of the new version
+        confirm(parties[0]).await.assert_status(StatusCode::FORBIDDEN);
+        // registered parties are not invited again
+        let invite = client.post(format!("/collaboration/{}/invitations", id))
+            .body_json(&serde_json::json!({"party_id": parties[0], "role": "input"}))
+            .send().await;
+        invite.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let invitation = common::invitation_id(&client, id, parties[0], "input").await;
+        common::approve_current_program(&client, id, invitation).await;
+        confirm(parties[0]).await.assert_status_is_ok();
+        let resp = client.get(format!("/collaboration/{}/status", id)).send().await;
+        resp.json().await.value().object().get("status").assert_string("collecting");
+    }
+
+    #[tokio::test]
+    async fn results_record_the_executed_program() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let resp = create_correct_collaboration(&client).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        propose_program(&client, id, &common::test_program(2)).await.assert_status_is_ok();
+        let mut connection = assert_ok!(establish_connection(&db.db_url));
+        assert_ok!(diesel::sql_query(format!("UPDATE collaborations SET status = 'ready' WHERE id = {}", id)).execute(&mut connection));
+
+        assert!(assert_ok!(collab_ops::claim_execution(id as i32, &db.db_url)).is_some());
+        let result = assert_ok!(collab_ops::get_result(id as i32, &db.db_url)).unwrap();
+        assert_eq!(result.program_sha256, sha256_hex(&common::test_program(2)));
+        let resp = client.get(format!("/collaboration/{}/status", id)).send().await;
+        resp.json().await.value().object().get("executed_program_sha256").assert_string(&sha256_hex(&common::test_program(2)));
+
+        // the program of a running collaboration is fixed
+        propose_program(&client, id, &common::test_program(3)).await.assert_status(StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn programs_are_checked_before_they_are_stored() {
+        let db = DBTestContext::new();
+        let client = common::test_client(&db.db_url);
+        let two_columns = r#"{"columns":[{"name":"a","type":"int"},{"name":"b","type":"int"}]}"#;
+        let program = common::test_program(3);
+        let fields = [("number_of_parties", "2"), ("csv_schema", two_columns), ("mpc_program", program.as_str())];
+        let resp = create_collaboration_with_fields(&client, &fields).await;
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        resp.json().await.value().object().get("message")
+            .assert_string("The program reads 3 inputs, rows of 2 columns need a multiple of 2");
+        let invalid: [&[u8]; 3] = [b"", &[0xc3, 0x28], b"print_ln('no inputs')"];
+        for program in invalid {
+            let form = collaboration_form_with_program(&[], program).await;
+            client.post("/collaboration").multipart(form).send().await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        let resp = client.get("/collaboration").send().await;
+        resp.json().await.value().array().assert_len(0);
+
+        let resp = create_collaboration_with_fields(&client, &[("number_of_parties", "2"), ("csv_schema", two_columns)]).await;
+        resp.assert_status_is_ok();
+        let id = resp.json().await.value().object().get("id").i64();
+        propose_program(&client, id, &common::test_program(3)).await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        // a single row of one party is enough
+        propose_program(&client, id, &common::test_program(2)).await.assert_status_is_ok();
+    }
+}
@@ -0,0 +1,458 @@
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use crate::cs_client::{ClearTextSecret, SecretTag};
+use crate::cs_config::CarbynestackConfig;
+use crate::error::{Error, Result};
+use crate::gfp::{parse_bigint, GfpCodec};
+use crate::netaccess::NetAccess;
+
+/// Shares of input masks or secrets as delivered by a single Amphora instance.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct OutputDeliveryObject {
+    secret_shares: String,
+    r_shares: String,
+    v_shares: String,
+    w_shares: String,
+    u_shares: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MaskedInput {
+    secret_id: String,
+    data: Vec<MaskedInputData>,
+    tags: Vec<SecretTag>,
+}
+
+#[derive(Serialize, Debug)]
+struct MaskedInputData {
+    value: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SecretShare {
+    #[serde(default)]
+    tags: Vec<SecretTag>,
+    #[serde(flatten)]
+    shares: OutputDeliveryObject,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Metadata {
+    secret_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetadataPage {
+    content: Vec<Metadata>,
+}
+
+/// Combined clear text values of the output delivery objects of all providers.
+struct Combined {
+    secrets: Vec<BigInt>,
+    rs: Vec<BigInt>,
+    vs: Vec<BigInt>,
+    ws: Vec<BigInt>,
+    us: Vec<BigInt>,
+}
+
+/// Rust native client of the Amphora services of all providers in a CarbyneStack config.
+///
+/// Secrets are masked with input masks of all providers before they are uploaded,
+/// and reconstructed from the shares of all providers when downloaded.
+pub struct AmphoraClient<N: NetAccess> {
+    config: CarbynestackConfig,
+    codec: GfpCodec,
+    net: N,
+}
+
+impl<N: NetAccess> AmphoraClient<N> {
+    pub fn new(config: CarbynestackConfig, net: N) -> Result<AmphoraClient<N>> {
+        let codec = GfpCodec::from_config(&config)?;
+        Ok(AmphoraClient { config, codec, net })
+    }
+
+    fn amphora_urls(&self) -> Vec<String> {
+        self.config
+            .providers
+            .iter()
+            .map(|p| p.amphora_service_url.trim_end_matches('/').to_string())
+            .collect()
+    }
+
+    /// Create a single secret holding all values. Returns the id of the created secret.
+    pub async fn create_secrets(
+        &self,
+        secrets: Vec<String>,
+        uuid: Option<String>,
+    ) -> Result<Vec<String>> {
+        self.create_tagged_secrets(secrets, uuid, Vec::new()).await
+    }
+
+    /// Create a single secret holding all values and the given tags.
+    pub async fn create_tagged_secrets(
+        &self,
+        secrets: Vec<String>,
+        uuid: Option<String>,
+        tags: Vec<SecretTag>,
+    ) -> Result<Vec<String>> {
+        let values = secrets
+            .iter()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| parse_bigint(s))
+            .collect::<Result<Vec<BigInt>>>()?;
+        if values.is_empty() {
+            return Err(Error::InvalidNumber("no secret values given".to_string()));
+        }
+        let secret_id = uuid.unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        // The secret id is used as request id, so all providers hand out the same masks.
+        let mut deliveries = Vec::new();
+        for url in self.amphora_urls() {
+            let bytes = self
+                .net
+                .get(&format!(
+                    "{}/input-masks?requestId={}&count={}",
+                    url,
+                    secret_id,
+                    values.len()
+                ))
+                .await?;
+            deliveries.push(serde_json::from_slice::<OutputDeliveryObject>(&bytes)?);
+        }
+        let masks = combine(&self.codec, &deliveries)?;
+        check_consistency(&masks, &self.codec)?;
+        if masks.secrets.len() != values.len() {
+            return Err(Error::InvalidShare(format!(
+                "requested {} input masks, got {}",
+                values.len(),
+                masks.secrets.len()
+            )));
+        }
+
+        let masked_input = MaskedInput {
+            secret_id: secret_id.clone(),
+            data: values
+                .iter()
+                .zip(&masks.secrets)
+                .map(|(value, mask)| MaskedInputData {
+                    value: self.codec.encode(&[self.codec.reduce(&(value - mask))]),
+                })
+                .collect(),
+            tags,
+        };
+        let body = serde_json::to_string(&masked_input)?;
+        for url in self.amphora_urls() {
+            self.net.post(&format!("{}/masked-inputs", url), body.clone()).await?;
+        }
+        event!(Level::DEBUG, "Secret {} created with {} values", secret_id, values.len());
+        Ok(vec![secret_id])
+    }
+
+    /// Get the clear text values of a secret.
+    pub async fn get_secret(&self, secret_id: &str) -> Result<ClearTextSecret> {
+        let request_id = Uuid::new_v4();
+        let mut responses = Vec::new();
+        for url in self.amphora_urls() {
+            responses.push(
+                self.net
+                    .get(&format!(
+                        "{}/secret-shares/{}?requestId={}",
+                        url, secret_id, request_id
+                    ))
+                    .await?,
+            );
+        }
+        reconstruct(&self.codec, secret_id, &responses)
+    }
+
+    /// List the ids of all secrets stored by the first provider.
+    pub async fn list_secrets(&self) -> Result<Vec<String>> {
+        let url = match self.amphora_urls().into_iter().next() {
+            Some(url) => url,
+            None => return Ok(vec![]),
+        };
+        let bytes = self.net.get(&format!("{}/secret-shares", url)).await?;
+        let page = serde_json::from_slice::<MetadataPage>(&bytes)?;
+        Ok(page.content.into_iter().map(|m| m.secret_id).collect())
+    }
+
+    /// Delete secrets on all providers.
+    pub async fn delete_secrets(&self, secret_ids: Vec<String>) -> Result<String> {
+        for url in self.amphora_urls() {
+            for id in &secret_ids {
+                self.net.delete(&format!("{}/secret-shares/{}", url, id)).await?;
+            }
+        }
+        Ok(format!("Deleted secrets {}", secret_ids.join(", ")))
+    }
+}
+
+/// Decode the output delivery objects of all providers and add them up.
+fn combine(codec: &GfpCodec, deliveries: &[OutputDeliveryObject]) -> Result<Combined> {
+    let combine = |f: fn(&OutputDeliveryObject) -> &str| {
+        codec.combine(&deliveries.iter().map(f).collect::<Vec<&str>>())
+    };
+    Ok(Combined {
+        secrets: combine(|d| &d.secret_shares)?,
+        rs: combine(|d| &d.r_shares)?,
+        vs: combine(|d| &d.v_shares)?,
+        ws: combine(|d| &d.w_shares)?,
+        us: combine(|d| &d.u_shares)?,
+    })
+}
+
+/// Reconstruct a secret from the raw `secret-shares` responses of all providers of the config, in provider order.
+///
+/// All responses have to belong to the same request id. Fails with an `IntegrityError`
+/// if the combined shares do not satisfy w = s * r and u = v * r, i.e. a provider tampered with its share.
+pub fn reconstruct_secret(config: &CarbynestackConfig, secret_id: &str, responses: &[Vec<u8>]) -> Result<ClearTextSecret> {
+    if responses.len() != config.providers.len() {
+        return Err(Error::InvalidShare(format!(
+            "got shares of {} providers, expected {}",
+            responses.len(),
+            config.providers.len()
+        )));
+    }
+    reconstruct(&GfpCodec::from_config(config)?, secret_id, responses)
+}
+
+fn reconstruct(codec: &GfpCodec, secret_id: &str, responses: &[Vec<u8>]) -> Result<ClearTextSecret> {
+    let mut deliveries = Vec::new();
+    let mut tags = Vec::new();
+    for bytes in responses {
+        let share = serde_json::from_slice::<SecretShare>(bytes)?;
+        // tags are stored alike on all providers
+        if tags.is_empty() {
+            tags = share.tags;
+        }
+        deliveries.push(share.shares);
+    }
+    let combined = combine(codec, &deliveries)?;
+    check_consistency(&combined, codec)?;
+    ClearTextSecret::from_field(secret_id, &combined.secrets, codec, tags)
+}
+
+/// Check the relations w = s * r and u = v * r for all combined values.
+fn check_consistency(combined: &Combined, codec: &GfpCodec) -> Result<()> {
+    let len = combined.secrets.len();
+    if [&combined.rs, &combined.vs, &combined.ws, &combined.us]
+        .iter()
+        .any(|v| v.len() != len)
+    {
+        return Err(Error::IntegrityError("number of shares does not match".to_string()));
+    }
+    for i in 0..len {
+        if codec.reduce(&(&combined.secrets[i] * &combined.rs[i])) != combined.ws[i]
+            || codec.reduce(&(&combined.vs[i] * &combined.rs[i])) != combined.us[i]
+        {
+            return Err(Error::IntegrityError(format!("share {} is inconsistent", i)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use crate::netaccess::MockNetAccess;
+
+    use super::*;
+
+    fn config() -> CarbynestackConfig {
+        CarbynestackConfig::from_json(
+            r#"{
+            "noSslValidation":true,
+            "prime":"198766463529478683931867765928436695041",
+            "providers":[
+                {"amphoraServiceUrl":"http://csmock/0/amphora",
+                "baseUrl":"http://csmock/0/",
+                "castorServiceUrl":"http://csmock/0/castor",
+                "ephemeralServiceUrl":"http://csmock/0/",
+                "id":1},
+                {"amphoraServiceUrl":"http://csmock/1/amphora",
+                "baseUrl":"http://csmock/1/",
+                "castorServiceUrl":"http://csmock/1/castor",
+                "ephemeralServiceUrl":"http://csmock/1/",
+                "id":2}],
+            "r":"141515903391459779531506841503331516415",
+            "rinv":"133854242216446749056083838363708373830"}"#,
+        )
+        .unwrap()
+    }
+
+    /// Build the output delivery object of one provider for the given secret shares.
+    /// r is shared as (2, 3), v as (4, 5); w and u are split between both providers.
+    fn delivery(provider: usize, secrets: &[(i64, i64)], tamper: bool) -> String {
+        let codec = GfpCodec::from_config(&config()).unwrap();
+        let pick = |pair: (i64, i64)| BigInt::from(if provider == 0 { pair.0 } else { pair.1 });
+        let s = secrets.iter().map(|s| pick(*s)).collect::<Vec<BigInt>>();
+        let r = secrets.iter().map(|_| pick((2, 3))).collect::<Vec<BigInt>>();
+        let v = secrets.iter().map(|_| pick((4, 5))).collect::<Vec<BigInt>>();
+        let w = secrets
+            .iter()
+            .map(|(s0, s1)| {
+                let w = BigInt::from((s0 + s1) * 5 + if tamper { 1 } else { 0 });
+                if provider == 0 { w } else { BigInt::from(0) }
+            })
+            .collect::<Vec<BigInt>>();
+        let u = secrets
+            .iter()
+            .map(|_| pick((45, 0)))
+            .collect::<Vec<BigInt>>();
+        serde_json::json!({
+            "secretId": "id",
+            "tags": [{"key": "gameID", "value": "game-1", "valueType": "STRING"}],
+            "secretShares": codec.encode(&s),
+            "rShares": codec.encode(&r),
+            "vShares": codec.encode(&v),
+            "wShares": codec.encode(&w),
+            "uShares": codec.encode(&u),
+            "data": codec.encode(&s),
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_create_secrets() -> Result<()> {
+        let mut net = MockNetAccess::new();
+        net.expect_get()
+            .times(2)
+            .returning(|url| {
+                let provider = if url.contains("/0/") { 0 } else { 1 };
+                Ok(delivery(provider, &[(10, 20), (1, 2)], false).into_bytes())
+            })
+            .withf(|url| url.ends_with("/amphora/input-masks?requestId=secret-1&count=2"));
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let captured = bodies.clone();
+        net.expect_post()
+            .times(2)
+            .returning(move |_, body| {
+                captured.lock().unwrap().push(body);
+                Ok(vec![])
+            })
+            .withf(|url, _| url.ends_with("/amphora/masked-inputs"));
+
+        let client = AmphoraClient::new(config(), net)?;
+        let ids = client
+            .create_secrets(vec!["100".to_string(), "-1".to_string(), "".to_string()], Some("secret-1".to_string()))
+            .await?;
+        assert_eq!(ids, vec!["secret-1"]);
+
+        let codec = GfpCodec::from_config(&config())?;
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies[0], bodies[1]);
+        let body: serde_json::Value = serde_json::from_str(&bodies[0])?;
+        assert_eq!(body["secretId"], "secret-1");
+        let masked = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| codec.decode(d["value"].as_str().unwrap()).unwrap()[0].clone())
+            .collect::<Vec<BigInt>>();
+        // masked value + mask = secret
+        assert_eq!(codec.reduce(&(&masked[0] + 30)), BigInt::from(100));
+        assert_eq!(codec.reduce(&(&masked[1] + 3)), codec.reduce(&BigInt::from(-1)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_secrets_invalid_value() -> Result<()> {
+        let mut net = MockNetAccess::new();
+        net.expect_get().times(0);
+        net.expect_post().times(0);
+        let client = AmphoraClient::new(config(), net)?;
+        let res = client.create_secrets(vec!["abcd".to_string()], None).await;
+        assert!(matches!(res, Err(Error::InvalidNumber(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_secret() -> Result<()> {
+        let mut net = MockNetAccess::new();
+        net.expect_get()
+            .times(2)
+            .returning(|url| {
+                let provider = if url.contains("/0/") { 0 } else { 1 };
+                Ok(delivery(provider, &[(20, 2), (5, 6), (-3, 2)], false).into_bytes())
+            })
+            .withf(|url| url.contains("/amphora/secret-shares/secret-1?requestId="));
+        let client = AmphoraClient::new(config(), net)?;
+        let secret = client.get_secret("secret-1").await?;
+        assert_eq!(secret.secret_id, "secret-1");
+        assert_eq!(secret.values, vec![22, 11, -1]);
+        assert_eq!(secret.game_id.as_deref(), Some("game-1"));
+        assert_eq!(secret.creation_date, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_secret_tampered() -> Result<()> {
+        let mut net = MockNetAccess::new();
+        net.expect_get()
+            .times(2)
+            .returning(|url| {
+                let provider = if url.contains("/0/") { 0 } else { 1 };
+                Ok(delivery(provider, &[(20, 2)], true).into_bytes())
+            });
+        let client = AmphoraClient::new(config(), net)?;
+        let res = client.get_secret("secret-1").await;
+        assert!(matches!(res, Err(Error::IntegrityError(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconstruct_secret() -> Result<()> {
+        let responses = |tamper| (0..2).map(|p| delivery(p, &[(7, -2)], tamper).into_bytes()).collect::<Vec<Vec<u8>>>();
+        let secret = reconstruct_secret(&config(), "secret-1", &responses(false))?;
+        assert_eq!(secret.values, vec![5]);
+        assert!(matches!(reconstruct_secret(&config(), "secret-1", &responses(true)), Err(Error::IntegrityError(_))));
+        assert!(matches!(reconstruct_secret(&config(), "secret-1", &responses(false)[..1]), Err(Error::InvalidShare(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_and_delete_secrets() -> Result<()> {
+        let mut net = MockNetAccess::new();
+        net.expect_get()
+            .times(1)
+            .returning(|_| Ok(r#"{"content":[{"secretId":"a","tags":[]},{"secretId":"b","tags":[]}]}"#.as_bytes().to_vec()))
+            .withf(|url| url == "http://csmock/0/amphora/secret-shares");
+        net.expect_delete()
+            .times(4)
+            .returning(|_| Ok(vec![]));
+        let client = AmphoraClient::new(config(), net)?;
+        assert_eq!(client.list_secrets().await?, vec!["a", "b"]);
+        client.delete_secrets(vec!["a".to_string(), "b".to_string()]).await?;
+        Ok(())
+    }
+
+    /// Two clients with different configs running in parallel only talk to their own providers.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_configs_are_isolated() -> Result<()> {
+        let client = |host: &'static str| {
+            let mut config = config();
+            for provider in config.providers.iter_mut() {
+                provider.amphora_service_url = provider.amphora_service_url.replace("csmock", host);
+            }
+            let mut net = MockNetAccess::new();
+            net.expect_get()
+                .times(1)
+                .returning(move |_| Ok(format!(r#"{{"content":[{{"secretId":"{}","tags":[]}}]}}"#, host).into_bytes()))
+                .withf(move |url| url == format!("http://{}/0/amphora/secret-shares", host));
+            AmphoraClient::new(config, net)
+        };
+        let (left, right) = (client("party-a")?, client("party-b")?);
+        let (left, right) = tokio::join!(left.list_secrets(), right.list_secrets());
+        assert_eq!(left?, vec!["party-a"]);
+        assert_eq!(right?, vec!["party-b"]);
+        Ok(())
+    }
+}
@@ -1,47 +1,106 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::encoding::FixedPointEncoding;
+use crate::gfp::GfpCodec;
 use mockall::predicate::*;
 use mockall::*;
+use num_bigint::BigInt;
 use poem_openapi::Object;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /// Mockable cs client interface
+// Only used with concrete types, so auto trait bounds are checked at the call sites.
+#[allow(async_fn_in_trait)]
 #[automock]
 pub trait CsClient {
-    fn create_secrets(
+    async fn create_secrets(
         &self,
         secrets: Vec<String>,
         uuid: Option<String>,
     ) -> Result<Vec<String>>;
-    fn delete_secrets(&self, secrets: Vec<String>) -> Result<String>;
-    fn get_secret(&self, secret_id: &str) -> Result<ClearTextSecret>;
-    fn list_secrets(&self) -> Result<Vec<String>>;
+    async fn delete_secrets(&self, secrets: Vec<String>) -> Result<String>;
+    async fn get_secret(&self, secret_id: &str) -> Result<ClearTextSecret>;
+    async fn list_secrets(&self) -> Result<Vec<String>>;
     fn get_comp_party_urls(&self) -> Vec<String>;
-    fn execute_program(&self, spdz_program: String, secret_ids: Vec<String>) -> Result<String>;
+    /// Execute the base64 encoded program and return the ids of the result secrets.
+    async fn execute_program(&self, spdz_program: String, secret_ids: Vec<String>) -> Result<Vec<String>>;
+}
+
+/// Tag key of the game id Ephemeral attaches to computation results.
+pub const TAG_GAME_ID: &str = "gameID";
+/// Tag key of the creation date Amphora attaches to every secret.
+pub const TAG_CREATION_DATE: &str = "creation-date";
+
+#[derive(Serialize, Deserialize, Object, Debug, Clone, PartialEq)]
+#[oai(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+pub struct SecretTag {
+    pub key: String,
+    pub value: String,
+    pub value_type: Option<String>,
 }
 
 #[derive(Serialize, Object, Debug)]
+#[oai(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
 pub struct ClearTextSecret {
-    pub result: String,
-    // pub creation_date: Option<String>,
-    // pub game_id: Option<String>
+    pub secret_id: String,
+    /// All values of the secret. Field elements above prime / 2 are negative values.
+    pub values: Vec<i64>,
+    /// The values decoded as fixed-point decimals, set if the collaboration uses a fractional precision.
+    pub decimal_values: Option<Vec<f64>>,
+    pub tags: Vec<SecretTag>,
+    pub creation_date: Option<String>,
+    pub game_id: Option<String>,
+}
+
+impl ClearTextSecret {
+    /// Build a secret from its clear text field elements, creation date and game id are taken from the tags.
+    pub(crate) fn from_field(
+        secret_id: &str,
+        values: &[BigInt],
+        codec: &GfpCodec,
+        tags: Vec<SecretTag>,
+    ) -> Result<ClearTextSecret> {
+        let values = values
+            .iter()
+            .map(|v| {
+                let signed = codec.to_signed(v);
+                i64::try_from(&signed).map_err(|_| {
+                    Error::InvalidNumber(format!("{} does not fit into a 64 bit integer", signed))
+                })
+            })
+            .collect::<Result<Vec<i64>>>()?;
+        let tag = |key: &str| tags.iter().find(|t| t.key == key).map(|t| t.value.clone());
+        Ok(ClearTextSecret {
+            secret_id: secret_id.to_string(),
+            values,
+            decimal_values: None,
+            creation_date: tag(TAG_CREATION_DATE),
+            game_id: tag(TAG_GAME_ID),
+            tags,
+        })
+    }
+
+    /// Decode the values with the fixed-point encoding of the collaboration.
+    pub fn decode_fixed_point(&mut self, encoding: &FixedPointEncoding) {
+        if encoding.precision() > 0 {
+            self.decimal_values = Some(self.values.iter().map(|v| encoding.decode(*v)).collect());
+        }
+    }
 }
 
 // export JavaCsClient
 pub use java_cs_client::JavaCsClient;
 mod java_cs_client {
-    use crate::{cs_config::CarbynestackConfig, error::Error};
-    use std::{env, ffi::OsStr, io::Write, process::{Child, Command, Stdio}, sync::Mutex};
+    use crate::{cs_config::CarbynestackConfig, error::Error, gfp::parse_bigint};
+    use std::{env, ffi::OsStr, path::Path, process::Stdio};
+    use tempdir::TempDir;
+    use tokio::{io::AsyncWriteExt, process::{Child, Command}};
 
     use super::*;
     use base64::{prelude::BASE64_STANDARD, Engine};
-    use lazy_static::lazy_static;
     use tracing::{event, Level};
 
-    // used to prevent race condition on different cs-configs.
-    lazy_static! {
-        static ref CONFIG_LOCK: Mutex<i32> = Mutex::new(0i32);
-    }
-
     fn jar_location() -> String {
         match env::var("CS_JAR_LOCATION") {
             Ok(addr) => addr,
@@ -49,34 +108,62 @@ mod java_cs_client {
         }
     }
 
+    fn java_binary() -> String {
+        match env::var("CS_JAVA_BINARY") {
+            Ok(java) => java,
+            Err(_) => "java".to_string(),
+        }
+    }
+
     pub struct JavaCsClient {
         config: CarbynestackConfig,
+        java_binary: String,
     }
 
     struct SecretUtils {}
     impl SecretUtils {
-        pub fn parse_secret(resp: String) -> ClearTextSecret {
-            let strings = resp.split("\n").collect::<Vec<&str>>();
-            let res = ClearTextSecret {
-                result: String::from(strings[0].replace("[", "").replace("]", "")),
-                // creation_date: None,
-                // game_id: None,
-            };
-            // for s in strings {
-            //     let s = String::from(s.trim());
-            //     if s.starts_with("creation-date") {
-            //         res.creation_date = Some(s.replace("creation-date -> ", ""))
-            //     } else if s.starts_with("gameID") {
-            //         res.game_id = Some(s.replace("gameID -> ", ""))
-            //     }
-            // }
-            res
+        /// Parse the output of `amphora get-secret`: the values in brackets followed by `key -> value` tag lines.
+        pub fn parse_secret(secret_id: &str, resp: &str, codec: &GfpCodec) -> Result<ClearTextSecret> {
+            let mut lines = resp.lines();
+            let values = lines
+                .next()
+                .unwrap_or_default()
+                .replace(['[', ']'], "")
+                .split(',')
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(parse_bigint)
+                .collect::<Result<Vec<BigInt>>>()?;
+            let tags = lines
+                .filter_map(|l| l.split_once("->"))
+                .map(|(key, value)| SecretTag {
+                    key: key.trim().to_string(),
+                    value: value.trim().to_string(),
+                    value_type: None,
+                })
+                .collect();
+            ClearTextSecret::from_field(secret_id, &values, codec, tags)
         }
     }
 
     impl JavaCsClient {
+        /// Client running cs.jar with the java binary of `CS_JAVA_BINARY`, `java` by default.
         pub fn new(config: CarbynestackConfig) -> Result<JavaCsClient> {
-            Ok(JavaCsClient { config })
+            Ok(JavaCsClient { config, java_binary: java_binary() })
+        }
+
+        /// Run cs.jar with the given java binary instead.
+        pub fn with_java_binary(mut self, java_binary: impl Into<String>) -> JavaCsClient {
+            self.java_binary = java_binary.into();
+            self
+        }
+
+        /// Write the config into a fresh home directory used by a single cs.jar call,
+        /// so concurrent calls with different configs never share a config file.
+        fn config_home(&self) -> Result<TempDir> {
+            let home = TempDir::new("cs-home")?;
+            self.config.save_config_json_in(home.path())?;
+            Ok(home)
         }
     }
 
@@ -85,7 +172,7 @@ mod java_cs_client {
     }
 
     impl CsCommand {
-        fn new() -> CsCommand {
+        fn new(java: &str, home: &Path) -> CsCommand {
             //let mut tmp_jar = NamedTempFile::new()?;
             //fs::write(&mut tmp_jar, CS_JAR)?;
             //let jar_path = tmp_jar.path().to_str().ok_or(Error::CommandError(
@@ -94,17 +181,21 @@ mod java_cs_client {
             //let jvm_args = InitArgsBuilder::new()
             //    .version(jni::JNIVersion::V8)
             //    .option("-Djava.class.path=".to_owned() + jar_path);
-            let mut command = Command::new("java");
-            command.arg("-jar").arg(jar_location());
+            let mut command = Command::new(java);
+            command
+                .env("HOME", home)
+                .arg(format!("-Duser.home={}", home.display()))
+                .arg("-jar")
+                .arg(jar_location());
             CsCommand { command }
         }
-        fn amphora() -> CsCommand {
-            let mut cscommand = CsCommand::new();
+        fn amphora(java: &str, home: &Path) -> CsCommand {
+            let mut cscommand = CsCommand::new(java, home);
             cscommand.command.arg("amphora");
             cscommand
         }
-        fn ephemeral() -> CsCommand {
-            let mut cscommand = CsCommand::new();
+        fn ephemeral(java: &str, home: &Path) -> CsCommand {
+            let mut cscommand = CsCommand::new(java, home);
             cscommand.command.arg("ephemeral");
             cscommand
         }
@@ -132,8 +223,8 @@ mod java_cs_client {
         }
 
         // returns result of stdout if successful
-        fn output(mut self) -> Result<String> {
-            match self.command.output() {
+        async fn output(mut self) -> Result<String> {
+            match self.command.output().await {
                 Ok(output) => {
                     let stderr = String::from_utf8(output.stderr)?;
                     let stdout = String::from_utf8(output.stdout)?;
@@ -166,71 +257,73 @@ mod java_cs_client {
             self.config.providers.iter().map(|p| p.base_url.clone()).collect()
         }
         /// Create a new secret
-        fn create_secrets(
+        async fn create_secrets(
             &self,
             secrets: Vec<String>,
             uuid: Option<String>,
         ) -> Result<Vec<String>> {
-            self.config.save_config_json()?;
+            let home = self.config_home()?;
             let output = if let Some(uuid) = uuid {
-                CsCommand::amphora()
+                CsCommand::amphora(&self.java_binary, home.path())
                     .arg("create-secret")
                     .arg("--secret-id")
                     .arg(uuid)
                     .args(secrets)
-                    .output()?
+                    .output().await?
             } else {
-                CsCommand::amphora()
+                CsCommand::amphora(&self.java_binary, home.path())
                     .arg("create-secret")
                     .args(secrets)
-                    .output()?
+                    .output().await?
             };
             let res: Vec<String> = vec![output.replace("\n", "")];
             Ok(res)
         }
 
         /// Delete secrets specified by secret_ids.
-        fn delete_secrets(&self, secret_ids: Vec<String>) -> Result<String> {
-            self.config.save_config_json()?;
-            let output = CsCommand::amphora()
+        async fn delete_secrets(&self, secret_ids: Vec<String>) -> Result<String> {
+            let home = self.config_home()?;
+            let output = CsCommand::amphora(&self.java_binary, home.path())
                 .arg("delete-secrets")
                 .args(secret_ids)
-                .output()?;
+                .output().await?;
             Ok(output)
         }
 
         /// Get secret by id
-        fn get_secret(&self, secret_id: &str) -> Result<ClearTextSecret> {
-            let output = CsCommand::amphora()
+        async fn get_secret(&self, secret_id: &str) -> Result<ClearTextSecret> {
+            let home = self.config_home()?;
+            let output = CsCommand::amphora(&self.java_binary, home.path())
                 .arg("get-secret")
                 .arg(secret_id)
-                .output()?;
-            Ok(SecretUtils::parse_secret(output))
+                .output().await?;
+            SecretUtils::parse_secret(secret_id, &output, &GfpCodec::from_config(&self.config)?)
         }
 
         /// List all secret ids
-        fn list_secrets(&self) -> Result<Vec<String>> {
-            let output = CsCommand::amphora().arg("get-secrets").arg("-l").output()?;
+        async fn list_secrets(&self) -> Result<Vec<String>> {
+            let home = self.config_home()?;
+            let output = CsCommand::amphora(&self.java_binary, home.path()).arg("get-secrets").arg("-l").output().await?;
             Ok(output
                 .lines()
                 .map(|s| String::from(s))
                 .collect::<Vec<String>>())
         }
 
-        fn execute_program(&self, spdz_program:String, secret_ids:Vec<String>) -> Result<String> {
+        async fn execute_program(&self, spdz_program:String, secret_ids:Vec<String>) -> Result<Vec<String>> {
             let program = BASE64_STANDARD.decode(spdz_program)?;
-            self.config.save_config_json()?;
-            let mut java_execute = CsCommand::ephemeral()
+            let home = self.config_home()?;
+            let mut java_execute = CsCommand::ephemeral(&self.java_binary, home.path())
                 .arg("execute")
                 .args(secret_ids.into_iter().map(|id| ("-i".to_string(), id)).flat_map(|tup| [tup.0, tup.1].clone()).collect::<Vec<String>>())
                 .arg("ephemeral-generic.default")
                 .std_piped()
                 .spawn()?;
             let mut stdin = java_execute.stdin.take().expect("Failed to open stdin");
-            std::thread::spawn(move || {
-                stdin.write_all(&program).expect("Failed to write to stdin");
+            tokio::spawn(async move {
+                stdin.write_all(&program).await.expect("Failed to write to stdin");
             });
-            let output = java_execute.wait_with_output()?;
+            let output = java_execute.wait_with_output().await?;
 
             if output.status.success() {
                 let stdout = String::from_utf8_lossy(&output.stdout)
@@ -240,7 +333,7 @@ mod java_cs_client {
                     .replace("]", "");
                 event!(Level::DEBUG, "Try parsing {}", &stdout);
                 event!(Level::INFO, "MPC Execution completed successfully.");
-                Ok(stdout)
+                Ok(stdout.split(',').map(|id| id.trim().to_string()).filter(|id| !id.is_empty()).collect())
             } else {
                 let message = format!("Error:\nstderr:\n{}\nstdout\n{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout));
                 event!(Level::ERROR, "MPC program execution failed: {}", &message);
@@ -256,10 +349,114 @@ mod java_cs_client {
         use super::*;
 
         #[test]
-        fn test_parse_result() {
-            let test_input = "[052deaa9-b4d7-440d-a44d-e0241cef21ff]\ngameID -> c3f5c561-2790-48fc-a490-63989272a2a\ncreation-date -> 2024/10/30";
-            let parsed = SecretUtils::parse_secret(test_input.to_string());
-            assert_eq!(parsed.result, "052deaa9-b4d7-440d-a44d-e0241cef21ff");
+        fn test_parse_result() -> Result<()> {
+            let test_input = "[22, 198766463529478683931867765928436695040]\ngameID -> c3f5c561-2790-48fc-a490-63989272a2a\ncreation-date -> 2024/10/30";
+            let codec = GfpCodec::from_config(&config("198766463529478683931867765928436695041"))?;
+            let parsed = SecretUtils::parse_secret("secret-1", test_input, &codec)?;
+            assert_eq!(parsed.secret_id, "secret-1");
+            assert_eq!(parsed.values, vec![22, -1]);
+            assert_eq!(parsed.game_id.as_deref(), Some("c3f5c561-2790-48fc-a490-63989272a2a"));
+            assert_eq!(parsed.creation_date.as_deref(), Some("2024/10/30"));
+            assert_eq!(parsed.tags.len(), 2);
+            Ok(())
+        }
+
+        fn config(prime: &str) -> CarbynestackConfig {
+            CarbynestackConfig {
+                prime: prime.to_string(),
+                r: "1".to_string(),
+                rinv: "1".to_string(),
+                no_ssl_validation: true,
+                providers: Vec::new(),
+            }
+        }
+
+        /// Runs two clients with different configs in parallel and checks that each call
+        /// only sees its own config.
+        #[tokio::test(flavor = "multi_thread")]
+        async fn test_concurrent_configs_are_isolated() -> Result<()> {
+            let bin = TempDir::new("fake-java")?;
+            let java = bin.path().join("java");
+            // stand-in for the JVM printing the prime of the config it sees after a while
+            std::fs::write(&java, "#!/bin/sh\nsleep 0.2\ngrep -o '\"prime\":\"[0-9]*\"' \"$HOME/.cs/config\"\n")?;
+            std::process::Command::new("chmod").arg("+x").arg(&java).status()?;
+            let java = java.to_string_lossy().to_string();
+
+            let left = JavaCsClient::new(config("7"))?.with_java_binary(&java);
+            let right = JavaCsClient::new(config("11"))?.with_java_binary(&java);
+            let (left, right) = tokio::join!(left.list_secrets(), right.list_secrets());
+            assert_eq!(left?, vec![r#""prime":"7""#]);
+            assert_eq!(right?, vec![r#""prime":"11""#]);
+            Ok(())
+        }
+    }
+}
+
+// export NativeCsClient
+pub use native_cs_client::NativeCsClient;
+mod native_cs_client {
+    use crate::{amphora::AmphoraClient, cs_config::CarbynestackConfig, ephemeral::EphemeralClient, error::Error, netaccess::RequestsClient};
+
+    use super::*;
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use tracing::{event, Level};
+
+    /// CsClient talking to the REST APIs of the providers directly, no JVM required.
+    pub struct NativeCsClient {
+        amphora: AmphoraClient<RequestsClient>,
+        ephemeral: EphemeralClient<RequestsClient>,
+        comp_party_urls: Vec<String>,
+    }
+
+    impl NativeCsClient {
+        pub fn new(config: CarbynestackConfig) -> Result<NativeCsClient> {
+            let comp_party_urls = config.providers.iter().map(|p| p.base_url.clone()).collect();
+            let no_ssl_validation = config.no_ssl_validation;
+            let net = || RequestsClient::new().with_ssl_validation_disabled(no_ssl_validation);
+            Ok(NativeCsClient {
+                amphora: AmphoraClient::new(config.clone(), net())?,
+                ephemeral: EphemeralClient::new(config, net()),
+                comp_party_urls,
+            })
+        }
+    }
+
+    impl CsClient for NativeCsClient {
+        fn get_comp_party_urls(&self) -> Vec<String> {
+            self.comp_party_urls.clone()
+        }
+
+        async fn create_secrets(
+            &self,
+            secrets: Vec<String>,
+            uuid: Option<String>,
+        ) -> Result<Vec<String>> {
+            self.amphora.create_secrets(secrets, uuid).await
+        }
+
+        async fn delete_secrets(&self, secret_ids: Vec<String>) -> Result<String> {
+            self.amphora.delete_secrets(secret_ids).await
+        }
+
+        async fn get_secret(&self, secret_id: &str) -> Result<ClearTextSecret> {
+            self.amphora.get_secret(secret_id).await
+        }
+
+        async fn list_secrets(&self) -> Result<Vec<String>> {
+            self.amphora.list_secrets().await
+        }
+
+        async fn execute_program(&self, spdz_program: String, secret_ids: Vec<String>) -> Result<Vec<String>> {
+            let program = String::from_utf8(BASE64_STANDARD.decode(spdz_program)?)?;
+            let execution = self.ephemeral.execute(program, secret_ids).await;
+            if execution.is_success() {
+                event!(Level::INFO, "MPC Execution {} completed successfully.", execution.game_id);
+                Ok(execution.result_ids())
+            } else {
+                let message = execution.errors().join("\n");
+                event!(Level::ERROR, "MPC program execution {} failed: {}", execution.game_id, &message);
+                Err(Error::ExecutionFailed(message))
+            }
         }
     }
 }
@@ -3,6 +3,7 @@ use serde::{Serialize, Deserialize};
 use tracing::{event, Level};
 use crate::error::{Error, Result};
 use std::env;
+use std::path::Path;
 use std::io::{BufWriter, Write};
 
 
@@ -10,7 +11,7 @@ use crate::netaccess::NetAccess;
 
 type BigNumber = String;
 
-#[derive(Object, Deserialize, Serialize, Debug)]
+#[derive(Object, Deserialize, Serialize, Debug, Clone)]
 #[oai(rename_all = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub struct CarbynestackConfig {
@@ -24,7 +25,7 @@ pub struct CarbynestackConfig {
     pub providers: Vec<CarbynestackProvider>
 }
 
-#[derive(Object, Deserialize, Serialize, Debug)]
+#[derive(Object, Deserialize, Serialize, Debug, Clone)]
 #[oai(rename_all = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub struct CarbynestackProvider {
@@ -40,7 +41,12 @@ impl CarbynestackConfig {
     /// Save CarbyneStack config as json at $HOME/.cs/config
     pub fn save_config_json(&self) -> Result<()> {
         let home = env::var("HOME")?;
-        let path = std::path::Path::new(&home).join(".cs");
+        self.save_config_json_in(Path::new(&home))
+    }
+
+    /// Save CarbyneStack config as json at `<home>/.cs/config`
+    pub fn save_config_json_in(&self, home: &Path) -> Result<()> {
+        let path = home.join(".cs");
         std::fs::create_dir_all(path.clone())?;
         event!(Level::DEBUG, "Saving cs-config to file {}", &path.display());
 
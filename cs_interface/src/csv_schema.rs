@@ -0,0 +1,216 @@
+use std::collections::HashSet;
+
+use num_bigint::BigInt;
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::FixedPointEncoding;
+use crate::error::{Error, Result};
+
+#[derive(Enum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[oai(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    /// Signed integer
+    Int,
+    /// Decimal, encoded with the fixed-point precision of the collaboration
+    FixedPoint,
+    /// `true`/`false` or `1`/`0`, encoded as 1 and 0
+    Boolean,
+    /// One of `values`, encoded as its index in the list
+    Categorical,
+}
+
+impl ColumnType {
+    /// Name of the type as used in the schema json.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColumnType::Int => "int",
+            ColumnType::FixedPoint => "fixed_point",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Categorical => "categorical",
+        }
+    }
+}
+
+/// A single column of the csv files input parties upload.
+#[derive(Object, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CsvColumn {
+    pub name: String,
+    #[oai(rename = "type")]
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    /// Smallest allowed value of int and fixed_point columns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// Largest allowed value of int and fixed_point columns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Empty cells are allowed and encoded as 0
+    #[oai(default)]
+    #[serde(default)]
+    pub nullable: bool,
+    /// Allowed values of categorical columns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<String>>,
+}
+
+/// Structure of the csv files input parties upload for a collaboration.
+#[derive(Object, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CsvSchema {
+    pub columns: Vec<CsvColumn>,
+}
+
+impl CsvSchema {
+    /// Parse and validate a schema.
+    pub fn from_json(json: &str) -> Result<CsvSchema> {
+        let schema: CsvSchema = serde_json::from_str(json)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Check that the schema itself is consistent.
+    pub fn validate(&self) -> Result<()> {
+        if self.columns.is_empty() {
+            return Err(Error::InvalidSchema("at least one column is required".to_string()));
+        }
+        let mut names = HashSet::new();
+        for column in &self.columns {
+            let name = column.name.trim();
+            if name.is_empty() || name.contains(',') {
+                return Err(Error::InvalidSchema(format!("invalid column name '{}'", column.name)));
+            }
+            if !names.insert(name) {
+                return Err(Error::InvalidSchema(format!("duplicate column '{}'", name)));
+            }
+            let numeric = matches!(column.column_type, ColumnType::Int | ColumnType::FixedPoint);
+            if !numeric && (column.min.is_some() || column.max.is_some()) {
+                return Err(Error::InvalidSchema(format!("column '{}' does not support bounds", name)));
+            }
+            if let (Some(min), Some(max)) = (column.min, column.max)
+                && min > max
+            {
+                return Err(Error::InvalidSchema(format!("column '{}' has min > max", name)));
+            }
+            match (&column.column_type, &column.values) {
+                (ColumnType::Categorical, Some(values)) if !values.is_empty() => {}
+                (ColumnType::Categorical, _) => {
+                    return Err(Error::InvalidSchema(format!("categorical column '{}' needs values", name)));
+                }
+                (_, Some(_)) => {
+                    return Err(Error::InvalidSchema(format!("only categorical columns have values, not '{}'", name)));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The expected header line of the csv files.
+    pub fn header_line(&self) -> String {
+        self.columns
+            .iter()
+            .map(|c| c.name.trim())
+            .collect::<Vec<&str>>()
+            .join(",")
+    }
+}
+
+impl CsvColumn {
+    /// Encode a single cell, checking type, bounds and nullability.
+    pub fn encode(&self, cell: &str, encoding: &FixedPointEncoding) -> Result<BigInt> {
+        let cell = cell.trim();
+        if cell.is_empty() {
+            return if self.nullable {
+                Ok(BigInt::from(0))
+            } else {
+                Err(Error::InvalidNumber("value is required".to_string()))
+            };
+        }
+        let invalid = || Error::InvalidNumber(format!("'{}' is not a valid {} value", cell, self.column_type.as_str()));
+        match self.column_type {
+            ColumnType::Int | ColumnType::FixedPoint => {
+                let encoding = match self.column_type {
+                    ColumnType::Int => FixedPointEncoding::integer(),
+                    _ => *encoding,
+                };
+                let value = encoding.encode(cell).map_err(|_| invalid())?;
+                let number = cell.parse::<f64>().map_err(|_| invalid())?;
+                if self.min.is_some_and(|min| number < min) || self.max.is_some_and(|max| number > max) {
+                    return Err(Error::InvalidNumber(format!(
+                        "{} is out of range [{}, {}]",
+                        cell,
+                        self.min.map(|m| m.to_string()).unwrap_or_default(),
+                        self.max.map(|m| m.to_string()).unwrap_or_default()
+                    )));
+                }
+                Ok(value)
+            }
+            ColumnType::Boolean => match cell.to_lowercase().as_str() {
+                "true" | "1" => Ok(BigInt::from(1)),
+                "false" | "0" => Ok(BigInt::from(0)),
+                _ => Err(invalid()),
+            },
+            ColumnType::Categorical => self
+                .values
+                .iter()
+                .flatten()
+                .position(|v| v == cell)
+                .map(BigInt::from)
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn schema() -> CsvSchema {
+        CsvSchema::from_json(
+            r#"{"columns":[
+                {"name":"age","type":"int","min":0,"max":120},
+                {"name":"income","type":"fixed_point","nullable":true},
+                {"name":"smoker","type":"boolean"},
+                {"name":"region","type":"categorical","values":["north","south"]}]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_header_line() {
+        assert_eq!(schema().header_line(), "age,income,smoker,region");
+    }
+
+    #[test]
+    fn test_encode() -> Result<()> {
+        let schema = schema();
+        let encoding = FixedPointEncoding::new(2)?;
+        let encode = |i: usize, cell: &str| schema.columns[i].encode(cell, &encoding);
+        assert_eq!(encode(0, "42")?, BigInt::from(42));
+        assert_eq!(encode(1, "1.5")?, BigInt::from(6));
+        assert_eq!(encode(1, "")?, BigInt::from(0));
+        assert_eq!(encode(2, "TRUE")?, BigInt::from(1));
+        assert_eq!(encode(3, "south")?, BigInt::from(1));
+        for (i, cell) in [(0, "121"), (0, "-1"), (0, "1.5"), (0, ""), (2, "yes"), (3, "east")] {
+            assert!(matches!(encode(i, cell), Err(Error::InvalidNumber(_))), "{}", cell);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_schemas() {
+        for json in [
+            r#"{"columns":[]}"#,
+            r#"{"columns":[{"name":"a","type":"int"},{"name":"a","type":"int"}]}"#,
+            r#"{"columns":[{"name":"a,b","type":"int"}]}"#,
+            r#"{"columns":[{"name":"a","type":"int","min":2,"max":1}]}"#,
+            r#"{"columns":[{"name":"a","type":"boolean","max":1}]}"#,
+            r#"{"columns":[{"name":"a","type":"categorical"}]}"#,
+            r#"{"columns":[{"name":"a","type":"int","values":["x"]}]}"#,
+        ] {
+            assert!(matches!(CsvSchema::from_json(json), Err(Error::InvalidSchema(_))), "{}", json);
+        }
+        assert!(matches!(CsvSchema::from_json(r#"{"columns":[{"name":"a","type":"text"}]}"#), Err(Error::SerdeJson(_))));
+    }
+}
@@ -0,0 +1,151 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::gfp::parse_bigint;
+use crate::netaccess::NetAccess;
+
+/// Largest supported fractional precision, decoded values must fit into an i64.
+pub const MAX_PRECISION: u32 = 32;
+
+/// Maps signed integers and fixed-point decimals to integers that can be stored in the field and back.
+///
+/// Like `sfix` in MP-SPDZ, a decimal `x` is represented by the integer `round(x * 2^precision)`.
+/// Negative values end up in the upper half of the field. A precision of 0 encodes plain integers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedPointEncoding {
+    precision: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct CollaborationEncoding {
+    #[serde(default)]
+    fixed_point_precision: u32,
+}
+
+impl FixedPointEncoding {
+    pub fn new(precision: u32) -> Result<FixedPointEncoding> {
+        if precision > MAX_PRECISION {
+            return Err(Error::InvalidNumber(format!(
+                "precision {} exceeds the maximum of {}",
+                precision, MAX_PRECISION
+            )));
+        }
+        Ok(FixedPointEncoding { precision })
+    }
+
+    /// Encoding of plain signed integers.
+    pub fn integer() -> FixedPointEncoding {
+        FixedPointEncoding { precision: 0 }
+    }
+
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    /// Get the encoding configured for a collaboration.
+    pub async fn get_from_coordinator(coord_url: &str, collaboration_id: i32, net: &impl NetAccess) -> Result<FixedPointEncoding> {
+        let url = format!("{}/collaboration/{}", coord_url, collaboration_id);
+        let bytes = net.get(&url).await?;
+        let collaboration = serde_json::from_slice::<CollaborationEncoding>(&bytes)?;
+        FixedPointEncoding::new(collaboration.fixed_point_precision)
+    }
+
+    /// Encode a decimal like `-12.375`, rounding half away from zero to the precision.
+    pub fn encode(&self, value: &str) -> Result<BigInt> {
+        let trimmed = value.trim();
+        let (negative, unsigned) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let digits = format!("{}{}", int_part, frac_part);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::InvalidNumber(value.to_string()));
+        }
+        let numerator = parse_bigint(&digits)? << self.precision;
+        let denominator = BigInt::from(10).pow(frac_part.len() as u32);
+        let (quotient, remainder) = numerator.div_rem(&denominator);
+        if self.precision == 0 && remainder != BigInt::from(0) {
+            return Err(Error::InvalidNumber(format!("{} is not an integer", value)));
+        }
+        let rounded = if remainder * 2 >= denominator { quotient + 1 } else { quotient };
+        Ok(if negative { -rounded } else { rounded })
+    }
+
+    /// Encode all non empty values, e.g. the lines of an uploaded csv.
+    pub fn encode_all(&self, values: &[String]) -> Result<Vec<String>> {
+        values
+            .iter()
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| self.encode(v).map(|e| e.to_string()))
+            .collect()
+    }
+
+    /// Decode a signed value as returned in a `ClearTextSecret`.
+    pub fn decode(&self, value: i64) -> f64 {
+        value as f64 / 2f64.powi(self.precision as i32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::netaccess::MockNetAccess;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_integer() -> Result<()> {
+        let encoding = FixedPointEncoding::integer();
+        assert_eq!(encoding.encode("42")?, BigInt::from(42));
+        assert_eq!(encoding.encode(" -7 ")?, BigInt::from(-7));
+        assert_eq!(encoding.encode("3.0")?, BigInt::from(3));
+        assert!(matches!(encoding.encode("3.5"), Err(Error::InvalidNumber(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_fixed_point() -> Result<()> {
+        let encoding = FixedPointEncoding::new(16)?;
+        assert_eq!(encoding.encode("1.5")?, BigInt::from(98304));
+        assert_eq!(encoding.encode("-0.25")?, BigInt::from(-16384));
+        assert_eq!(encoding.encode("2")?, BigInt::from(131072));
+        // 0.1 * 2^16 = 6553.6
+        assert_eq!(encoding.encode("0.1")?, BigInt::from(6554));
+        assert_eq!(encoding.encode("-0.1")?, BigInt::from(-6554));
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_invalid() -> Result<()> {
+        let encoding = FixedPointEncoding::new(8)?;
+        for value in ["", "-", "1.2.3", "abc", "1e5", "--1", "-.", "1.-5"] {
+            assert!(matches!(encoding.encode(value), Err(Error::InvalidNumber(_))), "{}", value);
+        }
+        assert!(FixedPointEncoding::new(MAX_PRECISION + 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let encoding = FixedPointEncoding::new(16)?;
+        for value in ["12.375", "-3.5", "0", "-1024.0078125"] {
+            let encoded = i64::try_from(encoding.encode(value)?).unwrap();
+            assert_eq!(encoding.decode(encoded), value.parse::<f64>().unwrap());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_from_coordinator() -> Result<()> {
+        let mut net = MockNetAccess::new();
+        net.expect_get()
+            .times(1)
+            .returning(|_| Ok(r#"{"id":1,"name":"test","fixed_point_precision":16}"#.as_bytes().to_vec()))
+            .withf(|url| url == "http://coordinator/collaboration/1");
+        let encoding = FixedPointEncoding::get_from_coordinator("http://coordinator", 1, &net).await?;
+        assert_eq!(encoding.precision(), 16);
+        Ok(())
+    }
+}
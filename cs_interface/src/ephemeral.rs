@@ -0,0 +1,219 @@
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+use uuid::Uuid;
+
+use crate::cs_config::CarbynestackConfig;
+use crate::error::Result;
+use crate::netaccess::NetAccess;
+
+/// Output type that stores the results of a computation as Amphora secrets.
+const OUTPUT_TYPE_AMPHORA_SECRET: &str = "AMPHORASECRET";
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct OutputOptions {
+    #[serde(rename = "type")]
+    output_type: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct StartComputationPayload {
+    /// Shared by all providers to link the computation processes of the virtual cloud.
+    game_id: String,
+    amphora_params: Vec<String>,
+    secret_params: Vec<String>,
+    output: OutputOptions,
+    code: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ComputationResponse {
+    response: Vec<String>,
+}
+
+/// Outcome of the computation on a single provider.
+#[derive(Debug)]
+pub struct ProviderExecution {
+    pub provider_id: i32,
+    /// Ids of the secrets holding the results, empty if the execution failed.
+    pub result_ids: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Outcome of a computation on all providers.
+#[derive(Debug)]
+pub struct EphemeralExecution {
+    pub game_id: String,
+    pub providers: Vec<ProviderExecution>,
+}
+
+impl EphemeralExecution {
+    /// True if the computation succeeded on every provider.
+    pub fn is_success(&self) -> bool {
+        self.providers.iter().all(|p| p.error.is_none())
+    }
+
+    /// Result ids returned by all providers, without duplicates.
+    pub fn result_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = Vec::new();
+        for id in self.providers.iter().flat_map(|p| p.result_ids.iter()) {
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
+        ids
+    }
+
+    /// Errors of all failed providers, prefixed with the provider id.
+    pub fn errors(&self) -> Vec<String> {
+        self.providers
+            .iter()
+            .filter_map(|p| p.error.as_ref().map(|e| format!("provider {}: {}", p.provider_id, e)))
+            .collect()
+    }
+}
+
+/// Rust native client of the Ephemeral services of all providers in a CarbyneStack config.
+pub struct EphemeralClient<N: NetAccess> {
+    config: CarbynestackConfig,
+    net: N,
+}
+
+impl<N: NetAccess> EphemeralClient<N> {
+    pub fn new(config: CarbynestackConfig, net: N) -> EphemeralClient<N> {
+        EphemeralClient { config, net }
+    }
+
+    /// Start the program on all providers at once using a common game id and wait for all of them.
+    pub async fn execute(&self, code: String, secret_ids: Vec<String>) -> EphemeralExecution {
+        let game_id = Uuid::new_v4().to_string();
+        event!(Level::DEBUG, "Starting computation with game id {}", game_id);
+        let executions = self.config.providers.iter().map(|provider| {
+            let payload = StartComputationPayload {
+                game_id: game_id.clone(),
+                amphora_params: secret_ids.clone(),
+                secret_params: Vec::new(),
+                output: OutputOptions {
+                    output_type: OUTPUT_TYPE_AMPHORA_SECRET.to_string(),
+                },
+                code: code.clone(),
+            };
+            let url = format!(
+                "{}?compile=true",
+                provider.ephemeral_service_url.trim_end_matches('/')
+            );
+            async move {
+                let result: Result<Vec<String>> = match serde_json::to_string(&payload) {
+                    Ok(body) => self.net.post(&url, body).await.and_then(|bytes| {
+                        Ok(serde_json::from_slice::<ComputationResponse>(&bytes)?.response)
+                    }),
+                    Err(err) => Err(err.into()),
+                };
+                match result {
+                    Ok(result_ids) => ProviderExecution {
+                        provider_id: provider.id,
+                        result_ids,
+                        error: None,
+                    },
+                    Err(err) => {
+                        event!(Level::ERROR, "Execution on provider {} failed: {}", provider.id, err);
+                        ProviderExecution {
+                            provider_id: provider.id,
+                            result_ids: Vec::new(),
+                            error: Some(err.to_string()),
+                        }
+                    }
+                }
+            }
+        });
+        let providers = join_all(executions).await;
+        EphemeralExecution { game_id, providers }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    use crate::error::Error;
+    use crate::netaccess::MockNetAccess;
+
+    use super::*;
+
+    fn config() -> CarbynestackConfig {
+        CarbynestackConfig::from_json(
+            r#"{
+            "noSslValidation":true,
+            "prime":"198766463529478683931867765928436695041",
+            "providers":[
+                {"amphoraServiceUrl":"http://csmock/0/amphora",
+                "baseUrl":"http://csmock/0/",
+                "castorServiceUrl":"http://csmock/0/castor",
+                "ephemeralServiceUrl":"http://csmock/0/",
+                "id":1},
+                {"amphoraServiceUrl":"http://csmock/1/amphora",
+                "baseUrl":"http://csmock/1/",
+                "castorServiceUrl":"http://csmock/1/castor",
+                "ephemeralServiceUrl":"http://csmock/1/",
+                "id":2}],
+            "r":"141515903391459779531506841503331516415",
+            "rinv":"133854242216446749056083838363708373830"}"#,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_execute() {
+        let mut net = MockNetAccess::new();
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let captured = bodies.clone();
+        net.expect_post()
+            .times(2)
+            .returning(move |_, body| {
+                captured.lock().unwrap().push(body);
+                Ok(r#"{"response":["00000000-0000-0000-0000-000000000000"]}"#.as_bytes().to_vec())
+            })
+            .withf(|url, _| url == "http://csmock/0?compile=true" || url == "http://csmock/1?compile=true");
+        let client = EphemeralClient::new(config(), net);
+        let execution = client.execute("program".to_string(), vec!["a".to_string(), "b".to_string()]).await;
+
+        assert!(execution.is_success());
+        assert_eq!(execution.result_ids(), vec!["00000000-0000-0000-0000-000000000000"]);
+        let bodies = bodies.lock().unwrap();
+        let game_ids = bodies
+            .iter()
+            .map(|b| serde_json::from_str::<serde_json::Value>(b).unwrap())
+            .map(|b| {
+                assert_eq!(b["amphoraParams"], serde_json::json!(["a", "b"]));
+                assert_eq!(b["output"]["type"], "AMPHORASECRET");
+                assert_eq!(b["code"], "program");
+                b["gameId"].as_str().unwrap().to_string()
+            })
+            .collect::<HashSet<String>>();
+        assert_eq!(game_ids, HashSet::from([execution.game_id.clone()]));
+    }
+
+    #[tokio::test]
+    async fn test_execute_provider_error() {
+        let mut net = MockNetAccess::new();
+        net.expect_post()
+            .times(2)
+            .returning(|url, _| {
+                if url.starts_with("http://csmock/1") {
+                    Err(Error::HttpError { code: 500, message: "compilation failed".to_string() })
+                } else {
+                    Ok(r#"{"response":["result"]}"#.as_bytes().to_vec())
+                }
+            });
+        let client = EphemeralClient::new(config(), net);
+        let execution = client.execute("program".to_string(), vec![]).await;
+
+        assert!(!execution.is_success());
+        assert_eq!(execution.result_ids(), vec!["result"]);
+        assert_eq!(execution.errors().len(), 1);
+        assert!(execution.errors()[0].starts_with("provider 2:"));
+    }
+}
@@ -27,4 +27,22 @@ pub enum Error {
 
     #[error("b64 decode error {0}")]
     B64DecodeError(#[from] base64::DecodeError),
+
+    #[error("invalid number {0}")]
+    InvalidNumber(String),
+
+    #[error("invalid share {0}")]
+    InvalidShare(String),
+
+    #[error("integrity check failed: {0}")]
+    IntegrityError(String),
+
+    #[error("MPC execution failed: {0}")]
+    ExecutionFailed(String),
+
+    #[error("invalid csv schema: {0}")]
+    InvalidSchema(String),
+
+    #[error("invalid signature: {0}")]
+    InvalidSignature(String),
 }
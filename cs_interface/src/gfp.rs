@@ -0,0 +1,270 @@
+use std::str::FromStr;
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+
+use crate::cs_config::CarbynestackConfig;
+use crate::error::{Error, Result};
+
+/// Number of bytes of a single field element as used by the MPC backend.
+pub const WORD_WIDTH: usize = 16;
+
+/// Converts values from and to the GFp wire format used by Amphora.
+///
+/// Values are transferred in Montgomery representation (multiplied by `r`),
+/// little endian and padded to [`WORD_WIDTH`] bytes. This is the same layout
+/// as the two swapped big endian 64 bit limbs of the Java SDK.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GfpCodec {
+    pub prime: BigInt,
+    pub r: BigInt,
+    pub rinv: BigInt,
+}
+
+impl GfpCodec {
+    /// Create a codec, the prime has to fit into [`WORD_WIDTH`] bytes and `rinv` has to be the inverse of `r`.
+    pub fn new(prime: BigInt, r: BigInt, rinv: BigInt) -> Result<GfpCodec> {
+        if prime <= BigInt::from(2) || prime.bits() > (WORD_WIDTH * 8) as u64 {
+            return Err(Error::InvalidNumber(format!(
+                "prime {} does not fit into {} bytes",
+                prime, WORD_WIDTH
+            )));
+        }
+        if (&r * &rinv).mod_floor(&prime) != BigInt::from(1) {
+            return Err(Error::InvalidNumber(format!(
+                "r * rinv is not 1 modulo {}",
+                prime
+            )));
+        }
+        Ok(GfpCodec { prime, r, rinv })
+    }
+
+    pub fn from_config(config: &CarbynestackConfig) -> Result<GfpCodec> {
+        GfpCodec::new(
+            parse_bigint(&config.prime)?,
+            parse_bigint(&config.r)?,
+            parse_bigint(&config.rinv)?,
+        )
+    }
+
+    /// Reduce value into the field.
+    pub fn reduce(&self, value: &BigInt) -> BigInt {
+        value.mod_floor(&self.prime)
+    }
+
+    /// Map a field element to a signed value, elements above prime / 2 are negative.
+    pub fn to_signed(&self, value: &BigInt) -> BigInt {
+        let value = self.reduce(value);
+        if &value * 2 > self.prime {
+            value - &self.prime
+        } else {
+            value
+        }
+    }
+
+    /// Encode a single value into its GFp representation.
+    pub fn encode_value(&self, value: &BigInt) -> Vec<u8> {
+        let (_, mut bytes) = (value * &self.r).mod_floor(&self.prime).to_bytes_le();
+        bytes.resize(WORD_WIDTH, 0);
+        bytes
+    }
+
+    /// Decode a single GFp encoded value.
+    pub fn decode_value(&self, gfp: &[u8]) -> Result<BigInt> {
+        if gfp.len() != WORD_WIDTH {
+            return Err(Error::InvalidShare(format!(
+                "expected {} bytes, got {}",
+                WORD_WIDTH,
+                gfp.len()
+            )));
+        }
+        let mont = BigInt::from_bytes_le(Sign::Plus, gfp);
+        Ok((mont * &self.rinv).mod_floor(&self.prime))
+    }
+
+    /// Encode a list of values as concatenated GFp values.
+    pub fn encode_bytes(&self, values: &[BigInt]) -> Vec<u8> {
+        values.iter().flat_map(|v| self.encode_value(v)).collect()
+    }
+
+    /// Decode concatenated GFp values.
+    pub fn decode_bytes(&self, bytes: &[u8]) -> Result<Vec<BigInt>> {
+        if !bytes.len().is_multiple_of(WORD_WIDTH) {
+            return Err(Error::InvalidShare(format!(
+                "length {} is not a multiple of {}",
+                bytes.len(),
+                WORD_WIDTH
+            )));
+        }
+        bytes.chunks(WORD_WIDTH).map(|c| self.decode_value(c)).collect()
+    }
+
+    /// Encode a list of values as base64 string of concatenated GFp values.
+    pub fn encode(&self, values: &[BigInt]) -> String {
+        BASE64_STANDARD.encode(self.encode_bytes(values))
+    }
+
+    /// Decode a base64 string of concatenated GFp values.
+    pub fn decode(&self, encoded: &str) -> Result<Vec<BigInt>> {
+        self.decode_bytes(&BASE64_STANDARD.decode(encoded)?)
+    }
+
+    /// Decode the shares of all providers and add them up element-wise.
+    pub fn combine(&self, shares: &[&str]) -> Result<Vec<BigInt>> {
+        let mut combined: Vec<BigInt> = Vec::new();
+        for (i, share) in shares.iter().enumerate() {
+            let values = self.decode(share)?;
+            if i == 0 {
+                combined = values;
+            } else if values.len() != combined.len() {
+                return Err(Error::InvalidShare(format!(
+                    "provider {} returned {} values, expected {}",
+                    i,
+                    values.len(),
+                    combined.len()
+                )));
+            } else {
+                combined = combined.iter().zip(values).map(|(l, r)| l + r).collect();
+            }
+        }
+        Ok(combined.iter().map(|v| self.reduce(v)).collect())
+    }
+}
+
+pub fn parse_bigint(value: &str) -> Result<BigInt> {
+    BigInt::from_str(value.trim()).map_err(|_| Error::InvalidNumber(value.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use num_bigint::RandBigInt;
+    use rand::Rng;
+
+    use super::*;
+
+    fn codec() -> GfpCodec {
+        GfpCodec::new(
+            parse_bigint("198766463529478683931867765928436695041").unwrap(),
+            parse_bigint("141515903391459779531506841503331516415").unwrap(),
+            parse_bigint("133854242216446749056083838363708373830").unwrap(),
+        )
+        .unwrap()
+    }
+
+    /// Codecs for primes of different sizes, with the Montgomery radix 2^128 like MP-SPDZ.
+    fn codecs() -> Vec<GfpCodec> {
+        let primes = [(BigInt::from(1) << 61u32) - 1, (BigInt::from(1) << 127u32) - 1, codec().prime];
+        primes
+            .into_iter()
+            .map(|prime| {
+                let r = (BigInt::from(1) << 128u32).mod_floor(&prime);
+                let rinv = r.modpow(&(&prime - 2), &prime);
+                GfpCodec::new(prime, r, rinv).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_new_invalid() {
+        let codec = codec();
+        assert!(GfpCodec::new(codec.prime.clone(), codec.r.clone(), codec.r.clone()).is_err());
+        assert!(GfpCodec::new(BigInt::from(1) << 130u32, BigInt::from(1), BigInt::from(1)).is_err());
+        assert!(GfpCodec::new(BigInt::from(2), BigInt::from(1), BigInt::from(1)).is_err());
+    }
+
+    #[test]
+    fn prop_round_trip() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        for codec in codecs() {
+            let bound = &codec.prime * 3;
+            for _ in 0..200 {
+                let value = rng.gen_bigint_range(&-&bound, &bound);
+                let encoded = codec.encode_value(&value);
+                assert_eq!(encoded.len(), WORD_WIDTH);
+                assert_eq!(codec.decode_value(&encoded)?, codec.reduce(&value), "{}", value);
+            }
+            let values = (0..50).map(|_| rng.gen_bigint_range(&BigInt::from(0), &codec.prime)).collect::<Vec<BigInt>>();
+            assert_eq!(codec.decode(&codec.encode(&values))?, values);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn prop_combine_additive_shares() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        for codec in codecs() {
+            for providers in 1..5 {
+                let secret = rng.gen_bigint_range(&BigInt::from(0), &codec.prime);
+                let mut shares = (1..providers)
+                    .map(|_| rng.gen_bigint_range(&BigInt::from(0), &codec.prime))
+                    .collect::<Vec<BigInt>>();
+                let rest = codec.reduce(&(&secret - shares.iter().sum::<BigInt>()));
+                shares.push(rest);
+                let encoded = shares.iter().map(|s| codec.encode(std::slice::from_ref(s))).collect::<Vec<String>>();
+                let combined = codec.combine(&encoded.iter().map(|s| s.as_str()).collect::<Vec<&str>>())?;
+                assert_eq!(combined, vec![secret]);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn prop_signed_values() {
+        let mut rng = rand::thread_rng();
+        for codec in codecs() {
+            let half = &codec.prime / 2;
+            for _ in 0..200 {
+                let value = rng.gen_bigint_range(&-&half, &half);
+                assert_eq!(codec.to_signed(&codec.reduce(&value)), value);
+            }
+        }
+    }
+
+    #[test]
+    fn prop_decode_arbitrary_bytes() {
+        let mut rng = rand::thread_rng();
+        let codec = codec();
+        for _ in 0..200 {
+            let bytes = (0..rng.gen_range(0..64)).map(|_| rng.r#gen::<u8>()).collect::<Vec<u8>>();
+            match codec.decode_bytes(&bytes) {
+                Ok(values) => assert!(values.iter().all(|v| v < &codec.prime)),
+                Err(err) => assert!(matches!(err, Error::InvalidShare(_))),
+            }
+        }
+        assert!(matches!(codec.decode("not base64!"), Err(Error::B64DecodeError(_))));
+    }
+
+    #[test]
+    fn test_gfp_round_trip() -> Result<()> {
+        let codec = codec();
+        let values = vec![BigInt::from(0), BigInt::from(22), BigInt::from(500000), &codec.prime - 1];
+        let decoded = codec.decode(&codec.encode(&values))?;
+        assert_eq!(decoded, values);
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_signed() {
+        let codec = codec();
+        assert_eq!(codec.to_signed(&BigInt::from(5)), BigInt::from(5));
+        assert_eq!(codec.to_signed(&(&codec.prime - 5)), BigInt::from(-5));
+        assert_eq!(codec.to_signed(&BigInt::from(-7)), BigInt::from(-7));
+    }
+
+    #[test]
+    fn test_decode_invalid_length() {
+        let codec = codec();
+        let res = codec.decode(&BASE64_STANDARD.encode([1u8; 15]));
+        assert!(matches!(res, Err(Error::InvalidShare(_))));
+    }
+
+    #[test]
+    fn test_combine() -> Result<()> {
+        let codec = codec();
+        let left = codec.encode(&[BigInt::from(5), &codec.prime - 3]);
+        let right = codec.encode(&[BigInt::from(7), BigInt::from(10)]);
+        assert_eq!(codec.combine(&[&left, &right])?, vec![BigInt::from(12), BigInt::from(7)]);
+        Ok(())
+    }
+}
@@ -2,6 +2,12 @@ mod cs_config;
 mod cs_client;
 mod netaccess;
 mod error;
+mod gfp;
+mod amphora;
+mod ephemeral;
+mod encoding;
+mod csv_schema;
+pub mod signature;
 // Add cs.jar as binary
 //const CS_JAR: &[u8] = include_bytes!("../dependencies/cs.jar");
 
@@ -9,4 +15,9 @@ mod error;
 pub use cs_config::*;
 pub use cs_client::*;
 pub use netaccess::*;
+pub use amphora::{reconstruct_secret, AmphoraClient};
+pub use ephemeral::*;
+pub use encoding::*;
+pub use csv_schema::*;
+pub use gfp::{parse_bigint, GfpCodec, WORD_WIDTH};
 pub use error::Error;
\ No newline at end of file
@@ -1,24 +1,31 @@
 use poem_openapi::types::ToJSON;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, Url};
 use crate::error::{Error, Result};
 use mockall::predicate::*;
 use mockall::*;
 
 /// Trait used for net access.
 /// To use the mocked version use MockNetAccess
+#[allow(async_fn_in_trait)]
 #[automock]
 pub trait NetAccess {
     /// Execute get request on url.
     async fn get(&self, url: &str) -> Result<Vec<u8>>;
     async fn post(&self, url: &str, body: String) -> Result<Vec<u8>>;
+    /// Execute delete request on url.
+    async fn delete(&self, url: &str) -> Result<Vec<u8>>;
 }
 
 /// Wrapper for net access using requests.
-pub struct RequestsClient {}
+pub struct RequestsClient {
+    /// Base url and bearer token sent along with all requests to urls below it
+    bearer: Option<(String, String)>,
+    /// Accept invalid TLS certificates, e.g. self-signed ones of a test deployment
+    no_ssl_validation: bool,
+}
 impl NetAccess for RequestsClient {
     async fn get(&self, url: &str) -> Result<Vec<u8>> {
-        let resp = Client::new()
-            .get(url)
+        let resp = self.authorize(self.client()?.get(url), url)
             .header("accepts", "application/json")
             .send().await?;
 
@@ -31,8 +38,7 @@ impl NetAccess for RequestsClient {
         Ok(result)
     }
     async fn post(&self,url: &str,body: String) -> Result<Vec<u8>> {
-        let res = Client::new()
-            .post(url)
+        let res = self.authorize(self.client()?.post(url), url)
             .body(body)
             .header("accepts", "application/json")
             .header("Content-Type", "application/json")
@@ -47,10 +53,79 @@ impl NetAccess for RequestsClient {
             Err(Error::HttpError { code: status_code, message: s })
         }
     }
+    async fn delete(&self, url: &str) -> Result<Vec<u8>> {
+        let res = self.authorize(self.client()?.delete(url), url)
+            .header("accepts", "application/json")
+            .send().await?;
+        let is_success = res.status().is_success();
+        let status_code = res.status().as_u16();
+        let body = res.bytes().await?.to_vec();
+        if is_success {
+            Ok(body)
+        } else {
+            let s = String::from_utf8_lossy(&body).to_string();
+            Err(Error::HttpError { code: status_code, message: s })
+        }
+    }
 }
 
 impl RequestsClient {
     pub fn new() -> RequestsClient {
-        RequestsClient{}
+        RequestsClient{ bearer: None, no_ssl_validation: false }
+    }
+
+    /// Client sending `Authorization: Bearer <token>` to all urls below `base_url`, e.g. the coordinator.
+    pub fn with_bearer_token(base_url: &str, token: &str) -> RequestsClient {
+        RequestsClient{ bearer: Some((base_url.to_string(), token.to_string())), no_ssl_validation: false }
+    }
+
+    /// Skip the validation of TLS certificates, as configured by `no_ssl_validation` of the Carbyne Stack config.
+    pub fn with_ssl_validation_disabled(mut self, no_ssl_validation: bool) -> RequestsClient {
+        self.no_ssl_validation = no_ssl_validation;
+        self
+    }
+
+    fn client(&self) -> Result<Client> {
+        Ok(Client::builder().danger_accept_invalid_certs(self.no_ssl_validation).build()?)
+    }
+
+    fn authorize(&self, request: RequestBuilder, url: &str) -> RequestBuilder {
+        match &self.bearer {
+            Some((base_url, token)) if is_below(base_url, url) => request.bearer_auth(token),
+            _ => request,
+        }
+    }
+}
+
+/// Whether `url` has the origin of `base_url` and a path at or below its path.
+fn is_below(base_url: &str, url: &str) -> bool {
+    let (Ok(base), Ok(url)) = (Url::parse(base_url), Url::parse(url)) else {
+        return false;
+    };
+    if base.origin() != url.origin() {
+        return false;
+    }
+    let base_path = base.path().trim_end_matches('/');
+    match url.path().strip_prefix(base_path) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_below() {
+        assert!(is_below("http://coord:8080", "http://coord:8080/collaboration/1"));
+        assert!(is_below("http://coord:8080/api/", "http://coord:8080/api/collaboration/1"));
+        assert!(is_below("http://coord:8080/api", "http://coord:8080/api"));
+        assert!(!is_below("http://coord:8080/api", "http://coord:8080/apiary"));
+        assert!(!is_below("http://coord:8080", "http://coord:8080.evil.com/collaboration/1"));
+        assert!(!is_below("http://coord:8080", "http://coord:8081/collaboration/1"));
+        assert!(!is_below("http://coord", "https://coord/collaboration/1"));
+        assert!(!is_below("http://coord", "http://coord@evil.com/collaboration/1"));
+        assert!(!is_below("not a url", "not a url/collaboration"));
     }
 }
\ No newline at end of file
@@ -0,0 +1,114 @@
+//! Signatures of result notifications the coordinator sends to output parties.
+//!
+//! The coordinator signs `{timestamp}.{body}` with HMAC-SHA256 and the secret the output party agreed on
+//! when it registered. The receiver checks the signature and rejects notifications older than `MAX_AGE_SECONDS`.
+
+use ring::{hmac, rand::{SecureRandom, SystemRandom}};
+
+use crate::error::{Error, Result};
+
+/// Header with the unix time in seconds the notification was signed at
+pub const TIMESTAMP_HEADER: &str = "X-Notification-Timestamp";
+/// Header with the signature, `sha256=` followed by the hex encoded HMAC
+pub const SIGNATURE_HEADER: &str = "X-Notification-Signature";
+/// Notifications with a timestamp further away from the current time are rejected
+pub const MAX_AGE_SECONDS: i64 = 300;
+
+const SIGNATURE_PREFIX: &str = "sha256=";
+
+/// Generate a random secret of 32 bytes, hex encoded.
+pub fn generate_secret() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| Error::InvalidSignature("unable to generate a secret".to_string()))?;
+    Ok(to_hex(&bytes))
+}
+
+/// Signature header value of `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, &signed_content(timestamp, body));
+    format!("{}{}", SIGNATURE_PREFIX, to_hex(tag.as_ref()))
+}
+
+/// Check the headers of a notification received at `now` (unix seconds).
+pub fn verify(secret: &str, timestamp: &str, signature: &str, body: &[u8], now: i64) -> Result<()> {
+    let timestamp = timestamp
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| Error::InvalidSignature(format!("invalid timestamp '{}'", timestamp)))?;
+    if (now - timestamp).abs() > MAX_AGE_SECONDS {
+        return Err(Error::InvalidSignature(format!("timestamp {} is outside of the accepted window", timestamp)));
+    }
+    let tag = signature
+        .trim()
+        .strip_prefix(SIGNATURE_PREFIX)
+        .and_then(from_hex)
+        .ok_or_else(|| Error::InvalidSignature("malformed signature".to_string()))?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, &signed_content(timestamp, body), &tag)
+        .map_err(|_| Error::InvalidSignature("signature does not match".to_string()))
+}
+
+fn signed_content(timestamp: i64, body: &[u8]) -> Vec<u8> {
+    let mut content = format!("{}.", timestamp).into_bytes();
+    content.extend_from_slice(body);
+    content
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BODY: &[u8] = br#"{"code":200,"collaborationId":1}"#;
+
+    #[test]
+    fn test_sign_and_verify() -> Result<()> {
+        let secret = generate_secret()?;
+        assert_eq!(secret.len(), 64);
+        let signature = sign(&secret, 1000, BODY);
+        verify(&secret, "1000", &signature, BODY, 1000 + MAX_AGE_SECONDS)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_known_signature() {
+        // echo -n '1700000000.{"code":200,"collaborationId":1}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", 1700000000, BODY),
+            "sha256=040145e79cab8f8dec2cd52adcaf0634f72c9efd65683934b4832db5260032cd"
+        );
+    }
+
+    #[test]
+    fn test_reject() {
+        let signature = sign("secret", 1000, BODY);
+        let rejected = [
+            verify("other", "1000", &signature, BODY, 1000),
+            verify("secret", "1000", &signature, br#"{"code":500}"#, 1000),
+            verify("secret", "1001", &signature, BODY, 1000),
+            verify("secret", "1000", &signature, BODY, 1001 + MAX_AGE_SECONDS),
+            verify("secret", "abc", &signature, BODY, 1000),
+            verify("secret", "1000", "sha256=zz", BODY, 1000),
+            verify("secret", "1000", &signature[7..], BODY, 1000),
+        ];
+        for result in rejected {
+            assert!(matches!(result, Err(Error::InvalidSignature(_))), "{:?}", result);
+        }
+    }
+}
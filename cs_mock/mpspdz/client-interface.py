@@ -1,19 +1,20 @@
 #!/usr/bin/python3
 
-import sys, random
+import os, sys, random
 sys.path.append('.')
 
 from client import *
 from domains import *
 
-base_port = 10000
+base_port = int(os.environ.get('BASE_PORT', 10000))
 client_id = 0
+hosts = os.environ.get('PARTY_HOSTS', 'party0,party1').split(',')
 
 
 data = list(map(lambda x: int(x), sys.argv[1:]))
 data_len = len(data)
-# For now hardcoded party0 and party1, matches docker hostnames.
-client = Client(['party0', 'party1'], base_port, client_id)
+# Defaults to party0 and party1, matches docker hostnames.
+client = Client(hosts, base_port, client_id)
 
 # Send Private Inputs
 # First send len of data to all clients
@@ -1,20 +1,25 @@
 #!/bin/sh
 
-program=$PROGRAM
 party=$PARTY
 host=${HOST:-localhost}
+parties=${PARTIES:-2}
 notification_port=734
 
 while true; do
   echo "Waiting for the next computation job"
-  nc -l -p $notification_port > /dev/null
-  ./compile.py $PROGRAM
+  # the mock sends the name of the program to run, plain pings run $PROGRAM
+  job=$(nc -l -p $notification_port | tr -d '[:space:]')
+  program=${job:-$PROGRAM}
+  log=Player-Data/$program-party$party.log
   # echo "Running offline phase"
   # ./mascot-party.x -mp 11000 -N 2 -h $host --offline-only $PARTY $PROGRAM
   # echo "Running online phase"
   if [ "$PROTOCOL" = "shamir" ]; then
-    ./shamir-party.x -mp 11000 -N 2 -h $host $PARTY $PROGRAM
+    binary=./shamir-party.x
   else
-    ./mascot-party.x -mp 11000 -N 2 -h $host $PARTY $PROGRAM
+    binary=./mascot-party.x
   fi
+  ./compile.py $program > $log 2>&1 && $binary -mp 11000 -N $parties -h $host $party $program >> $log 2>&1
+  echo $? > $log.done
+  cat $log
 done
@@ -1,11 +1,10 @@
 use once_cell::sync::Lazy;
-use std::{collections::HashMap, str::FromStr, sync::Mutex};
+use std::{collections::HashMap, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
 use tracing::{event, Level};
 
-use base64::{prelude::BASE64_STANDARD, Engine};
 use num_bigint::{BigInt, RandBigInt};
 use num_integer::Integer;
-use poem::Result;
+use poem::{http::StatusCode, Result};
 use poem_openapi::{
     param::{Path, Query},
     payload::Json,
@@ -13,9 +12,20 @@ use poem_openapi::{
 };
 use std::ops::{Add, Mul};
 
-use super::utils::{to_bigint_arr, to_gfp};
+use cs_interface::GfpCodec;
+
+use super::config::MockConfig;
+
+pub struct AmphoraApi {
+    config: MockConfig,
+}
+
+impl AmphoraApi {
+    pub fn new(config: MockConfig) -> AmphoraApi {
+        AmphoraApi { config }
+    }
+}
 
-pub struct AmphoraApi;
 #[OpenApi]
 impl AmphoraApi {
     /// Retrieve a set of party individual InputMask shares. The InputMaks can be used to secret share confidential data (compute a MaskedInput).
@@ -28,7 +38,8 @@ impl AmphoraApi {
     ) -> Result<GetInputMasksResponses> {
         event!(Level::INFO, "Request to get input-masks from vcp {}", vcp_nr.0);
         Ok(GetInputMasksResponses::OK(Json(generate_input_masks(
-            vcp_nr.0,
+            self.config.vcp(vcp_nr.0)?,
+            &self.config,
             request_id.0,
             count.0,
         ))))
@@ -46,7 +57,9 @@ impl AmphoraApi {
             "Adding masked input. Data lenght: {}",
             body.0.data.len()
         );
-        add_secrets(vcp_nr.0, &body.0.secret_id, &body.0.data);
+        add_secrets(self.config.vcp(vcp_nr.0)?, &self.config.codec, &body.0.secret_id, &body.0.data)
+            .map_err(|e| poem::Error::from_string(e.to_string(), StatusCode::BAD_REQUEST))?;
+        add_tags(&body.0.secret_id, body.0.tags.unwrap_or_default());
         Ok(PostMaskedInputResponse::OK(Json(body.0.secret_id)))
     }
 
@@ -58,10 +71,10 @@ impl AmphoraApi {
         #[oai(name = "requestId")] request_id: Query<String>,
     ) -> Result<GetSecretShareResponse> {
         let id = secret_id.0.clone();
-        let odo = get_secret_share(vcp_nr.0, &secret_id.0, &request_id.0);
+        let odo = get_secret_share(self.config.vcp(vcp_nr.0)?, &self.config.codec, &secret_id.0, &request_id.0);
         Ok(GetSecretShareResponse::OK(Json(SecretShareResponse {
+            tags: get_tags(&id),
             secret_id: id,
-            tags: Vec::new(),
             secret_shares: odo.secret_shares.clone(),
             v_shares: odo.v_shares,
             w_shares: odo.w_shares,
@@ -70,6 +83,34 @@ impl AmphoraApi {
             data: odo.secret_shares,
         })))
     }
+
+    /// Retrieve the metadata of all stored secrets.
+    #[oai(path = "/:vcp_nr/amphora/secret-shares", method = "get")]
+    async fn list_secret_shares(&self, vcp_nr: Path<i32>) -> Result<ListSecretSharesResponse> {
+        event!(Level::INFO, "Request to list secrets from vcp {}", vcp_nr.0);
+        self.config.vcp(vcp_nr.0)?;
+        let content = list_secrets()
+            .into_iter()
+            .map(|secret_id| Metadata {
+                tags: get_tags(&secret_id),
+                secret_id,
+            })
+            .collect();
+        Ok(ListSecretSharesResponse::OK(Json(MetadataPage { content })))
+    }
+
+    /// Delete the secret with the given id.
+    #[oai(path = "/:vcp_nr/amphora/secret-shares/:secretId", method = "delete")]
+    async fn delete_secret_share(
+        &self,
+        vcp_nr: Path<i32>,
+        #[oai(name = "secretId")] secret_id: Path<String>,
+    ) -> Result<DeleteSecretShareResponse> {
+        event!(Level::INFO, "Request to delete secret {} from vcp {}", secret_id.0, vcp_nr.0);
+        self.config.vcp(vcp_nr.0)?;
+        delete_secret(&secret_id.0);
+        Ok(DeleteSecretShareResponse::OK)
+    }
 }
 
 #[derive(ApiResponse)]
@@ -90,6 +131,18 @@ enum GetSecretShareResponse {
     OK(Json<SecretShareResponse>),
 }
 
+#[derive(ApiResponse)]
+enum ListSecretSharesResponse {
+    #[oai(status = 200)]
+    OK(Json<MetadataPage>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteSecretShareResponse {
+    #[oai(status = 200)]
+    OK,
+}
+
 #[derive(Object)]
 #[oai(rename_all = "camelCase")]
 pub struct OutputDeliveryObject {
@@ -114,7 +167,7 @@ struct UploadMaskedInputObject {
     tags: Option<Vec<Tag>>,
 }
 
-#[derive(Object)]
+#[derive(Object, Clone)]
 #[oai(rename_all = "camelCase")]
 struct Tag {
     key: String,
@@ -135,76 +188,49 @@ struct SecretShareResponse {
     data: String,
 }
 
+#[derive(Object)]
+#[oai(rename_all = "camelCase")]
+struct Metadata {
+    secret_id: String,
+    tags: Vec<Tag>,
+}
+
+#[derive(Object)]
+#[oai(rename_all = "camelCase")]
+struct MetadataPage {
+    content: Vec<Metadata>,
+}
+
+/// Additive shares of an input mask for all VCPs, index i holds the share of VCP i.
+///
+/// Like the MAC check of Amphora, w = s * r and u = v * r hold for the combined values,
+/// i.e. each VCP gets w_i = s_i * r and u_i = v_i * r.
 struct InputMasksObject {
-    secret: (BigInt, BigInt),
-    r: (BigInt, BigInt),
-    v: (BigInt, BigInt),
-    u: (BigInt, BigInt),
-    w: (BigInt, BigInt),
+    secret: Vec<BigInt>,
+    r: Vec<BigInt>,
+    v: Vec<BigInt>,
+    u: Vec<BigInt>,
+    w: Vec<BigInt>,
+}
+
+fn random_shares(vcps: usize, prime: &BigInt) -> Vec<BigInt> {
+    let mut rng = rand::thread_rng();
+    (0..vcps).map(|_| rng.gen_bigint(256).mod_floor(prime)).collect()
 }
-pub static P: Lazy<BigInt> =
-    Lazy::new(|| BigInt::from_str("198766463529478683931867765928436695041").unwrap());
-pub static R: Lazy<BigInt> =
-    Lazy::new(|| BigInt::from_str("141515903391459779531506841503331516415").unwrap());
-pub static R_INV: Lazy<BigInt> =
-    Lazy::new(|| BigInt::from_str("133854242216446749056083838363708373830").unwrap());
 
 impl InputMasksObject {
-    fn generate() -> InputMasksObject {
-        let mut rng = rand::thread_rng();
-        // Generate a random pair
-        let s = (
-            rng.gen_bigint(256).mod_floor(&P),
-            rng.gen_bigint(256).mod_floor(&P),
-            //BigInt::from_str("12345").unwrap(),
-            //BigInt::from_str("67890").unwrap(),
-        );
-        InputMasksObject::generate_for(s)
+    fn generate(vcps: usize, prime: &BigInt) -> InputMasksObject {
+        InputMasksObject::generate_for(random_shares(vcps, prime), prime)
     }
 
-    fn generate_for(s: (BigInt, BigInt)) -> InputMasksObject {
-        let mut rng = rand::thread_rng();
-        // Generate r-values
-        let r = (
-            rng.gen_bigint(256).mod_floor(&P),
-            rng.gen_bigint(256).mod_floor(&P),
-            //BigInt::from_str("23451").unwrap(),
-            //BigInt::from_str("78906").unwrap(),
-        );
-        // Generate v-values
-        let v = (
-            rng.gen_bigint(256).mod_floor(&P),
-            rng.gen_bigint(256).mod_floor(&P),
-            //BigInt::from_str("34512").unwrap(),
-            //BigInt::from_str("89067").unwrap(),
-        );
-        let w = (
-            s.0.clone()
-                .mul(&r.0)
-                .add(s.0.clone().mul(&r.1))
-                .mod_floor(&P), //w_0 = s_0 * r_0 + s_0 * r_1
-            s.1.clone()
-                .mul(&r.1)
-                .add(s.1.clone().mul(&r.0))
-                .mod_floor(&P), //w_1 = s_1 * r_1 + s_1 * r_0
-        );
-        let u = (
-            v.0.clone()
-                .mul(&r.0)
-                .add(v.0.clone().mul(&r.1))
-                .mod_floor(&P), // u_0 = v_0 * r_0 + v_0 * r_1
-            v.1.clone()
-                .mul(&r.1)
-                .add(v.1.clone().mul(&r.0))
-                .mod_floor(&P), // u_1 = v_1 * r_1 + v_1 * r_0
-        );
-        InputMasksObject {
-            secret: s,
-            r,
-            v,
-            u,
-            w,
-        }
+    /// Masks for the given secret shares, one per VCP.
+    fn generate_for(secret: Vec<BigInt>, prime: &BigInt) -> InputMasksObject {
+        let r = random_shares(secret.len(), prime);
+        let v = random_shares(secret.len(), prime);
+        let r_sum: BigInt = r.iter().sum();
+        let w = secret.iter().map(|s| s.mul(&r_sum).mod_floor(prime)).collect();
+        let u = v.iter().map(|v| v.mul(&r_sum).mod_floor(prime)).collect();
+        InputMasksObject { secret, r, v, u, w }
     }
 }
 
@@ -214,78 +240,79 @@ static GLOBAL_RANDOMNESS: Lazy<Mutex<HashMap<String, Vec<InputMasksObject>>>> =
     Mutex::new(m)
 });
 
-static GLOBAL_SECRETS: Lazy<Mutex<HashMap<String, Vec<(BigInt, BigInt)>>>> = Lazy::new(|| {
+/// Shares of all values of a secret, one entry per VCP for each value.
+static GLOBAL_SECRETS: Lazy<Mutex<HashMap<String, Vec<Vec<BigInt>>>>> = Lazy::new(|| {
     let m = HashMap::new();
     Mutex::new(m)
 });
 
-fn generate_input_masks(vcp: i32, id: String, count: i32) -> OutputDeliveryObject {
-    // Try to get previously generated randomness
-    let mut random_db = GLOBAL_RANDOMNESS.lock().unwrap();
-    if !random_db.contains_key(&id) {
-        let mut new_input_mask: Vec<InputMasksObject> = Vec::new();
-        for _ in 0..count {
-            new_input_mask.push(InputMasksObject::generate());
-        }
-        random_db.insert(id.clone(), new_input_mask);
-    }
-
-    // Get input-masks from random_db.
-    let input_masks = random_db.get(&id).unwrap();
-    let bin_output_delivery;
-    // crate tuple of (s_share, r_share, v_share, u_share, w_share) for VCP0 or VCP1
-    if vcp > 0 {
-        bin_output_delivery = input_masks.iter().fold(
-            (
-                Vec::<u8>::new(),
-                Vec::<u8>::new(),
-                Vec::<u8>::new(),
-                Vec::<u8>::new(),
-                Vec::<u8>::new(),
-            ),
-            |mut v, m| {
-                v.0.append(&mut to_gfp(&m.secret.1, &R, &P));
-                v.1.append(&mut to_gfp(&m.r.1, &R, &P));
-                v.2.append(&mut to_gfp(&m.v.1, &R, &P));
-                v.3.append(&mut to_gfp(&m.u.1, &R, &P));
-                v.4.append(&mut to_gfp(&m.w.1, &R, &P));
-                v
-            },
-        );
-    } else {
-        bin_output_delivery = input_masks.iter().fold(
-            (
-                Vec::<u8>::new(),
-                Vec::<u8>::new(),
-                Vec::<u8>::new(),
-                Vec::<u8>::new(),
-                Vec::<u8>::new(),
-            ),
-            |mut v, m| {
-                v.0.append(&mut to_gfp(&m.secret.0, &R, &P));
-                v.1.append(&mut to_gfp(&m.r.0, &R, &P));
-                v.2.append(&mut to_gfp(&m.v.0, &R, &P));
-                v.3.append(&mut to_gfp(&m.u.0, &R, &P));
-                v.4.append(&mut to_gfp(&m.w.0, &R, &P));
-                v
-            },
-        );
-    }
+/// Output delivery object with the shares of the VCP, `secrets` holds the share of the VCP for each value.
+fn output_delivery(vcp: usize, codec: &GfpCodec, secrets: &[BigInt], masks: &[InputMasksObject]) -> OutputDeliveryObject {
+    let encode = |f: fn(&InputMasksObject) -> &Vec<BigInt>| {
+        codec.encode(&masks.iter().map(|m| f(m)[vcp].clone()).collect::<Vec<BigInt>>())
+    };
     OutputDeliveryObject {
-        secret_shares: BASE64_STANDARD.encode(bin_output_delivery.0),
-        r_shares: BASE64_STANDARD.encode(bin_output_delivery.1),
-        v_shares: BASE64_STANDARD.encode(bin_output_delivery.2),
-        u_shares: BASE64_STANDARD.encode(bin_output_delivery.3),
-        w_shares: BASE64_STANDARD.encode(bin_output_delivery.4),
+        secret_shares: codec.encode(secrets),
+        r_shares: encode(|m| &m.r),
+        v_shares: encode(|m| &m.v),
+        u_shares: encode(|m| &m.u),
+        w_shares: encode(|m| &m.w),
     }
 }
 
+fn generate_input_masks(vcp: usize, config: &MockConfig, id: String, count: i32) -> OutputDeliveryObject {
+    // Try to get previously generated randomness
+    let mut random_db = GLOBAL_RANDOMNESS.lock().unwrap();
+    let input_masks = random_db
+        .entry(id)
+        .or_insert_with(|| (0..count).map(|_| InputMasksObject::generate(config.vcps, &config.codec.prime)).collect());
+    let secrets = input_masks.iter().map(|m| m.secret[vcp].clone()).collect::<Vec<BigInt>>();
+    output_delivery(vcp, &config.codec, &secrets, input_masks)
+}
+
+static GLOBAL_TAGS: Lazy<Mutex<HashMap<String, Vec<Tag>>>> = Lazy::new(|| {
+    let m = HashMap::new();
+    Mutex::new(m)
+});
+
 pub fn delete_secret(secret_id: &String) {
     let mut secrets = GLOBAL_SECRETS.lock().unwrap();
     secrets.remove(secret_id);
+    GLOBAL_TAGS.lock().unwrap().remove(secret_id);
+}
+
+/// Store the tags of a secret. Like Amphora, a creation-date tag is added if missing.
+fn add_tags(secret_id: &str, mut tags: Vec<Tag>) {
+    if !tags.iter().any(|t| t.key == "creation-date") {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        tags.push(Tag {
+            key: "creation-date".to_string(),
+            value: now.to_string(),
+            value_type: Some("LONG".to_string()),
+        });
+    }
+    GLOBAL_TAGS.lock().unwrap().insert(secret_id.to_string(), tags);
+}
+
+fn get_tags(secret_id: &str) -> Vec<Tag> {
+    GLOBAL_TAGS
+        .lock()
+        .unwrap()
+        .get(secret_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub fn list_secrets() -> Vec<String> {
+    let secrets = GLOBAL_SECRETS.lock().unwrap();
+    secrets.keys().cloned().collect()
 }
 
-pub fn get_secrets_internal(secret_id: &String) -> Vec<(BigInt, BigInt)> {
+/// Shares of all VCPs for each value of a secret.
+pub fn get_secrets_internal(secret_id: &String) -> Vec<Vec<BigInt>> {
     let secrets = GLOBAL_SECRETS.lock().unwrap();
     if let Some(s) = secrets.get(secret_id) {
         event!(Level::INFO, "Found secrets for id {}", secret_id);
@@ -296,160 +323,98 @@ pub fn get_secrets_internal(secret_id: &String) -> Vec<(BigInt, BigInt)> {
     }
 }
 
-pub fn get_secret_share(vcp: i32, secret_id: &String, request_id: &String) -> OutputDeliveryObject {
+pub fn get_secret_share(vcp: usize, codec: &GfpCodec, secret_id: &String, request_id: &String) -> OutputDeliveryObject {
     let secrets = GLOBAL_SECRETS.lock().unwrap();
     if let Some(secret) = secrets.get(secret_id) {
-        event!(
-            Level::INFO,
-            "get shares vcp {}, s1: {}, s2: {}",
-            vcp,
-            secret[0].0,
-            secret[0].1
-        );
+        event!(Level::INFO, "get shares vcp {} of secret {}", vcp, secret_id);
         let mut random_db = GLOBAL_RANDOMNESS.lock().unwrap();
-        if !random_db.contains_key(request_id) {
-            let mut new_input_mask: Vec<InputMasksObject> = Vec::new();
-            for s in secret {
-                new_input_mask.push(InputMasksObject::generate_for(s.clone()));
-            }
-            random_db.insert(request_id.clone(), new_input_mask);
-        }
-        let input_masks: &Vec<InputMasksObject> = random_db.get(request_id).unwrap();
-
-        // Create output delivery object for VCP0 or VCP1
-        let bin_output_delivery = secret
-            .iter()
-            .map(|v| if vcp > 0 { &v.1 } else { &v.0 })
-            .zip(input_masks)
-            .fold(
-                (
-                    Vec::<u8>::new(),
-                    Vec::<u8>::new(),
-                    Vec::<u8>::new(),
-                    Vec::<u8>::new(),
-                    Vec::<u8>::new(),
-                ),
-                |mut v, m| {
-                    v.0.append(&mut to_gfp(&m.0, &R, &P));
-                    if vcp > 0 {
-                        v.1.append(&mut to_gfp(&m.1.r.1, &R, &P));
-                        v.2.append(&mut to_gfp(&m.1.v.1, &R, &P));
-                        v.3.append(&mut to_gfp(&m.1.u.1, &R, &P));
-                        v.4.append(&mut to_gfp(&m.1.w.1, &R, &P));
-                    } else {
-                        v.1.append(&mut to_gfp(&m.1.r.0, &R, &P));
-                        v.2.append(&mut to_gfp(&m.1.v.0, &R, &P));
-                        v.3.append(&mut to_gfp(&m.1.u.0, &R, &P));
-                        v.4.append(&mut to_gfp(&m.1.w.0, &R, &P));
-                    }
-                    v
-                },
-            );
-        return OutputDeliveryObject {
-            secret_shares: BASE64_STANDARD.encode(bin_output_delivery.0),
-            r_shares: BASE64_STANDARD.encode(bin_output_delivery.1),
-            v_shares: BASE64_STANDARD.encode(bin_output_delivery.2),
-            u_shares: BASE64_STANDARD.encode(bin_output_delivery.3),
-            w_shares: BASE64_STANDARD.encode(bin_output_delivery.4),
-        };
+        let input_masks = random_db
+            .entry(request_id.clone())
+            .or_insert_with(|| secret.iter().map(|s| InputMasksObject::generate_for(s.clone(), &codec.prime)).collect());
+        let shares = secret.iter().map(|s| s[vcp].clone()).collect::<Vec<BigInt>>();
+        output_delivery(vcp, codec, &shares, input_masks)
     } else {
-        return OutputDeliveryObject {
+        OutputDeliveryObject {
             secret_shares: "".to_string(),
             r_shares: "".to_string(),
             v_shares: "".to_string(),
             w_shares: "".to_string(),
             u_shares: "".to_string(),
-        };
+        }
     }
 }
 
-fn add_secrets(vcp: i32, secret_id: &String, data: &Vec<DataObject>) {
+/// Add the masked input of a VCP, fails if no input masks were requested for the secret id or the data is malformed.
+///
+/// The share of a VCP is its input mask share, VCP 0 adds the masked value on top.
+fn add_secrets(vcp: usize, codec: &GfpCodec, secret_id: &String, data: &Vec<DataObject>) -> std::result::Result<(), cs_interface::Error> {
     event!(Level::INFO, "Add secrets from party {} with data len {} and id {}", vcp, data.len(), secret_id);
     let random_db = GLOBAL_RANDOMNESS.lock().unwrap();
-    let input_masks = random_db.get(secret_id).expect("Secret ID not found");
-    // let decoded_data = BASE64_STANDARD.decode(&data).expect("Unable to decode base64 encoded data");
-    // let decoded_data = to_bigint_arr(&decoded_data, &R_INV, &P);
-    let decoded_data = data
-        .iter()
-        .map(|d| {
-            BASE64_STANDARD
-                .decode(&d.value)
-                .expect("Unable to decode base64 encoded data")
-        })
-        .map(|decoded| to_bigint_arr(&decoded, &R_INV, &P))
-        .flatten()
-        .collect::<Vec<BigInt>>();
-    assert!(input_masks.len() == decoded_data.len());
+    let input_masks = random_db
+        .get(secret_id)
+        .ok_or_else(|| cs_interface::Error::InvalidShare(format!("no input masks for secret {}", secret_id)))?;
+    let mut decoded_data = Vec::new();
+    for d in data {
+        decoded_data.append(&mut codec.decode(&d.value)?);
+    }
+    if input_masks.len() != decoded_data.len() {
+        return Err(cs_interface::Error::InvalidShare(format!(
+            "got {} values for {} input masks",
+            decoded_data.len(),
+            input_masks.len()
+        )));
+    }
 
-    // Calculate shares by adding the decoded data to the input masks of the VCP mod P
     let shares: Vec<BigInt> = decoded_data
         .iter()
         .zip(input_masks)
-        .map(|z| {
-            //z.0.add(
-            //    match vcp {
-            //        0 => z.1.secret.0.clone(),
-            //        _ => z.1.secret.1.clone()
-            //    }
-            //).mod_floor(&P)
-            let secret_to_add: BigInt = if vcp > 0 {
-                z.1.secret.1.clone()
-            } else {
-                z.1.secret.0.clone()
-            };
-            //event!(
-            //    Level::INFO,
-            //    "vcp {} Adding {} to {}",
-            //    vcp,
-            //    z.0,
-            //    secret_to_add
-            //);
-            let mut val =
-                z.0.add(secret_to_add.mul(BigInt::from_str("2").unwrap()))
-                    .div_floor(&BigInt::from_str("2").unwrap());
-            if vcp > 0 {
-                val = val.add(z.0.mod_floor(&BigInt::from_str("2").unwrap()));
-            }
-            val.mod_floor(&P)
+        .map(|(masked, mask)| {
+            let share = &mask.secret[vcp];
+            if vcp == 0 { masked.add(share).mod_floor(&codec.prime) } else { share.clone() }
         })
         .collect();
-    event!(Level::INFO, "secret: {}", shares[0]);
     // Save share to GLOBAL_SECRETS
+    let vcps = input_masks[0].secret.len();
     let mut secrets = GLOBAL_SECRETS.lock().unwrap();
-    if secrets.contains_key(secret_id) {
-        secrets
-            .get_mut(secret_id)
-            .unwrap()
-            .iter_mut()
-            .zip(shares)
-            .for_each(|(s, v)| {
-                if vcp > 0 {
-                    s.1 = v;
-                } else {
-                    s.0 = v;
-                }
-            });
-    } else {
-        secrets.insert(
-            secret_id.clone(),
+    let stored = secrets
+        .entry(secret_id.clone())
+        .or_insert_with(|| shares.iter().map(|_| vec![BigInt::from(0); vcps]).collect());
+    stored.iter_mut().zip(shares).for_each(|(s, v)| s[vcp] = v);
+    Ok(())
+}
+
+/// Store computation results as random additive shares of all VCPs, tagged with the game id of the computation.
+pub fn store_secret(secret_id: &str, values: &[BigInt], game_id: &str, config: &MockConfig) {
+    let prime = &config.codec.prime;
+    let shares = values
+        .iter()
+        .map(|value| {
+            let mut shares = random_shares(config.vcps, prime);
+            let others: BigInt = shares[1..].iter().sum();
+            shares[0] = (value - others).mod_floor(prime);
             shares
-                .iter()
-                .map(|s| {
-                    if vcp > 0 {
-                        (BigInt::from_str("0").unwrap(), s.clone())
-                    } else {
-                        (s.clone(), BigInt::from_str("0").unwrap())
-                    }
-                })
-                .collect(),
-        );
-    }
+        })
+        .collect();
+    GLOBAL_SECRETS.lock().unwrap().insert(secret_id.to_string(), shares);
+    add_tags(
+        secret_id,
+        vec![Tag {
+            key: cs_interface::TAG_GAME_ID.to_string(),
+            value: game_id.to_string(),
+            value_type: Some("STRING".to_string()),
+        }],
+    );
 }
 
 #[cfg(test)]
 mod test {
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use std::str::FromStr;
+
     use super::*;
-    use crate::api::utils::zipp_shares;
+
+    static CODEC: Lazy<GfpCodec> = Lazy::new(|| MockConfig::default().codec);
+    use crate::api::test_utils::{native_client_config, start_api};
 
     use std::ops::Sub;
 
@@ -460,22 +425,14 @@ mod test {
             value: "jhXaWICsBMZadvGKldslPg==".to_string(),
         };
         let share = vec![share];
-        add_secrets(0, &uuid, &share);
-        add_secrets(1, &uuid, &share);
-        let odo_0 = get_secret_share(0, &uuid, &uuid);
-        let odo_1 = get_secret_share(0, &uuid, &uuid);
-        let s_0 = to_bigint_arr(
-            &BASE64_STANDARD.decode(odo_0.secret_shares).unwrap(),
-            &R_INV,
-            &P,
-        );
-        let s_1 = to_bigint_arr(
-            &BASE64_STANDARD.decode(odo_1.secret_shares).unwrap(),
-            &R_INV,
-            &P,
-        );
+        add_secrets(0, &CODEC, &uuid, &share).unwrap();
+        add_secrets(1, &CODEC, &uuid, &share).unwrap();
+        let odo_0 = get_secret_share(0, &CODEC, &uuid, &uuid);
+        let odo_1 = get_secret_share(0, &CODEC, &uuid, &uuid);
+        let s_0 = CODEC.decode(&odo_0.secret_shares).unwrap();
+        let s_1 = CODEC.decode(&odo_1.secret_shares).unwrap();
         s_0.iter().zip(s_1).for_each(|(l, r)| {
-            let res = l.add(r).mod_floor(&P);
+            let res = l.add(r).mod_floor(&CODEC.prime);
             assert_eq!(res.to_string(), "450".to_string());
         });
     }
@@ -485,38 +442,30 @@ mod test {
         let uuid = "b3bde039-d497-4b71-9956-db12f2dddbee".to_string();
         let secret = BigInt::from_str("500000").unwrap();
         // generate input masks from both providers
-        let m_0 = generate_input_masks(0, uuid.clone(), 1);
-        let m_1 = generate_input_masks(1, uuid.clone(), 1);
+        let m_0 = generate_input_masks(0, &MockConfig::default(), uuid.clone(), 1);
+        let m_1 = generate_input_masks(1, &MockConfig::default(), uuid.clone(), 1);
 
         // encode secret with the help of input masks
-        let zipped = zipp_shares(&vec![m_0, m_1], &R_INV, &P);
+        let secret_masks = CODEC.combine(&[&m_0.secret_shares, &m_1.secret_shares]).unwrap();
         // share = secret - (r1 + r2) where r1, r2 are from the OutputDeliveryObjects
-        let secret_shared = secret.clone().sub(zipped.secrets[0].clone()).mod_floor(&P);
-        let encoded_share = BASE64_STANDARD.encode(to_gfp(&secret_shared, &R, &P));
+        let secret_shared = secret.clone().sub(secret_masks[0].clone()).mod_floor(&CODEC.prime);
+        let encoded_share = BASE64_STANDARD.encode(CODEC.encode_value(&secret_shared));
         let share_list = vec![DataObject {
             value: encoded_share,
         }];
         // Add secrets
-        add_secrets(0, &uuid, &share_list);
-        add_secrets(1, &uuid, &share_list);
+        add_secrets(0, &CODEC, &uuid, &share_list).unwrap();
+        add_secrets(1, &CODEC, &uuid, &share_list).unwrap();
 
         //let uuid = "b3bde039-d497-4b71-9956-db12f2dddbeb".to_string();
 
-        let odo_0 = get_secret_share(0, &uuid, &uuid);
-        let odo_1 = get_secret_share(1, &uuid, &uuid);
-        let s_0 = to_bigint_arr(
-            &BASE64_STANDARD.decode(odo_0.secret_shares).unwrap(),
-            &R_INV,
-            &P,
-        );
-        let s_1 = to_bigint_arr(
-            &BASE64_STANDARD.decode(odo_1.secret_shares).unwrap(),
-            &R_INV,
-            &P,
-        );
+        let odo_0 = get_secret_share(0, &CODEC, &uuid, &uuid);
+        let odo_1 = get_secret_share(1, &CODEC, &uuid, &uuid);
+        let s_0 = CODEC.decode(&odo_0.secret_shares).unwrap();
+        let s_1 = CODEC.decode(&odo_1.secret_shares).unwrap();
 
         s_0.iter().zip(s_1).for_each(|(l, r)| {
-            let res = l.add(r).mod_floor(&P);
+            let res = l.add(r).mod_floor(&CODEC.prime);
             assert_eq!(res, secret.clone());
         });
     }
@@ -525,46 +474,128 @@ mod test {
         let uuid = "b3bde039-d497-4b71-9956-db12f2dddbec".to_string();
         let secrets = vec![BigInt::from_str("1").unwrap(),BigInt::from_str("2").unwrap(),BigInt::from_str("3").unwrap()];
         // generate input masks from both providers
-        let m_0 = generate_input_masks(0, uuid.clone(), secrets.len() as i32);
-        let m_1 = generate_input_masks(1, uuid.clone(), secrets.len() as i32);
+        let m_0 = generate_input_masks(0, &MockConfig::default(), uuid.clone(), secrets.len() as i32);
+        let m_1 = generate_input_masks(1, &MockConfig::default(), uuid.clone(), secrets.len() as i32);
 
         // encode secret with the help of input masks
-        let zipped = zipp_shares(&vec![m_0, m_1], &R_INV, &P);
+        let secret_masks = CODEC.combine(&[&m_0.secret_shares, &m_1.secret_shares]).unwrap();
         // share = secret - (r1 + r2) where r1, r2 are from the OutputDeliveryObjects
-        //let secret_shared = secret.clone().sub(zipped.secrets[0].clone()).mod_floor(&P);
+        //let secret_shared = secret.clone().sub(secret_masks[0].clone()).mod_floor(&CODEC.prime);
         let secret_shares = secrets.iter()
             .cloned()
-            .zip(zipped.secrets)
-            .map(|(s,z)| s.sub(z).mod_floor(&P))
-            .map(        |secret_share| BASE64_STANDARD.encode(to_gfp(&secret_share, &R, &P)))
+            .zip(secret_masks)
+            .map(|(s,z)| s.sub(z).mod_floor(&CODEC.prime))
+            .map(        |secret_share| BASE64_STANDARD.encode(CODEC.encode_value(&secret_share)))
             .map(|encoded  | DataObject{value: encoded})
             .collect::<Vec<DataObject>>();
-        //let encoded_share = BASE64_STANDARD.encode(to_gfp(&secret_shared, &R, &P));
+        //let encoded_share = BASE64_STANDARD.encode(CODEC.encode_value(&secret_shared));
         //let share_list = vec![DataObject {
         //    value: encoded_share,
         //}];
         // Add secrets
-        add_secrets(0, &uuid, &secret_shares);
-        add_secrets(1, &uuid, &secret_shares);
+        add_secrets(0, &CODEC, &uuid, &secret_shares).unwrap();
+        add_secrets(1, &CODEC, &uuid, &secret_shares).unwrap();
 
         //let uuid = "b3bde039-d497-4b71-9956-db12f2dddbeb".to_string();
 
-        let odo_0 = get_secret_share(0, &uuid, &uuid);
-        let odo_1 = get_secret_share(1, &uuid, &uuid);
-        let s_0 = to_bigint_arr(
-            &BASE64_STANDARD.decode(odo_0.secret_shares).unwrap(),
-            &R_INV,
-            &P,
-        );
-        let s_1 = to_bigint_arr(
-            &BASE64_STANDARD.decode(odo_1.secret_shares).unwrap(),
-            &R_INV,
-            &P,
-        );
+        let odo_0 = get_secret_share(0, &CODEC, &uuid, &uuid);
+        let odo_1 = get_secret_share(1, &CODEC, &uuid, &uuid);
+        let s_0 = CODEC.decode(&odo_0.secret_shares).unwrap();
+        let s_1 = CODEC.decode(&odo_1.secret_shares).unwrap();
 
         s_0.iter().zip(s_1).enumerate().for_each(|(i,(l, r))| {
-            let res = l.add(r).mod_floor(&P);
+            let res = l.add(r).mod_floor(&CODEC.prime);
             assert_eq!(res, secrets[i].clone());
         });
     }
+
+    #[tokio::test]
+    async fn test_native_amphora_client() {
+        let base_url = start_api(AmphoraApi::new(MockConfig::default())).await;
+        let client = cs_interface::AmphoraClient::new(
+            native_client_config(&base_url, 2),
+            cs_interface::RequestsClient::new(),
+        )
+        .unwrap();
+        let secret_id = "b3bde039-d497-4b71-9956-db12f2dddbaa".to_string();
+
+        let ids = client
+            .create_secrets(vec!["22".to_string(), "500000".to_string()], Some(secret_id.clone()))
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![secret_id.clone()]);
+
+        let secret = client.get_secret(&secret_id).await.unwrap();
+        assert_eq!(secret.values, vec![22, 500000]);
+        assert!(secret.creation_date.is_some());
+        assert!(client.list_secrets().await.unwrap().contains(&secret_id));
+
+        client.delete_secrets(vec![secret_id.clone()]).await.unwrap();
+        assert!(!client.list_secrets().await.unwrap().contains(&secret_id));
+    }
+
+    /// Shares of N VCPs add up to the secret and satisfy the MAC relations checked by the clients.
+    #[test]
+    fn test_shares_of_n_vcps() {
+        let uuid = "b3bde039-d497-4b71-9956-db12f2dddbc4".to_string();
+        let vcps = 4;
+        let config = MockConfig::new(vcps).unwrap();
+        let masks = (0..vcps).map(|vcp| generate_input_masks(vcp, &config, uuid.clone(), 2)).collect::<Vec<_>>();
+        let mask_values = CODEC.combine(&masks.iter().map(|m| m.secret_shares.as_str()).collect::<Vec<&str>>()).unwrap();
+        let data = [BigInt::from(7), BigInt::from(-3)]
+            .iter()
+            .zip(mask_values)
+            .map(|(secret, mask)| DataObject { value: CODEC.encode(&[secret.sub(mask)]) })
+            .collect::<Vec<DataObject>>();
+        for vcp in 0..vcps {
+            add_secrets(vcp, &CODEC, &uuid, &data).unwrap();
+        }
+
+        let request_id = "request-n-vcps".to_string();
+        let deliveries = (0..vcps).map(|vcp| get_secret_share(vcp, &CODEC, &uuid, &request_id)).collect::<Vec<_>>();
+        let combine = |f: fn(&OutputDeliveryObject) -> &String| {
+            CODEC.combine(&deliveries.iter().map(|d| f(d).as_str()).collect::<Vec<&str>>()).unwrap()
+        };
+        let (secrets, rs, vs, ws, us) = (
+            combine(|d| &d.secret_shares),
+            combine(|d| &d.r_shares),
+            combine(|d| &d.v_shares),
+            combine(|d| &d.w_shares),
+            combine(|d| &d.u_shares),
+        );
+        assert_eq!(secrets.iter().map(|s| CODEC.to_signed(s)).collect::<Vec<_>>(), vec![BigInt::from(7), BigInt::from(-3)]);
+        for i in 0..secrets.len() {
+            assert_eq!(CODEC.reduce(&(&secrets[i] * &rs[i])), ws[i]);
+            assert_eq!(CODEC.reduce(&(&vs[i] * &rs[i])), us[i]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_native_amphora_client_three_vcps() {
+        let base_url = start_api(AmphoraApi::new(MockConfig::new(3).unwrap())).await;
+        let client = cs_interface::AmphoraClient::new(
+            native_client_config(&base_url, 3),
+            cs_interface::RequestsClient::new(),
+        )
+        .unwrap();
+        let secret_id = "b3bde039-d497-4b71-9956-db12f2dddba3".to_string();
+        client.create_secrets(vec!["-5".to_string(), "1234".to_string()], Some(secret_id.clone())).await.unwrap();
+        assert_eq!(client.get_secret(&secret_id).await.unwrap().values, vec![-5, 1234]);
+        assert_eq!(get_secrets_internal(&secret_id)[0].len(), 3);
+
+        // a fourth vcp is not simulated by this instance
+        let res = cs_interface::NetAccess::get(&cs_interface::RequestsClient::new(), &format!("{}/3/amphora/secret-shares", base_url)).await;
+        assert!(matches!(res, Err(cs_interface::Error::HttpError { code: 404, .. })), "{:?}", res);
+    }
+
+    /// Clients using the field of the mock instance get their values back, here 2^61 - 1.
+    #[tokio::test]
+    async fn test_native_amphora_client_custom_field() {
+        let config = MockConfig::with_field(2, "2305843009213693951", "64", "36028797018963968").unwrap();
+        let base_url = start_api(AmphoraApi::new(config.clone())).await;
+        let client = cs_interface::AmphoraClient::new(config.cs_config(&base_url), cs_interface::RequestsClient::new()).unwrap();
+        let secret_id = "b3bde039-d497-4b71-9956-db12f2dddb61".to_string();
+        client.create_secrets(vec!["-42".to_string(), "1000000".to_string()], Some(secret_id.clone())).await.unwrap();
+        assert_eq!(client.get_secret(&secret_id).await.unwrap().values, vec![-42, 1000000]);
+    }
 }
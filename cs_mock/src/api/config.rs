@@ -0,0 +1,104 @@
+use std::env;
+
+use cs_interface::{parse_bigint, GfpCodec};
+
+use crate::computation::MpSpdzConfig;
+
+/// Default number of simulated virtual cloud providers (VCPs)
+pub const DEFAULT_VCPS: usize = 2;
+/// Default field of MP-SPDZ with 128 bit prime, as used by the CarbyneStack
+pub const DEFAULT_PRIME: &str = "198766463529478683931867765928436695041";
+pub const DEFAULT_R: &str = "141515903391459779531506841503331516415";
+pub const DEFAULT_RINV: &str = "133854242216446749056083838363708373830";
+
+/// Settings of a mock instance, read at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockConfig {
+    /// Number of VCPs, served under `/0/` to `/{vcps - 1}/`
+    pub vcps: usize,
+    /// Prime and Montgomery parameters of the field all VCPs compute in
+    pub codec: GfpCodec,
+    /// Programs are run with MP-SPDZ if set, otherwise evaluated in plaintext
+    pub mpspdz: Option<MpSpdzConfig>,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        MockConfig::with_field(DEFAULT_VCPS, DEFAULT_PRIME, DEFAULT_R, DEFAULT_RINV).unwrap()
+    }
+}
+
+impl MockConfig {
+    /// Config with `vcps` VCPs using the default field.
+    pub fn new(vcps: usize) -> Result<MockConfig, String> {
+        MockConfig::with_field(vcps, DEFAULT_PRIME, DEFAULT_R, DEFAULT_RINV)
+    }
+
+    /// Config with a custom field, fails unless r * rinv = 1 mod prime.
+    pub fn with_field(vcps: usize, prime: &str, r: &str, rinv: &str) -> Result<MockConfig, String> {
+        if vcps == 0 {
+            return Err("at least one VCP is required".to_string());
+        }
+        let parse = |value: &str| parse_bigint(value).map_err(|e| e.to_string());
+        let codec = GfpCodec::new(parse(prime)?, parse(r)?, parse(rinv)?).map_err(|e| e.to_string())?;
+        Ok(MockConfig { vcps, codec, mpspdz: None })
+    }
+
+    /// Read `VCP_COUNT`, the field from `FIELD_PRIME`, `FIELD_R` and `FIELD_RINV` and the MP-SPDZ settings from the environment.
+    pub fn from_env() -> Result<MockConfig, String> {
+        let vcps = match env::var("VCP_COUNT") {
+            Ok(count) => count.parse().map_err(|_| format!("invalid VCP_COUNT '{}'", count))?,
+            Err(_) => DEFAULT_VCPS,
+        };
+        let mut config = match (env::var("FIELD_PRIME"), env::var("FIELD_R"), env::var("FIELD_RINV")) {
+            (Ok(prime), Ok(r), Ok(rinv)) => MockConfig::with_field(vcps, &prime, &r, &rinv)?,
+            (Err(_), Err(_), Err(_)) => MockConfig::new(vcps)?,
+            _ => return Err("FIELD_PRIME, FIELD_R and FIELD_RINV have to be set together".to_string()),
+        };
+        config.mpspdz = MpSpdzConfig::from_env()?;
+        Ok(config)
+    }
+
+    /// The CarbyneStack config clients need to use with the VCPs served at `base_url`.
+    #[cfg(test)]
+    pub fn cs_config(&self, base_url: &str) -> cs_interface::CarbynestackConfig {
+        cs_interface::CarbynestackConfig {
+            prime: self.codec.prime.to_string(),
+            r: self.codec.r.to_string(),
+            rinv: self.codec.rinv.to_string(),
+            no_ssl_validation: true,
+            providers: (0..self.vcps)
+                .map(|vcp| cs_interface::CarbynestackProvider {
+                    id: vcp as i32 + 1,
+                    amphora_service_url: format!("{}/{}/amphora", base_url, vcp),
+                    castor_service_url: format!("{}/{}/castor", base_url, vcp),
+                    ephemeral_service_url: format!("{}/{}", base_url, vcp),
+                    base_url: format!("{}/{}", base_url, vcp),
+                })
+                .collect(),
+        }
+    }
+
+    /// Index of the VCP addressed in a request path, if it is simulated by this instance.
+    pub fn vcp(&self, vcp_nr: i32) -> poem::Result<usize> {
+        usize::try_from(vcp_nr)
+            .ok()
+            .filter(|vcp| *vcp < self.vcps)
+            .ok_or_else(|| poem::Error::from_string(format!("unknown vcp {}", vcp_nr), poem::http::StatusCode::NOT_FOUND))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_with_field() {
+        // 2^61 - 1 with the Montgomery radix 2^128
+        let config = MockConfig::with_field(3, "2305843009213693951", "64", "36028797018963968").unwrap();
+        assert_eq!(config.codec.prime.to_string(), "2305843009213693951");
+        assert!(MockConfig::with_field(3, "2305843009213693951", "64", "7").is_err());
+        assert!(MockConfig::with_field(0, DEFAULT_PRIME, DEFAULT_R, DEFAULT_RINV).is_err());
+        assert!(MockConfig::with_field(2, "abc", DEFAULT_R, DEFAULT_RINV).is_err());
+    }
+}
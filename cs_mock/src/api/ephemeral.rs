@@ -1,46 +1,23 @@
 use core::time;
-use std::env;
 
-use cs_interface::{CarbynestackConfig, CarbynestackProvider, CsClient};
-use poem::Result;
+use poem::{http::StatusCode, Result};
 use poem_openapi::{param::{Path, Query}, payload::Json, ApiResponse, Object, OpenApi};
 use tracing::{event, Level};
 
-use num_integer::Integer;
-
-use crate::{api::amphora::{delete_secret, get_secrets_internal, P}, computation::run_computation};
-fn cs_config() -> CarbynestackConfig {
-    CarbynestackConfig {
-        prime: "198766463529478683931867765928436695041".to_string(),
-        r: "141515903391459779531506841503331516415".to_string(),
-        rinv: "133854242216446749056083838363708373830".to_string(),
-        no_ssl_validation: true,
-        providers: vec![
-            CarbynestackProvider {
-                id: 1,
-                amphora_service_url: "http://127.0.0.1.sslip.io/0/amphora".to_string(),
-                castor_service_url: "http://127.0.0.1.sslip.io/0/castor".to_string(),
-                ephemeral_service_url: "http://127.0.0.1.sslip.io/0".to_string(),
-                base_url:  "http://127.0.0.1.sslip.io/0".to_string(),
-            },
-            CarbynestackProvider {
-                id: 2,
-                amphora_service_url: "http://127.0.0.1.sslip.io/1/amphora".to_string(),
-                castor_service_url: "http://127.0.0.1.sslip.io/1/castor".to_string(),
-                ephemeral_service_url: "http://127.0.0.1.sslip.io/1".to_string(),
-                base_url:  "http://127.0.0.1.sslip.io/1".to_string(),
-            }
-        ],
-    }
+use num_bigint::BigInt;
+
+use crate::{api::{amphora::{delete_secret, get_secrets_internal, store_secret}, config::MockConfig}, computation::run_computation, plaintext};
+const RESULT_UUID:&str = "00000000-0000-0000-0000-000000000000";
+pub struct EphemeralApi {
+    config: MockConfig,
 }
 
-fn use_mpc() -> bool {
-    // Check if USE_MPSPDZ environment variable is set.
-    env::var("USE_MPSPDZ").is_ok()
+impl EphemeralApi {
+    pub fn new(config: MockConfig) -> EphemeralApi {
+        EphemeralApi { config }
+    }
 }
 
-const RESULT_UUID:&str = "00000000-0000-0000-0000-000000000000";
-pub struct EphemeralApi;
 #[OpenApi]
 impl EphemeralApi {
 /// Trigger MPC function
@@ -50,30 +27,34 @@ impl EphemeralApi {
         vcp_id: Path<i32>, data: Json<StartComputationPayload>, compile: Query<bool>) -> Result<ExecuteResp> {
         
         event!(Level::INFO, "Execution request from vcp {}", vcp_id.0);
+        let vcp = self.config.vcp(vcp_id.0)?;
         let resp_obj = ComputationResponse {
             response: vec![RESULT_UUID.to_string()]
         };
         let empty_program = data.code.eq("0");
-        // Run manual computation when defined in env variables and program is not "0".
-        if vcp_id.0 == 0 && use_mpc() && compile.0 && !empty_program {
+        if vcp == 0 && !empty_program {
             // delete prev results
             delete_secret(&RESULT_UUID.to_string());
 
             event!(Level::INFO, "Request to run computation with data {:?}", data.amphora_params);
-            let mut secret_data = Vec::new();
-            for id in &data.amphora_params {
-                let shares = get_secrets_internal(id);
-                for share in shares {
-                    secret_data.push(share.0.checked_add(&share.1).unwrap().mod_floor(&P));
-                }
-            }
-            let res = run_computation(data.code.clone(), secret_data).await?;
-            let secrets = res.into_iter().map(|s| s.to_string()).collect();
-            let _ = tokio::spawn(async move {
-                let client = cs_interface::JavaCsClient::new(cs_config()).expect("Unable to create Java CS client");
-                client.create_secrets(secrets, Some(RESULT_UUID.to_string())).expect("Error creating secret!");
-            }).await;
-        } else if vcp_id.0 == 0 {
+            let codec = &self.config.codec;
+            let inputs = data
+                .amphora_params
+                .iter()
+                .flat_map(get_secrets_internal)
+                .map(|shares| codec.to_signed(&shares.iter().sum::<BigInt>()))
+                .collect::<Vec<BigInt>>();
+            // Run the program with MP-SPDZ when configured, otherwise evaluate it in plaintext.
+            let results = match &self.config.mpspdz {
+                Some(mpspdz) if compile.0 => run_computation(mpspdz, &data.code, &inputs).await?.values,
+                _ => plaintext::evaluate(&data.code, inputs).map_err(|err| {
+                    event!(Level::WARN, "Unable to evaluate the program: {}", err);
+                    poem::Error::from_string(err, StatusCode::UNPROCESSABLE_ENTITY)
+                })?,
+            };
+            event!(Level::INFO, "Computation finished with {} results", results.len());
+            store_secret(RESULT_UUID, &results, &data.game_id, &self.config);
+        } else if vcp == 0 {
             event!(Level::INFO, "Sleep 1 second to simulate MPC computation");
             tokio::time::sleep(time::Duration::from_millis(1000)).await;
         } else {
@@ -115,3 +96,75 @@ enum ExecuteResp {
     #[oai(status = 200)]
     OK(Json<ComputationResponse>)
 }
+
+#[cfg(test)]
+mod test {
+    use num_integer::Integer;
+
+    use super::*;
+    use crate::api::test_utils::{native_client_config, start_api};
+
+    /// The only test writing the result secret, the other tests execute the empty program.
+    #[tokio::test]
+    async fn test_native_ephemeral_client() {
+        let config = MockConfig::default();
+        let base_url = start_api(EphemeralApi::new(config.clone())).await;
+        let client = cs_interface::EphemeralClient::new(
+            native_client_config(&base_url, 2),
+            cs_interface::RequestsClient::new(),
+        );
+        let prime = &config.codec.prime;
+        store_secret("ephemeral-input-a", &[BigInt::from(-3)], "game", &config);
+        store_secret("ephemeral-input-b", &[BigInt::from(5)], "game", &config);
+        let program = include_str!("../../mpspdz/source/secure-comparison.mpc");
+        let execution = client
+            .execute(program.to_string(), vec!["ephemeral-input-a".to_string(), "ephemeral-input-b".to_string()])
+            .await;
+        assert!(execution.is_success(), "{:?}", execution.errors());
+        assert_eq!(execution.providers.len(), 2);
+        assert_eq!(execution.result_ids(), vec![RESULT_UUID]);
+        let results = get_secrets_internal(&RESULT_UUID.to_string())
+            .iter()
+            .map(|shares| shares.iter().sum::<BigInt>().mod_floor(prime))
+            .collect::<Vec<BigInt>>();
+        assert_eq!(results, vec![BigInt::from(1), BigInt::from(1)]);
+
+        let execution = client.execute("x = sqrt(4)".to_string(), vec![]).await;
+        assert!(!execution.is_success());
+        assert!(execution.errors()[0].contains("422"), "{:?}", execution.errors());
+    }
+
+    #[tokio::test]
+    async fn test_native_ephemeral_client_unreachable_provider() {
+        let base_url = start_api(EphemeralApi::new(MockConfig::default())).await;
+        let mut config = native_client_config(&base_url, 2);
+        config.providers[1].ephemeral_service_url = "http://127.0.0.1:1/1".to_string();
+        let client = cs_interface::EphemeralClient::new(config, cs_interface::RequestsClient::new());
+        let execution = client.execute("0".to_string(), vec![]).await;
+        assert!(!execution.is_success());
+        assert_eq!(execution.providers[0].result_ids, vec![RESULT_UUID]);
+        assert_eq!(execution.errors().len(), 1);
+    }
+
+    /// The mock takes one second per execution. Runs on a single threaded runtime
+    /// together with the mock server, so a blocking client would never finish.
+    #[tokio::test]
+    async fn test_execute_program_does_not_block_runtime() {
+        use base64::{prelude::BASE64_STANDARD, Engine};
+        use cs_interface::CsClient;
+        use std::time::{Duration, Instant};
+
+        let base_url = start_api(EphemeralApi::new(MockConfig::default())).await;
+        let client = cs_interface::NativeCsClient::new(native_client_config(&base_url, 2)).unwrap();
+        let start = Instant::now();
+        let (result, ticked) = tokio::join!(
+            client.execute_program(BASE64_STANDARD.encode("0"), vec![]),
+            async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                start.elapsed()
+            }
+        );
+        assert_eq!(result.unwrap(), vec![RESULT_UUID]);
+        assert!(ticked < Duration::from_millis(900), "runtime was blocked for {:?}", ticked);
+    }
+}
@@ -1,3 +1,29 @@
 pub mod amphora;
+pub mod config;
 pub mod ephemeral;
-mod utils;
\ No newline at end of file
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use poem::listener::{Acceptor, Listener, TcpListener};
+    use poem::{Route, Server};
+    use poem_openapi::{OpenApi, OpenApiService};
+
+    use super::config::MockConfig;
+
+    /// Start the api on a random port and return its base url.
+    pub async fn start_api<T: OpenApi + 'static>(api: T) -> String {
+        let acceptor = TcpListener::bind("127.0.0.1:0")
+            .into_acceptor()
+            .await
+            .unwrap();
+        let addr = acceptor.local_addr()[0].as_socket_addr().cloned().unwrap();
+        let app = Route::new().nest("/", OpenApiService::new(api, "", "1.0"));
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+        format!("http://{}", addr)
+    }
+
+    /// Config for the native clients with `vcps` providers served by the mock at `base_url`.
+    pub fn native_client_config(base_url: &str, vcps: usize) -> cs_interface::CarbynestackConfig {
+        MockConfig::new(vcps).unwrap().cs_config(base_url)
+    }
+}
@@ -1,63 +1,402 @@
-use std::process::Command;
+//! Runs programs with MP-SPDZ.
+//!
+//! The parties run [execute.sh](../mpspdz/execute.sh) in their own containers, sharing `Programs/Source` and
+//! `Player-Data` with the mock. For each run the program is written to `Programs/Source/{run_id}.mpc`, the run id
+//! is sent to the parties to compile and start it, and the inputs are sent with the python client
+//! `ExternalIO/client-interface.py`. Party 0 logs to `Player-Data/{run_id}-party0.log` and writes its exit
+//! code to `Player-Data/{run_id}-party0.log.done` when finished.
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
 use num_bigint::BigInt;
-use poem::Result;
+use poem::http::StatusCode;
+use tokio::{io::AsyncWriteExt, net::TcpStream, process::Command};
 use tracing::{event, Level};
 
-const PROGRAM_SOURCE_DIR:&'static str = "/root/MP-SPDZ/Programs/Source";
-
-// Run the computation
-pub async fn run_computation(program: String, data: Vec<BigInt>) -> Result<Vec<i32>> {
-    // First write the program
-    let path = std::path::Path::new(PROGRAM_SOURCE_DIR).join("program.mpc");
-    event!(Level::INFO, "Start executing computation");
-    std::fs::write(path, program).expect("Error writing program");
-
-    // Create certificates
-    match Command::new("Scripts/setup-ssl.sh").arg("2").output() {
-        Ok(output) => {
-            let stderr = String::from_utf8(output.stderr).unwrap();
-            let stdout = String::from_utf8(output.stdout).unwrap();
-            event!(Level::WARN, "stderr: {}", stderr);
-            event!(Level::WARN, "stdout: {}", stdout);
-        },
-        Err(e) => event!(Level::ERROR, "{}", e),
-    }
-    match Command::new("Scripts/setup-clients.sh").arg("1").output() {
-        Ok(output) => {
-            let stderr = String::from_utf8(output.stderr).unwrap();
-            let stdout = String::from_utf8(output.stdout).unwrap();
-            event!(Level::WARN, "stderr: {}", stderr);
-            event!(Level::WARN, "stdout: {}", stdout);
-        },
-        Err(e) => event!(Level::ERROR, "{}", e),
-    }
-
-    // Now start the clients, they wait for a ping on port 734
-    Command::new("nc").arg("-z").arg("party0").arg("734").output().expect("Error starting nc to trigger computation on party1");
-    Command::new("nc").arg("-z").arg("party1").arg("734").output().expect("Error starting nc to trigger computation on party2");
-
-    // Run the computation
-    let data = data.into_iter().map(|d| d.to_string()).collect::<Vec<String>>();
-    event!(Level::INFO, "Start computation");
-    let output = Command::new("python").arg("ExternalIO/client-interface.py").args(data.into_iter().map(|d| d.to_string())).output().expect("Expecting outputs to return stuff");
+pub const DEFAULT_MPSPDZ_DIR: &str = "/root/MP-SPDZ";
+pub const DEFAULT_PARTIES: &str = "party0,party1";
+/// Port the parties wait on for the next program to run
+pub const DEFAULT_NOTIFY_PORT: u16 = 734;
+/// Port of party 0 for client connections, party i listens on `client_port + i`
+pub const DEFAULT_CLIENT_PORT: u16 = 10000;
+pub const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+/// Settings of the MP-SPDZ integration, read at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MpSpdzConfig {
+    /// MP-SPDZ installation shared with the parties
+    pub mpspdz_dir: PathBuf,
+    /// Hosts of the parties, in party order
+    pub parties: Vec<String>,
+    pub notify_port: u16,
+    pub client_port: u16,
+    /// Command of the client sending the inputs and printing the results as json, run in `mpspdz_dir`
+    pub client: Vec<String>,
+    /// Parent of the working directories of the runs
+    pub work_dir: PathBuf,
+    /// Maximum duration of a run, including the setup
+    pub timeout: Duration,
+}
+
+impl Default for MpSpdzConfig {
+    fn default() -> Self {
+        MpSpdzConfig {
+            mpspdz_dir: PathBuf::from(DEFAULT_MPSPDZ_DIR),
+            parties: DEFAULT_PARTIES.split(',').map(String::from).collect(),
+            notify_port: DEFAULT_NOTIFY_PORT,
+            client_port: DEFAULT_CLIENT_PORT,
+            client: vec!["python".to_string(), "ExternalIO/client-interface.py".to_string()],
+            work_dir: env::temp_dir().join("cs-mock-runs"),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+        }
+    }
+}
+
+impl MpSpdzConfig {
+    /// `None` unless `USE_MPSPDZ` is set, the defaults can be changed with the `MPSPDZ_*` variables.
+    pub fn from_env() -> Result<Option<MpSpdzConfig>, String> {
+        if env::var("USE_MPSPDZ").is_err() {
+            return Ok(None);
+        }
+        let mut config = MpSpdzConfig::default();
+        if let Ok(dir) = env::var("MPSPDZ_DIR") {
+            config.mpspdz_dir = PathBuf::from(dir);
+        }
+        if let Ok(parties) = env::var("MPSPDZ_PARTIES") {
+            config.parties = parties.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+        }
+        if config.parties.is_empty() {
+            return Err("MPSPDZ_PARTIES needs at least one party".to_string());
+        }
+        let number = |name: &str| -> Result<Option<u64>, String> {
+            match env::var(name) {
+                Ok(value) => value.parse().map(Some).map_err(|_| format!("invalid {} '{}'", name, value)),
+                Err(_) => Ok(None),
+            }
+        };
+        let port = |name: &str| -> Result<Option<u16>, String> {
+            number(name)?.map(|p| u16::try_from(p).map_err(|_| format!("invalid {}", name))).transpose()
+        };
+        config.notify_port = port("MPSPDZ_NOTIFY_PORT")?.unwrap_or(config.notify_port);
+        config.client_port = port("MPSPDZ_CLIENT_PORT")?.unwrap_or(config.client_port);
+        if let Some(secs) = number("MPSPDZ_TIMEOUT_SECS")? {
+            config.timeout = Duration::from_secs(secs);
+        }
+        if let Ok(dir) = env::var("MPSPDZ_WORK_DIR") {
+            config.work_dir = PathBuf::from(dir);
+        }
+        Ok(Some(config))
+    }
+}
+
+/// Benchmark MP-SPDZ prints after a computation, including the preprocessing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Benchmark {
+    pub time_secs: f64,
+    /// Data sent by party 0
+    pub data_sent_mb: f64,
+    pub rounds: u64,
+    /// Data sent by all parties, only printed for more than one party
+    pub global_data_sent_mb: Option<f64>,
+}
+
+/// Parse all benchmarks of a party log, like
+/// ```text
+/// Time = 24.5207 seconds
+/// Data sent = 137.956 MB in ~335784 rounds (party 0 only; use '-v' for more details)
+/// Global data sent = 413.895 MB (all parties)
+/// ```
+pub fn parse_benchmarks(log: &str) -> Vec<Benchmark> {
+    let mut benchmarks = Vec::new();
+    let mut time = None;
+    for line in log.lines().map(str::trim) {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        match words.as_slice() {
+            ["Time", "=", secs, "seconds", ..] => time = secs.parse().ok(),
+            ["Data", "sent", "=", mb, "MB", "in", rounds, "rounds", ..] => {
+                let data = mb.parse().ok().zip(rounds.trim_start_matches('~').parse().ok());
+                if let (Some(time_secs), Some((data_sent_mb, rounds))) = (time.take(), data) {
+                    benchmarks.push(Benchmark { time_secs, data_sent_mb, rounds, global_data_sent_mb: None });
+                }
+            }
+            ["Global", "data", "sent", "=", mb, "MB", ..] => {
+                if let Some(benchmark) = benchmarks.last_mut() {
+                    benchmark.global_data_sent_mb = mb.parse().ok();
+                }
+            }
+            _ => {}
+        }
+    }
+    benchmarks
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputationResult {
+    pub id: String,
+    pub values: Vec<BigInt>,
+    /// Benchmarks logged by party 0, usually a single one
+    pub benchmarks: Vec<Benchmark>,
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    /// Certificates could not be created or the program not written
+    Setup(String),
+    /// A party is not reachable
+    Notify { party: String, message: String },
+    /// The client or a party failed
+    Failed { step: &'static str, message: String },
+    /// The client did not print a json array of numbers
+    InvalidOutput(String),
+    Timeout(Duration),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Setup(message) => write!(f, "MP-SPDZ setup failed: {}", message),
+            RunError::Notify { party, message } => write!(f, "unable to start the computation on {}: {}", party, message),
+            RunError::Failed { step, message } => write!(f, "{} failed: {}", step, message),
+            RunError::InvalidOutput(output) => write!(f, "invalid client output '{}'", output),
+            RunError::Timeout(timeout) => write!(f, "computation did not finish within {:?}", timeout),
+        }
+    }
+}
+
+impl From<RunError> for poem::Error {
+    fn from(err: RunError) -> Self {
+        let status = match err {
+            RunError::Notify { .. } => StatusCode::BAD_GATEWAY,
+            RunError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        poem::Error::from_string(err.to_string(), status)
+    }
+}
+
+/// Last lines of a log, for error messages.
+fn tail(log: &str) -> String {
+    let lines = log.trim_end().lines().collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(5)..].join("\n")
+}
+
+async fn run_command(program: &str, args: &[String], dir: &Path, step: &'static str) -> Result<String, RunError> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| RunError::Failed { step, message: e.to_string() })?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(RunError::Failed { step, message: format!("{}\n{}", output.status, tail(&stderr)) });
+    }
+    Ok(stdout)
+}
+
+/// Create the certificates of the parties and the client once, they are shared by all runs.
+async fn ensure_certificates(config: &MpSpdzConfig) -> Result<(), RunError> {
+    let player_data = config.mpspdz_dir.join("Player-Data");
+    let setup = |e: RunError| RunError::Setup(e.to_string());
+    if !player_data.join("P0.pem").exists() {
+        let parties = config.parties.len().to_string();
+        run_command("Scripts/setup-ssl.sh", &[parties], &config.mpspdz_dir, "setup-ssl.sh").await.map_err(setup)?;
+    }
+    if !player_data.join("C0.pem").exists() {
+        run_command("Scripts/setup-clients.sh", &["1".to_string()], &config.mpspdz_dir, "setup-clients.sh").await.map_err(setup)?;
+    }
+    Ok(())
+}
+
+/// Send the run id to a party, which then compiles and runs the program.
+async fn notify_party(config: &MpSpdzConfig, party: &str, run_id: &str) -> Result<(), RunError> {
+    let notify = |e: std::io::Error| RunError::Notify { party: party.to_string(), message: e.to_string() };
+    let mut stream = TcpStream::connect((party, config.notify_port)).await.map_err(notify)?;
+    stream.write_all(format!("{}\n", run_id).as_bytes()).await.map_err(notify)?;
+    stream.shutdown().await.map_err(notify)
+}
+
+/// Wait for party 0 to finish and return its log.
+async fn party_log(config: &MpSpdzConfig, run_id: &str) -> Result<String, RunError> {
+    let log = config.mpspdz_dir.join("Player-Data").join(format!("{}-party0.log", run_id));
+    let done = log.with_extension("log.done");
+    while !done.exists() {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    let content = fs::read_to_string(&log).unwrap_or_default();
+    let exit_code = fs::read_to_string(&done).unwrap_or_default();
+    let _ = fs::remove_file(&log);
+    let _ = fs::remove_file(&done);
+    if exit_code.trim() != "0" {
+        return Err(RunError::Failed {
+            step: "party 0",
+            message: format!("exit code {}\n{}", exit_code.trim(), tail(&content)),
+        });
+    }
+    Ok(content)
+}
+
+async fn run(config: &MpSpdzConfig, run_id: &str, run_dir: &Path, inputs: &[BigInt]) -> Result<ComputationResult, RunError> {
+    ensure_certificates(config).await?;
+    for party in &config.parties {
+        notify_party(config, party, run_id).await?;
+    }
+    let (program, args) = config.client.split_first().ok_or(RunError::Setup("no client configured".to_string()))?;
+    let mut args = args.to_vec();
+    args.extend(inputs.iter().map(|i| i.to_string()));
+    let output = Command::new(program)
+        .args(&args)
+        .current_dir(&config.mpspdz_dir)
+        .env("PARTY_HOSTS", config.parties.join(","))
+        .env("BASE_PORT", config.client_port.to_string())
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| RunError::Failed { step: "client", message: e.to_string() })?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-    if output.status.success() {
-        if !stderr.is_empty() {
-            event!(Level::ERROR, "stderr {}", stderr);
-            return Ok(vec![])
-        }
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        match serde_json::from_str::<Vec<i32>>(&stdout) {
-            Ok(data) => {
-                event!(Level::INFO, "Computation successfully finished");
-                return Ok(data)
-            },
-            Err(e) => {
-                event!(Level::ERROR, "json parse error {}", e);
-            },
-        }
-    } else {
-        event!(Level::ERROR, "Computation did not finish successfully status: {:?}\n{}", output.status.code(), stderr);
-    }
-    Ok(vec![])
+    let _ = fs::write(run_dir.join("client.log"), format!("{}\n{}", stdout, stderr));
+    if !output.status.success() {
+        return Err(RunError::Failed { step: "client", message: format!("{}\n{}", output.status, tail(&stderr)) });
+    }
+    let values = serde_json::from_str::<Vec<i128>>(stdout.trim())
+        .map_err(|_| RunError::InvalidOutput(stdout.trim().to_string()))?
+        .into_iter()
+        .map(BigInt::from)
+        .collect();
+    let log = party_log(config, run_id).await?;
+    let _ = fs::write(run_dir.join("party0.log"), &log);
+    Ok(ComputationResult { id: run_id.to_string(), values, benchmarks: parse_benchmarks(&log) })
+}
+
+/// Run a program with MP-SPDZ on the inputs, in a new working directory.
+///
+/// The working directory is removed after a successful run and kept with the logs otherwise.
+pub async fn run_computation(config: &MpSpdzConfig, program: &str, inputs: &[BigInt]) -> Result<ComputationResult, RunError> {
+    let run_id = format!("run-{:016x}", rand::random::<u64>());
+    let run_dir = config.work_dir.join(&run_id);
+    let source = config.mpspdz_dir.join("Programs/Source").join(format!("{}.mpc", run_id));
+    let write = |path: &Path| fs::write(path, program).map_err(|e| RunError::Setup(format!("{}: {}", path.display(), e)));
+    fs::create_dir_all(&run_dir).map_err(|e| RunError::Setup(e.to_string()))?;
+    write(&run_dir.join("program.mpc"))?;
+    write(&source)?;
+
+    event!(Level::INFO, "Start computation {} with {} inputs", run_id, inputs.len());
+    let res = match tokio::time::timeout(config.timeout, run(config, &run_id, &run_dir, inputs)).await {
+        Ok(res) => res,
+        Err(_) => Err(RunError::Timeout(config.timeout)),
+    };
+    let _ = fs::remove_file(&source);
+    match &res {
+        Ok(result) => {
+            event!(Level::INFO, "Computation {} finished: {:?}", run_id, result.benchmarks);
+            let _ = fs::remove_dir_all(&run_dir);
+        }
+        Err(err) => event!(Level::ERROR, "Computation {} failed, logs are kept in {}: {}", run_id, run_dir.display(), err),
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use tempdir::TempDir;
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    use super::*;
+
+    const LOG: &str = "Computing best split per node/depth 0 in MPC
+The following benchmarks are including preprocessing (offline phase).
+Time = 24.5207 seconds
+Data sent = 137.956 MB in ~335784 rounds (party 0 only; use '-v' for more details)
+Global data sent = 413.895 MB (all parties)
+
+Time = 0.5 seconds
+Data sent = 1.5 MB in ~12 rounds (party 0 only)
+";
+
+    #[test]
+    fn test_parse_benchmarks() {
+        assert_eq!(
+            parse_benchmarks(LOG),
+            vec![
+                Benchmark { time_secs: 24.5207, data_sent_mb: 137.956, rounds: 335784, global_data_sent_mb: Some(413.895) },
+                Benchmark { time_secs: 0.5, data_sent_mb: 1.5, rounds: 12, global_data_sent_mb: None },
+            ]
+        );
+        assert!(parse_benchmarks("Time = x seconds\nData sent = 1 MB in ~2 rounds").is_empty());
+    }
+
+    /// An MP-SPDZ directory with certificates, a single party on a random port and a shell script as client.
+    ///
+    /// The directory is removed when the returned `TempDir` is dropped.
+    async fn fake_mpspdz(client: &str, exit_code: &'static str) -> (TempDir, MpSpdzConfig) {
+        let temp_dir = TempDir::new("cs-mock-test").unwrap();
+        let dir = temp_dir.path().to_path_buf();
+        fs::create_dir_all(dir.join("Programs/Source")).unwrap();
+        fs::create_dir_all(dir.join("Player-Data")).unwrap();
+        fs::write(dir.join("Player-Data/P0.pem"), "").unwrap();
+        fs::write(dir.join("Player-Data/C0.pem"), "").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let notify_port = listener.local_addr().unwrap().port();
+        let player_data = dir.join("Player-Data");
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut run_id = String::new();
+            stream.read_to_string(&mut run_id).await.unwrap();
+            let log = player_data.join(format!("{}-party0.log", run_id.trim()));
+            fs::write(&log, LOG).unwrap();
+            fs::write(log.with_extension("log.done"), exit_code).unwrap();
+        });
+        let config = MpSpdzConfig {
+            work_dir: dir.join("runs"),
+            mpspdz_dir: dir,
+            parties: vec!["127.0.0.1".to_string()],
+            notify_port,
+            client: vec!["sh".to_string(), "-c".to_string(), client.to_string(), "client".to_string()],
+            timeout: Duration::from_secs(5),
+            ..MpSpdzConfig::default()
+        };
+        (temp_dir, config)
+    }
+
+    #[tokio::test]
+    async fn test_run_computation() {
+        let (_dir, config) = fake_mpspdz(r#"echo "[$1, $(($2 * 2))]""#, "0").await;
+        let result = run_computation(&config, "print_ln('test')", &[BigInt::from(-3), BigInt::from(4)]).await.unwrap();
+        assert_eq!(result.values, vec![BigInt::from(-3), BigInt::from(8)]);
+        assert_eq!(result.benchmarks.len(), 2);
+        assert_eq!(fs::read_dir(config.mpspdz_dir.join("Programs/Source")).unwrap().count(), 0);
+        assert!(!config.work_dir.join(&result.id).exists());
+    }
+
+    #[tokio::test]
+    async fn test_run_computation_errors() {
+        let (_dir, config) = fake_mpspdz("echo 'connection refused' >&2; exit 1", "0").await;
+        let err = run_computation(&config, "", &[]).await.unwrap_err();
+        assert!(matches!(&err, RunError::Failed { step: "client", message } if message.contains("connection refused")), "{}", err);
+        // the logs of failed runs are kept
+        assert_eq!(fs::read_dir(&config.work_dir).unwrap().count(), 1);
+
+        let (_dir, config) = fake_mpspdz("echo '[1]'", "1").await;
+        let err = run_computation(&config, "", &[]).await.unwrap_err();
+        assert!(matches!(err, RunError::Failed { step: "party 0", .. }), "{}", err);
+
+        let (_dir, config) = fake_mpspdz("echo 'no json'", "0").await;
+        assert!(matches!(run_computation(&config, "", &[]).await, Err(RunError::InvalidOutput(_))));
+
+        let (_dir, mut config) = fake_mpspdz("sleep 10", "0").await;
+        config.timeout = Duration::from_millis(300);
+        assert!(matches!(run_computation(&config, "", &[]).await, Err(RunError::Timeout(_))));
+
+        config.notify_port = 1;
+        assert!(matches!(run_computation(&config, "", &[]).await, Err(RunError::Notify { .. })));
+    }
 }
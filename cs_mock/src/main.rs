@@ -5,6 +5,7 @@ use std::env;
 
 mod api;
 mod computation;
+mod plaintext;
 
 #[tokio::main(worker_threads = 4)]
 async fn main() {
@@ -47,10 +48,15 @@ async fn main() {
         Err(_) => "".to_string()
     };
 
-    event!(Level::INFO, "Starting cs mock on {}:{}", addr, port);
+    let config = match api::config::MockConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => panic!("Invalid mock configuration: {}", e)
+    };
+
+    event!(Level::INFO, "Starting cs mock on {}:{} with {} VCPs", addr, port, config.vcps);
 
     let api_service =
-        OpenApiService::new((api::amphora::AmphoraApi, api::ephemeral::EphemeralApi), "Carbynestack Mock", "1.0")
+        OpenApiService::new((api::amphora::AmphoraApi::new(config.clone()), api::ephemeral::EphemeralApi::new(config)), "Carbynestack Mock", "1.0")
             .description("A mock of the CarbyneStack services, used for testing")
             .server(oas_server);
 
@@ -0,0 +1,509 @@
+//! Evaluates simple MP-SPDZ programs in the clear, so the mock returns real results without MP-SPDZ.
+//!
+//! Supported is one statement per line: assignments to names and array elements, integer arithmetic
+//! (`+ - * // %`, division rounds down; `/` is no integer division in MP-SPDZ and fails), comparisons evaluating to 0 or 1, `sum`, `len`, `mean`,
+//! `min`, `max`, `abs`, `Array(n, sint)`, `sint(x)` and the methods `reveal()` and `if_else(a, b)`.
+//! Operations on arrays are applied element-wise, so `sum(v > 10)` counts the values above 10.
+//! Inputs are read with `sint.receive_from_client(n, socket)` or `sint.read_from_socket(socket, n)`
+//! and outputs written with `sint.reveal_to_clients(clients, values)` or `sint.write_to_socket(socket, values)`.
+//! Like the client of the MP-SPDZ integration, the first value revealed to clients is the number of results.
+//! Socket handling and printing is ignored, anything else like loops fails.
+
+use std::collections::{HashMap, VecDeque};
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+pub type Result<T> = std::result::Result<T, String>;
+
+/// Functions that only set up the connection to the clients, their arguments are not evaluated.
+const IGNORED_FUNCTIONS: [&str; 8] = [
+    "listen",
+    "listen_for_clients",
+    "accept_client_connection",
+    "acceptclientconnection",
+    "closeclientconnection",
+    "print_ln",
+    "print_str",
+    "print_ln_to",
+];
+const TYPES: [&str; 3] = ["sint", "regint", "cint"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(BigInt),
+    Str,
+    Name(String),
+    Op(&'static str),
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>> {
+    const OPS: [&str; 20] = [
+        "//", "==", "!=", "<=", ">=", "<", ">", "=", "+", "-", "*", "/", "%", "(", ")", "[", "]", ",", ".", ":",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = line.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c == '#' {
+            break;
+        } else if c.is_ascii_digit() {
+            let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            tokens.push(Token::Int(rest[..end].parse().map_err(|_| format!("invalid number {}", &rest[..end]))?));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '\'' || c == '"' {
+            let end = rest[1..].find(c).ok_or("unterminated string")?;
+            tokens.push(Token::Str);
+            rest = &rest[end + 2..];
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Int(BigInt),
+    Str,
+    Name(String),
+    List(Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Method(Box<Expr>, String, Vec<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn accept(&mut self, op: &str) -> bool {
+        match self.peek() {
+            Some(Token::Op(o)) if *o == op => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if self.accept(op) { Ok(()) } else { Err(format!("expected '{}'", op)) }
+    }
+
+    fn peek_op(&self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => ops.iter().find(|o| *o == op).copied(),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let left = self.additive()?;
+        match self.peek_op(&["<", "<=", ">", ">=", "==", "!="]) {
+            Some(op) => {
+                self.pos += 1;
+                Ok(Expr::Binary(op, Box::new(left), Box::new(self.additive()?)))
+            }
+            None => Ok(left),
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr> {
+        let mut left = self.term()?;
+        while let Some(op) = self.peek_op(&["+", "-"]) {
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.peek_op(&["*", "/", "//", "%"]) {
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.accept("-") {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn args(&mut self, close: &str) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.accept(close) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            if self.accept(close) {
+                return Ok(args);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr> {
+        let mut expr = match self.next() {
+            Some(Token::Int(value)) => Expr::Int(value),
+            Some(Token::Str) => Expr::Str,
+            Some(Token::Name(name)) if self.accept("(") => Expr::Call(name, self.args(")")?),
+            Some(Token::Name(name)) => Expr::Name(name),
+            Some(Token::Op("(")) => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                expr
+            }
+            Some(Token::Op("[")) => Expr::List(self.args("]")?),
+            other => return Err(format!("unexpected {:?}", other)),
+        };
+        loop {
+            if self.accept("[") {
+                let index = self.expr()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else if self.accept(".") {
+                let Some(Token::Name(method)) = self.next() else {
+                    return Err("expected a method name".to_string());
+                };
+                self.expect("(")?;
+                expr = Expr::Method(Box::new(expr), method, self.args(")")?);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn done(&self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(format!("unexpected {:?}", token)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(BigInt),
+    Array(Vec<BigInt>),
+}
+
+impl Value {
+    fn int(self) -> Result<BigInt> {
+        match self {
+            Value::Int(value) => Ok(value),
+            Value::Array(_) => Err("expected a single value, got an array".to_string()),
+        }
+    }
+
+    fn values(self) -> Vec<BigInt> {
+        match self {
+            Value::Int(value) => vec![value],
+            Value::Array(values) => values,
+        }
+    }
+}
+
+fn apply(op: &str, left: &BigInt, right: &BigInt) -> Result<BigInt> {
+    let bool = |b: bool| BigInt::from(b as i32);
+    Ok(match op {
+        "+" => left + right,
+        "-" => left - right,
+        "*" => left * right,
+        // `/` on sint is a field or fixed point division in MP-SPDZ, rounding down would differ
+        "/" => return Err("unsupported operator /, use // for integer division".to_string()),
+        "//" | "%" if right == &BigInt::from(0) => return Err("division by zero".to_string()),
+        "//" => left.div_floor(right),
+        "%" => left.mod_floor(right),
+        "<" => bool(left < right),
+        "<=" => bool(left <= right),
+        ">" => bool(left > right),
+        ">=" => bool(left >= right),
+        "==" => bool(left == right),
+        "!=" => bool(left != right),
+        _ => return Err(format!("unsupported operator {}", op)),
+    })
+}
+
+struct Interpreter {
+    vars: HashMap<String, Value>,
+    inputs: VecDeque<BigInt>,
+    outputs: Vec<BigInt>,
+    revealed_to_clients: bool,
+}
+
+impl Interpreter {
+    fn take_inputs(&mut self, count: Value) -> Result<Value> {
+        let count = usize::try_from(count.int()?).map_err(|_| "invalid number of inputs".to_string())?;
+        if count > self.inputs.len() {
+            return Err(format!("program reads {} inputs, only {} are left", count, self.inputs.len()));
+        }
+        Ok(Value::Array(self.inputs.drain(..count).collect()))
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value> {
+        match expr {
+            Expr::Int(value) => Ok(Value::Int(value.clone())),
+            Expr::Str => Err("strings are only supported in print_ln".to_string()),
+            Expr::Name(name) => self.vars.get(name).cloned().ok_or_else(|| format!("undefined name '{}'", name)),
+            Expr::List(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.eval(item)?.int()?);
+                }
+                Ok(Value::Array(values))
+            }
+            Expr::Index(array, index) => {
+                let index = self.eval(index)?.int()?;
+                match self.eval(array)? {
+                    Value::Array(values) => usize::try_from(index.clone())
+                        .ok()
+                        .and_then(|i| values.get(i).cloned())
+                        .map(Value::Int)
+                        .ok_or_else(|| format!("index {} out of range", index)),
+                    Value::Int(_) => Err("only arrays can be indexed".to_string()),
+                }
+            }
+            Expr::Neg(value) => match self.eval(value)? {
+                Value::Int(value) => Ok(Value::Int(-value)),
+                Value::Array(values) => Ok(Value::Array(values.into_iter().map(|v| -v).collect())),
+            },
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (self.eval(left)?, self.eval(right)?);
+                match (left, right) {
+                    (Value::Int(l), Value::Int(r)) => Ok(Value::Int(apply(op, &l, &r)?)),
+                    (Value::Array(l), Value::Int(r)) => Ok(Value::Array(l.iter().map(|l| apply(op, l, &r)).collect::<Result<_>>()?)),
+                    (Value::Int(l), Value::Array(r)) => Ok(Value::Array(r.iter().map(|r| apply(op, &l, r)).collect::<Result<_>>()?)),
+                    (Value::Array(l), Value::Array(r)) if l.len() == r.len() => {
+                        Ok(Value::Array(l.iter().zip(&r).map(|(l, r)| apply(op, l, r)).collect::<Result<_>>()?))
+                    }
+                    _ => Err("arrays of different length".to_string()),
+                }
+            }
+            Expr::Call(name, args) => self.call(name, args),
+            Expr::Method(target, method, args) => self.method(target, method, args),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr]) -> Result<Value> {
+        if IGNORED_FUNCTIONS.contains(&name) {
+            return Ok(Value::Int(BigInt::from(0)));
+        }
+        if name == "Array" {
+            // the second argument is the type of the elements
+            let size = self.eval(args.first().ok_or("Array needs a size")?)?.int()?;
+            let size = usize::try_from(size).map_err(|_| "invalid array size".to_string())?;
+            return Ok(Value::Array(vec![BigInt::from(0); size]));
+        }
+        let args = args.iter().map(|a| self.eval(a)).collect::<Result<Vec<Value>>>()?;
+        let single = |args: Vec<Value>| -> Result<Value> {
+            match <[Value; 1]>::try_from(args) {
+                Ok([value]) => Ok(value),
+                Err(_) => Err(format!("{} takes a single argument", name)),
+            }
+        };
+        // min and max take an array or several values
+        let flatten = |args: Vec<Value>| -> Vec<BigInt> { args.into_iter().flat_map(Value::values).collect() };
+        match name {
+            _ if TYPES.contains(&name) => Ok(Value::Int(args.into_iter().next().map(Value::int).transpose()?.unwrap_or_default())),
+            "sum" => Ok(Value::Int(single(args)?.values().iter().sum())),
+            "len" => Ok(Value::Int(BigInt::from(single(args)?.values().len()))),
+            "abs" => Ok(Value::Int(BigInt::from(single(args)?.int()?.magnitude().clone()))),
+            "mean" => {
+                let values = single(args)?.values();
+                if values.is_empty() {
+                    return Err("mean of no values".to_string());
+                }
+                Ok(Value::Int(values.iter().sum::<BigInt>().div_floor(&BigInt::from(values.len()))))
+            }
+            "min" => flatten(args).into_iter().min().map(Value::Int).ok_or("min of no values".to_string()),
+            "max" => flatten(args).into_iter().max().map(Value::Int).ok_or("max of no values".to_string()),
+            _ => Err(format!("unsupported function '{}'", name)),
+        }
+    }
+
+    fn method(&mut self, target: &Expr, method: &str, args: &[Expr]) -> Result<Value> {
+        let class_method = match target {
+            Expr::Name(name) if TYPES.contains(&name.as_str()) && !self.vars.contains_key(name) => Some(name),
+            _ => None,
+        };
+        if let Some(name) = class_method {
+            let mut args = args.iter().map(|a| self.eval(a)).collect::<Result<Vec<Value>>>()?.into_iter();
+            return match method {
+                "receive_from_client" => self.take_inputs(args.next().ok_or("missing number of inputs")?),
+                "read_from_socket" => self.take_inputs(args.nth(1).unwrap_or(Value::Int(BigInt::from(1)))),
+                "write_to_socket" | "reveal_to_clients" => {
+                    self.revealed_to_clients |= method == "reveal_to_clients";
+                    self.outputs.extend(args.nth(1).ok_or("missing output values")?.values());
+                    Ok(Value::Int(BigInt::from(0)))
+                }
+                "Array" => self.call("Array", &[Expr::Int(args.next().ok_or("Array needs a size")?.int()?)]),
+                _ => Err(format!("unsupported method {}.{}", name, method)),
+            };
+        }
+        let value = self.eval(target)?;
+        match (method, args) {
+            ("reveal" | "get_vector", []) => Ok(value),
+            ("if_else", [if_true, if_false]) => {
+                if value.int()? != BigInt::from(0) { self.eval(if_true) } else { self.eval(if_false) }
+            }
+            _ => Err(format!("unsupported method {}", method)),
+        }
+    }
+
+    fn statement(&mut self, tokens: Vec<Token>) -> Result<()> {
+        if tokens.last() == Some(&Token::Op(":")) {
+            return Err("blocks like if, for and def are not supported".to_string());
+        }
+        let assignment = tokens.iter().position(|t| t == &Token::Op("="));
+        let mut parser = Parser { tokens, pos: 0 };
+        let Some(position) = assignment else {
+            let expr = parser.expr()?;
+            parser.done()?;
+            return self.eval(&expr).map(|_| ());
+        };
+        let target = parser.postfix()?;
+        if parser.pos != position {
+            return Err("invalid assignment".to_string());
+        }
+        parser.pos += 1;
+        let expr = parser.expr()?;
+        parser.done()?;
+        let value = self.eval(&expr)?;
+        match target {
+            Expr::Name(name) => {
+                self.vars.insert(name, value);
+                Ok(())
+            }
+            Expr::Index(array, index) => {
+                let Expr::Name(name) = *array else {
+                    return Err("invalid assignment".to_string());
+                };
+                let index = self.eval(&index)?.int()?;
+                match self.vars.get_mut(&name) {
+                    Some(Value::Array(values)) => {
+                        let slot = usize::try_from(index.clone()).ok().and_then(|i| values.get_mut(i));
+                        *slot.ok_or_else(|| format!("index {} out of range", index))? = value.int()?;
+                        Ok(())
+                    }
+                    _ => Err(format!("'{}' is not an array", name)),
+                }
+            }
+            _ => Err("invalid assignment".to_string()),
+        }
+    }
+}
+
+/// Run the program on the signed input values and return the results.
+pub fn evaluate(program: &str, inputs: Vec<BigInt>) -> Result<Vec<BigInt>> {
+    let mut interpreter = Interpreter {
+        vars: HashMap::new(),
+        inputs: inputs.into(),
+        outputs: Vec::new(),
+        revealed_to_clients: false,
+    };
+    for (number, line) in program.lines().enumerate() {
+        let tokens = tokenize(line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        if tokens.is_empty() {
+            continue;
+        }
+        interpreter.statement(tokens).map_err(|e| format!("line {}: {}", number + 1, e))?;
+    }
+    if !interpreter.revealed_to_clients {
+        return Ok(interpreter.outputs);
+    }
+    let mut outputs = interpreter.outputs.into_iter();
+    let count = outputs.next().and_then(|c| usize::try_from(c).ok()).ok_or("no result count revealed to the clients")?;
+    let results = outputs.collect::<Vec<BigInt>>();
+    if results.len() != count {
+        return Err(format!("revealed {} results, announced {}", results.len(), count));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(program: &str, inputs: &[i64]) -> Result<Vec<i64>> {
+        let results = evaluate(program, inputs.iter().map(|i| BigInt::from(*i)).collect())?;
+        Ok(results.into_iter().map(|r| i64::try_from(r).unwrap()).collect())
+    }
+
+    #[test]
+    fn test_comparison_program() {
+        let program = include_str!("../mpspdz/source/secure-comparison.mpc");
+        assert_eq!(run(program, &[3, 5]), Ok(vec![1, 1]));
+        assert_eq!(run(program, &[5, 3]), Ok(vec![0, 0]));
+        assert!(run(program, &[5]).unwrap_err().contains("only 1 are left"));
+    }
+
+    #[test]
+    fn test_socket_program() {
+        let program = include_str!("../../smoketesting/mpc_program.mpc");
+        assert_eq!(run(program, &[-42]), Ok(vec![-42]));
+    }
+
+    #[test]
+    fn test_statistics() {
+        let program = r#"
+            listen_for_clients(PORTNUM)
+            socket_id = accept_client_connection(PORTNUM)
+            v = sint.receive_from_client(5, socket_id)
+            stats = Array(7, sint)
+            stats[0] = sum(v)
+            stats[1] = mean(v)
+            stats[2] = len(v)
+            stats[3] = min(v)
+            stats[4] = max(v[0], v[1] * 2, -(v[2] - 1))
+            stats[5] = sum(v > 3)   # count values above 3
+            stats[6] = (v[0] < v[1]).if_else(v[3] // 2, 100 % 7)
+            sint.reveal_to_clients([socket_id], sint(7))
+            sint.reveal_to_clients([socket_id], stats)
+        "#;
+        assert_eq!(run(program, &[4, 1, -7, 9, 2]), Ok(vec![9, 1, 5, -7, 8, 2, 2]));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        for (program, error) in [
+            ("for i in range(3):", "line 1: blocks"),
+            ("x = y + 1", "line 1: undefined name 'y'"),
+            ("x = sqrt(4)", "unsupported function"),
+            ("x = 1 // 0", "division by zero"),
+            ("x = 7 / 2", "line 1: unsupported operator /"),
+            ("x = 1.5", "expected a method name"),
+            ("sint.reveal_to_clients([0], sint(2))\nsint.reveal_to_clients([0], sint(1))", "revealed 1 results, announced 2"),
+        ] {
+            let res = run(program, &[]);
+            assert!(res.as_ref().is_err_and(|e| e.contains(error)), "{}: {:?}", program, res);
+        }
+    }
+}
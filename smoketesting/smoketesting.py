@@ -38,7 +38,7 @@ if __name__ == '__main__':
     fields={
         "name": "smoketesting",
         "number_of_parties": "1",
-        "csv_header_line": "data",
+        "csv_schema": '{"columns":[{"name":"data","type":"int"}]}',
         "mpc_program": ("mpc_program.mpc", open("mpc_program.mpc", 'rb'), 'text/plain'),
         "cs_config": ("cs_config", open("csconfig", 'rb'), 'text/plain')
     }